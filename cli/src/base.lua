@@ -20,8 +20,4 @@ do
 	end
 end
 _modules = {§}
-if _modules["main"] then
-	return import("main")
-else
-	error("File \"main.clue\" was not found!")
-end
\ No newline at end of file
+--ENTRY
\ No newline at end of file
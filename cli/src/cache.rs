@@ -0,0 +1,246 @@
+use ahash::AHashSet;
+use clue_core::code::Code;
+use clue_core::env::Options;
+use clue_core::preprocessor::{PPCode, PPVar, PPVars};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The directory where the compiled files are cached by default
+pub const CACHE_DIR: &str = ".clue-cache";
+
+/// The compiled code of the files of a directory, stored on disk so that the next builds
+/// only compile again the files that changed or that use preprocessor variables that changed.
+/// Only the compilation is cached, every file is still preprocessed to find the variables it exports.
+/// Every build of a directory with the same options and indentation has its own subdirectory,
+/// where each entry is keyed by the hash of the preprocessed code of the file and the values of the variables it uses
+pub struct Cache {
+	dir: PathBuf,
+	used: Mutex<AHashSet<String>>,
+}
+
+/// Feeds a string to the hasher after its length, so that two strings cannot be mistaken for one
+fn hash_str(hasher: &mut Sha256, string: &str) {
+	hash_number(hasher, string.len());
+	hasher.update(string);
+}
+
+fn hash_number(hasher: &mut Sha256, number: usize) {
+	hasher.update((number as u64).to_le_bytes());
+}
+
+fn finish(hasher: Sha256) -> String {
+	hasher.finalize()[..8]
+		.iter()
+		.map(|byte| format!("{byte:02x}"))
+		.collect()
+}
+
+/// Adds the names of the variables used with `$` in the code to `names`
+fn find_variables(code: &Code, names: &mut Vec<String>) {
+	let mut chars = code.into_iter().map(|(c, ..)| *c).peekable();
+	while let Some(c) = chars.next() {
+		if c != b'$' {
+			continue;
+		}
+		let mut name = String::new();
+		while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == b'_') {
+			name.push(c as char);
+		}
+		if !name.is_empty() {
+			names.push(name);
+		}
+	}
+}
+
+fn find_codes_variables(codes: &PPCode, names: &mut Vec<String>) {
+	for (code, uses_vars) in &codes.0 {
+		if *uses_vars {
+			find_variables(code, names)
+		}
+	}
+}
+
+/// Feeds the options that change the output to the hasher,
+/// the ones that only print more information are left out
+fn hash_options(hasher: &mut Sha256, options: &Options) {
+	let options = Options {
+		env_tokens: false,
+		env_struct: false,
+		env_output: false,
+		env_expand: false,
+		env_symbols: false,
+		env_color: false,
+		..options.clone()
+	};
+	hash_str(hasher, &format!("{options:?}"));
+}
+
+impl Cache {
+	/// Opens the cache of the directory `input` compiled with the given options and indentation level,
+	/// stored inside `dir`. It is created when the first entry is written
+	pub fn new(dir: impl AsRef<Path>, input: &Path, scope: usize, options: &Options) -> Self {
+		let input = input.canonicalize().unwrap_or_else(|_| input.to_path_buf());
+		let mut hasher = Sha256::new();
+		hash_str(&mut hasher, env!("CARGO_PKG_VERSION"));
+		hash_options(&mut hasher, options);
+		hash_str(&mut hasher, &input.to_string_lossy());
+		hash_number(&mut hasher, scope);
+		Self {
+			dir: dir.as_ref().join(finish(hasher)),
+			used: Mutex::new(AHashSet::new()),
+		}
+	}
+
+	/// Returns the key of a file given its name, its preprocessed code, every preprocessor variable
+	/// and its options, which are not the ones of the cache if the file is inside a directory with its own options
+	pub fn key(
+		&self,
+		realname: &str,
+		codes: &PPCode,
+		variables: &PPVars,
+		options: &Options,
+	) -> String {
+		let mut hasher = Sha256::new();
+		hash_str(&mut hasher, realname);
+		hash_options(&mut hasher, options);
+		for (code, uses_vars) in &codes.0 {
+			hasher.update([*uses_vars as u8]);
+			hash_number(&mut hasher, code.len());
+			for (c, line, column) in code {
+				hasher.update([*c]);
+				hash_number(&mut hasher, *line);
+				hash_number(&mut hasher, *column);
+			}
+		}
+		// the values of the variables can use other variables too
+		let mut names = Vec::new();
+		let mut visited = AHashSet::new();
+		find_codes_variables(codes, &mut names);
+		while let Some(name) = names.pop() {
+			if !visited.insert(name.clone()) {
+				continue;
+			}
+			hash_str(&mut hasher, &name);
+			if let Ok(value) = env::var(&name) {
+				hash_str(&mut hasher, &value);
+				continue;
+			}
+			let Some(value) = variables.get(&Code::from((name.as_bytes(), 0, 0))) else {
+				continue;
+			};
+			hash_str(&mut hasher, &format!("{value:?}"));
+			match value {
+				PPVar::Simple(_) => {}
+				PPVar::ToProcess(code) => find_variables(code, &mut names),
+				PPVar::Macro { code, ppvars, .. } => {
+					find_codes_variables(code, &mut names);
+					for value in ppvars.values() {
+						if let PPVar::ToProcess(code) = value {
+							find_variables(code, &mut names)
+						}
+					}
+				}
+				PPVar::VarArgs(code) => find_codes_variables(code, &mut names),
+			}
+		}
+		finish(hasher)
+	}
+
+	/// Returns the compiled code and the statics stored with the given key, if there are any
+	pub fn get(&self, key: &str) -> Option<(String, String)> {
+		let entry = fs::read_to_string(self.dir.join(key)).ok()?;
+		let (len, entry) = entry.split_once('\n')?;
+		let len = len.parse().ok()?;
+		if !entry.is_char_boundary(len) {
+			return None;
+		}
+		let (statics, output) = entry.split_at(len);
+		self.used.lock().unwrap().insert(key.to_owned());
+		Some((output.to_owned(), statics.to_owned()))
+	}
+
+	/// Stores the compiled code and the statics of a file with the given key
+	pub fn insert(&self, key: &str, output: &str, statics: &str) -> std::io::Result<()> {
+		fs::create_dir_all(&self.dir)?;
+		let entry = format!("{}\n{statics}{output}", statics.len());
+		fs::write(self.dir.join(key), entry)?;
+		self.used.lock().unwrap().insert(key.to_owned());
+		Ok(())
+	}
+
+	/// Deletes the entries that were not used since the cache was opened,
+	/// the builds of other directories or with other options are kept
+	pub fn prune(&self) -> std::io::Result<()> {
+		let Ok(entries) = fs::read_dir(&self.dir) else {
+			return Ok(());
+		};
+		let used = self.used.lock().unwrap();
+		for entry in entries {
+			let entry = entry?;
+			if !used.contains(entry.file_name().to_string_lossy().as_ref()) {
+				fs::remove_file(entry.path())?;
+			}
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clue_core::preprocessor::preprocess_code;
+
+	fn key(cache: &Cache, code: &str) -> String {
+		let filename = String::from("main.clue");
+		let mut code = code.to_owned();
+		let (codes, variables, ..) = preprocess_code(
+			unsafe { code.as_bytes_mut() },
+			1,
+			false,
+			&filename,
+			&Options::default(),
+		)
+		.unwrap();
+		cache.key("main", &codes, &variables, &Options::default())
+	}
+
+	#[test]
+	fn keys() {
+		let cache = Cache::new(CACHE_DIR, Path::new("src"), 2, &Options::default());
+		let a = key(&cache, "@define X 1\n@define Y 2\nprint($X)");
+		assert_eq!(a, key(&cache, "@define X 1\n@define Y 3\nprint($X)"));
+		assert_ne!(a, key(&cache, "@define X 2\n@define Y 2\nprint($X)"));
+		assert_ne!(a, key(&cache, "@define X 1\n@define Y 2\nprint( $X)"));
+		let debug = Cache::new(
+			CACHE_DIR,
+			Path::new("src"),
+			2,
+			&Options {
+				env_debug: true,
+				..Options::default()
+			},
+		);
+		assert_ne!(cache.dir, debug.dir);
+		let printing = Cache::new(
+			CACHE_DIR,
+			Path::new("src"),
+			2,
+			&Options {
+				env_output: true,
+				..Options::default()
+			},
+		);
+		assert_eq!(cache.dir, printing.dir);
+		assert_ne!(
+			cache.dir,
+			Cache::new(CACHE_DIR, Path::new("src"), 0, &Options::default()).dir
+		);
+		assert_ne!(
+			cache.dir,
+			Cache::new(CACHE_DIR, Path::new("tests"), 2, &Options::default()).dir
+		);
+	}
+}
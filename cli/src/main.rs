@@ -1,32 +1,405 @@
 #![allow(clippy::blocks_in_conditions)]
 
-use clap::{crate_version, Parser};
+use cache::{Cache, CACHE_DIR};
+use clap::{crate_version, Args, Parser, Subcommand, ValueEnum};
 use clue_core::{
 	check,
 	compiler::*,
-	env::{BitwiseMode, ContinueMode, LuaVersion, Options},
+	diagnostic::Diagnostic,
+	env::{BitwiseMode, ContinueMode, LuaSTD, LuaVersion, Options, TypesMode},
 	format_clue,
+	formatter::format_code,
+	lint::{lint_code, LintRules, Rule},
 	parser::*,
 	preprocessor::*,
 	scanner::*,
+	sourcemap::{line_columns, SourceMap},
 };
-use std::{fs, path::PathBuf, time::Instant};
-use threads::compile_folder;
+use manifest::{Manifest, ManifestOptions, MANIFEST_NAME};
+#[cfg(feature = "mlua")]
+use repl::Repl;
+use std::{
+	fs,
+	io::{self, IsTerminal, Read},
+	path::{Path, PathBuf},
+	time::Instant,
+};
+#[cfg(feature = "mlua")]
+use test_runner::run_tests;
+use threads::{bundle, compile_folder, find_files, FolderOptions, Module};
+use watch::Watcher;
+
+#[cfg(feature = "lsp")]
+use clue_core::symbols::SymbolTable;
 
 #[cfg(feature = "rpmalloc")]
 #[global_allocator]
 /// The best memory allocator available for Clue
 static ALLOC: rpmalloc::RpMalloc = rpmalloc::RpMalloc;
 
+mod cache;
+mod manifest;
+#[cfg(feature = "mlua")]
+mod repl;
+#[cfg(feature = "mlua")]
+mod test_runner;
 mod threads;
+mod watch;
+
+#[derive(Default, Copy, Clone, PartialEq, Eq, ValueEnum)]
+/// When to use colors in error messages
+enum ColorMode {
+	#[default]
+	/// Use colors only if the errors are printed to a terminal and NO_COLOR is not set
+	Auto,
+
+	/// Always use colors
+	Always,
+
+	/// Never use colors
+	Never,
+}
+
+impl ColorMode {
+	fn enabled(self) -> bool {
+		match self {
+			ColorMode::Auto => io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+			ColorMode::Always => true,
+			ColorMode::Never => false,
+		}
+	}
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+/// How the compiled files of a directory are saved
+pub enum Layout {
+	#[default]
+	/// A single Lua file containing every file, which load each other with `import`
+	Bundle,
+
+	/// A Lua file for every file inside the output directory, in the same directories as the Clue files,
+	/// which load each other with `require`
+	Modules,
+
+	/// A single Lua file that adds every file to `package.preload`, which load each other with `require`
+	Preload,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+/// What `clue dump` prints
+enum DumpKind {
+	/// The tokens of the files
+	Tokens,
+
+	/// The syntax structure of the files
+	Ast,
+
+	/// The files after the preprocessor ran
+	Preprocessed,
+
+	#[cfg(feature = "lsp")]
+	/// The symbol table of the files as JSON
+	Symbols,
+}
+
+#[derive(Subcommand)]
+enum Command {
+	/// Compile a directory or a single *.clue file
+	Build {
+		/// The path to the directory where the *.clue files are located.
+		/// Every directory inside the given directory will be checked too.
+		/// If the path points to a single *.clue file, only that file will be compiled.
+		#[clap(default_value = ".")]
+		path: PathBuf,
+
+		/// The name the output file will have
+		/// [default for compiling a directory: main]
+		/// [default for compiling a single file: that file's name]
+		#[clap(value_name = "OUTPUT FILE NAME")]
+		outputname: Option<PathBuf>,
+
+		#[clap(flatten)]
+		build: BuildArgs,
+
+		#[clap(flatten)]
+		compile: CompileArgs,
+	},
+
+	/// Check a directory or a single *.clue file for errors without compiling it
+	Check {
+		/// The directory or the file to check
+		#[clap(default_value = ".")]
+		path: PathBuf,
+
+		#[clap(flatten)]
+		compile: CompileArgs,
+	},
+
+	#[cfg(feature = "mlua")]
+	/// Compile a directory or a single *.clue file and run it without saving it
+	Run {
+		/// The directory or the file to run
+		#[clap(default_value = ".")]
+		path: PathBuf,
+
+		#[clap(flatten)]
+		build: BuildArgs,
+
+		#[clap(flatten)]
+		compile: CompileArgs,
+	},
+
+	#[cfg(feature = "mlua")]
+	/// Start an interactive session that runs Clue code line by line
+	/// and prints the values of the expressions
+	Repl {
+		#[clap(flatten)]
+		compile: CompileArgs,
+	},
+
+	#[cfg(feature = "mlua")]
+	/// Run the tests of a directory. Every *_test.clue file must return a table of functions,
+	/// each function is a test that fails if it raises an error
+	Test {
+		/// The directory of the project
+		#[clap(default_value = ".")]
+		path: PathBuf,
+
+		#[clap(flatten)]
+		compile: CompileArgs,
+	},
+
+	/// Compile the given Clue code and print the output Lua code
+	Eval {
+		/// The Clue code to compile
+		code: String,
+
+		/// Save the output Lua code in the given file instead of printing it
+		#[clap(value_name = "OUTPUT FILE NAME")]
+		outputname: Option<PathBuf>,
+
+		#[cfg(feature = "mlua")]
+		/// Execute the output Lua code once it's compiled
+		#[clap(short, long)]
+		execute: bool,
+
+		#[clap(flatten)]
+		compile: CompileArgs,
+	},
+
+	/// Print what the compiler sees at one of its stages
+	Dump {
+		/// What to print
+		#[clap(value_enum, ignore_case(true))]
+		what: DumpKind,
+
+		/// The directory or the file to print
+		#[clap(default_value = ".")]
+		path: PathBuf,
+
+		#[clap(flatten)]
+		compile: CompileArgs,
+	},
+
+	/// Create a new project with a clue.toml manifest and a main.clue file
+	New {
+		/// The directory of the project, it must not exist or be empty
+		path: PathBuf,
+	},
+
+	/// Rewrite the locations inside a Lua error message or stack traceback
+	/// so that they point to the original *.clue files, using a source map
+	/// generated with --sourcemap
+	Trace {
+		/// The compiled Lua file or its source map (*.lua.map)
+		#[clap(value_name = "LUA OR MAP FILE")]
+		map: PathBuf,
+
+		/// The file containing the stack traceback [default: read it from stdin]
+		#[clap(value_name = "TRACE FILE")]
+		trace: Option<PathBuf>,
+	},
 
-#[derive(Parser)]
+	/// Format *.clue files in place
+	Fmt {
+		/// The files or directories to format [default: the current directory]
+		#[clap(value_name = "PATHS")]
+		paths: Vec<PathBuf>,
+
+		/// Do not change the files, only list the ones that are not formatted
+		/// and fail if there are any
+		#[clap(long)]
+		check: bool,
+	},
+
+	/// Check *.clue files for common mistakes.
+	/// The rules can be configured in a .cluelint file in the current directory or in one of its parents,
+	/// or for a single line with a "// lint: allow(RULE, ...)" comment
+	Lint {
+		/// The files or directories to check [default: the current directory]
+		#[clap(value_name = "PATHS")]
+		paths: Vec<PathBuf>,
+
+		/// Disable a rule, can be used more than once
+		#[clap(long, value_enum, value_name = "RULE")]
+		allow: Vec<Rule>,
+
+		/// Print the warnings to stdout as a JSON array
+		#[clap(long)]
+		json: bool,
+	},
+}
+
+#[derive(Args, Default)]
+/// The flags that change how the output is saved
+struct BuildArgs {
+	/// Write a source map mapping the output Lua lines to the Clue files next to the output file
+	#[clap(long, overrides_with = "no_sourcemap")]
+	sourcemap: bool,
+
+	/// Don't write a source map, even if the manifest enables it
+	#[clap(long, overrides_with = "sourcemap")]
+	no_sourcemap: bool,
+
+	/// Keep running and compile again every time the *.clue files,
+	/// the custom base or the imported Lua modules change
+	#[clap(short, long)]
+	watch: bool,
+
+	/// Compile every file of the directory again instead of reusing the ones
+	/// cached in .clue-cache that did not change
+	#[clap(long)]
+	no_cache: bool,
+
+	/// Use a custom Lua file as base for compiling the directory, only for the bundle layout
+	#[clap(short = 'B', long, value_name = "FILE NAME")]
+	base: Option<String>,
+
+	/// How the compiled files of a directory are saved,
+	/// with modules the output file name is the output directory [default: bundle]
+	#[clap(long, value_enum, ignore_case(true), value_name = "LAYOUT")]
+	layout: Option<Layout>,
+
+	/// The module the bundle runs, like "main" for main.clue or "foo.bar" for foo/bar.clue [default: main]
+	#[clap(long, value_name = "MODULE")]
+	entry: Option<String>,
+
+	/// Make a bundle that can be loaded with require: it returns what the module given with --entry returns,
+	/// or a table that imports any module when it is indexed if no entry is given
+	#[clap(long, overrides_with = "no_library")]
+	library: bool,
+
+	/// Don't make a library, even if the manifest enables it
+	#[clap(long, overrides_with = "library")]
+	no_library: bool,
+
+	/// Add the *.lua files of the directory to the output as they are,
+	/// unless there is a *.clue file with the same name. Every file can then be loaded with require too
+	#[clap(long, overrides_with = "no_include_lua")]
+	include_lua: bool,
+
+	/// Don't add the *.lua files of the directory, even if the manifest enables it
+	#[clap(long, overrides_with = "include_lua")]
+	no_include_lua: bool,
+}
+
+#[derive(Args, Default, Clone)]
+/// The flags that change how the code is compiled
+struct CompileArgs {
+	/// Use LuaJIT's bit library for bitwise operations
+	#[clap(
+		short,
+		long,
+		hide(true),
+		default_missing_value = "bit",
+		value_name = "VAR NAME"
+	)]
+	jitbit: Option<String>,
+
+	/// Change the way bitwise operators are compiled [default: Clue]
+	#[clap(short, long, value_enum, ignore_case(true), value_name = "MODE")]
+	bitwise: Option<BitwiseMode>,
+
+	/// Change the way continue identifiers are compiled [default: simple]
+	#[clap(short, long, value_enum, ignore_case(true), value_name = "MODE")]
+	r#continue: Option<ContinueMode>,
+
+	/// Use rawset to create globals
+	#[clap(short, long, overrides_with = "no_rawsetglobals")]
+	rawsetglobals: bool,
+
+	/// Don't use rawset to create globals, even if the manifest enables it
+	#[clap(long, overrides_with = "rawsetglobals")]
+	no_rawsetglobals: bool,
+
+	/// Add debug information in output (might slow down runtime)
+	#[clap(short, long, overrides_with = "no_debug")]
+	debug: bool,
+
+	/// Don't add debug information, even if the manifest enables it
+	#[clap(long, overrides_with = "debug")]
+	no_debug: bool,
+
+	/// Uses preset configuration based on the targeted Lua version
+	#[clap(
+		short,
+		long,
+		value_enum,
+		ignore_case(true),
+		conflicts_with("bitwise"),
+		conflicts_with("jitbit"),
+		conflicts_with("continue"),
+		value_name = "LUA VERSION"
+	)]
+	target: Option<LuaVersion>,
+
+	/// When to use colors in error messages
+	#[clap(
+		long,
+		value_enum,
+		ignore_case(true),
+		default_value = "auto",
+		value_name = "WHEN"
+	)]
+	color: ColorMode,
+
+	/// Change OS checked by @ifos [default: the current OS]
+	#[clap(long, value_name = "TARGET OS")]
+	targetos: Option<String>,
+
+	/// Enable type checking (might slow down compilation) [default: none]
+	#[clap(short = 'T', long, value_enum, ignore_case(true), value_name = "MODE")]
+	types: Option<TypesMode>,
+
+	/// Use the given Lua version's standard library (--types required) [default: luajit]
+	#[clap(
+		long,
+		value_enum,
+		ignore_case(true),
+		value_name = "LUA VERSION",
+		requires = "types"
+	)]
+	std: Option<LuaSTD>,
+
+	/// Use the options of the given profile of the clue.toml manifest
+	#[clap(long, value_name = "NAME")]
+	profile: Option<String>,
+}
+
+#[derive(Parser, Default)]
 #[clap(
 	version,
+	args_conflicts_with_subcommands = true,
+	subcommand_negates_reqs = true,
 	about = "C/Rust like programming language that compiles into Lua code\nMade by Maiori\nhttps://github.com/ClueLang/Clue",
 	long_about = None
 )]
+/// The command line, the flags given without a subcommand are kept for compatibility
+/// and every subcommand that compiles code is turned into them
 struct Cli {
+	#[clap(subcommand)]
+	command: Option<Command>,
+
 	/// The path to the directory where the *.clue files are located.
 	/// Every directory inside the given directory will be checked too.
 	/// If the path points to a single *.clue file, only that file will be compiled.
@@ -59,140 +432,303 @@ struct Cli {
 	#[clap(short = 'E', long)]
 	expand: bool,
 
-	/// Use LuaJIT's bit library for bitwise operations
-	#[clap(
-		short,
-		long,
-		hide(true),
-		default_missing_value = "bit",
-		value_name = "VAR NAME"
-	)]
-	jitbit: Option<String>,
-
-	/// Change the way bitwise operators are compiled
-	#[clap(
-		short,
-		long,
-		value_enum,
-		ignore_case(true),
-		default_value = "Clue",
-		value_name = "MODE"
-	)]
-	bitwise: BitwiseMode,
-
-	/// Change the way continue identifiers are compiled
-	#[clap(
-		short,
-		long,
-		value_enum,
-		ignore_case(true),
-		default_value = "simple",
-		value_name = "MODE"
-	)]
-	r#continue: ContinueMode,
-
 	/// Don't save compiled code
 	#[clap(short = 'D', long)]
 	dontsave: bool,
 
 	/// Treat PATH not as a path but as Clue code
-	#[clap(short, long)]
+	#[clap(short, long, conflicts_with = "watch")]
 	pathiscode: bool,
 
-	/// Use rawset to create globals
-	#[clap(short, long)]
-	rawsetglobals: bool,
+	#[clap(flatten)]
+	build: BuildArgs,
 
-	/// Add debug information in output (might slow down runtime)
-	#[clap(short, long)]
-	debug: bool,
+	#[clap(flatten)]
+	compile: CompileArgs,
 
-	/// Use a custom Lua file as base for compiling the directory
-	#[clap(short = 'B', long, value_name = "FILE NAME")]
-	base: Option<String>,
-
-	/// Uses preset configuration based on the targeted Lua version
-	#[clap(
-		short,
-		long,
-		value_enum,
-		ignore_case(true),
-		conflicts_with("bitwise"),
-		conflicts_with("jitbit"),
-		conflicts_with("continue"),
-		value_name = "LUA VERSION"
-	)]
-	target: Option<LuaVersion>,
-
-	/// Change OS checked by @ifos
-	#[clap(long, default_value = std::env::consts::OS, value_name = "TARGET OS")]
-	targetos: String,
-	/*/// This is not yet supported (Coming out in 4.0)
-	#[clap(short, long, value_name = "MODE")]
-	types: Option<String>,*/
-
-	/*	/// Enable type checking (might slow down compilation)
-		#[clap(
-			short,
-			long,
-			value_enum,
-			default_value = "none",
-			value_name = "MODE"
-		)]
-		types: TypesMode,
-
-		/// Use the given Lua version's standard library (--types required)
-		#[clap(
-			long,
-			value_enum,
-			default_value = "luajit",
-			value_name = "LUA VERSION",
-			requires = "types"
-		)]
-		std: LuaSTD,
-	*/
 	#[cfg(feature = "mlua")]
 	/// Execute the output Lua code once it's compiled
 	#[clap(short, long)]
 	execute: bool,
 
 	#[cfg(feature = "lsp")]
-	/// Print the symbol table of the compiled files
-	#[clap(long, hide(true))]
+	/// Print the symbol table of every compiled file as JSON
+	#[clap(long)]
 	symbols: bool,
+
+	/// The directories inside the compiled one that have their own options in the manifest,
+	/// the outermost first
+	#[clap(skip)]
+	directories: Vec<(PathBuf, CompileArgs)>,
+}
+
+impl BuildArgs {
+	/// Checks that the options only used by some layouts are not given with the others,
+	/// either in the command line or in the manifest
+	fn check_layout(&self) -> Result<(), String> {
+		match self.layout {
+			Some(Layout::Modules) if self.entry.is_some() || self.library => Err(String::from(
+				"--entry and --library cannot be used with --layout modules!",
+			)),
+			Some(Layout::Modules | Layout::Preload) if self.base.is_some() => Err(String::from(
+				"A custom base can only be used with --layout bundle!",
+			)),
+			_ => Ok(()),
+		}
+	}
+}
+
+impl CompileArgs {
+	/// Sets the options that were not given in the command line to the ones of the manifest,
+	/// the flags that are turned on can be turned off with their `--no-` version
+	fn apply_manifest(&mut self, manifest: &ManifestOptions) {
+		self.target = self.target.or(manifest.target);
+		self.bitwise = self.bitwise.or(manifest.bitwise);
+		self.r#continue = self.r#continue.or(manifest.r#continue);
+		self.rawsetglobals = flag(
+			self.rawsetglobals,
+			self.no_rawsetglobals,
+			manifest.rawsetglobals,
+		);
+		self.debug = flag(self.debug, self.no_debug, manifest.debug);
+		self.targetos = self.targetos.take().or_else(|| manifest.targetos.clone());
+		self.types = self.types.or(manifest.types);
+		self.std = self.std.or(manifest.std);
+	}
+}
+
+impl Cli {
+	/// Turns a subcommand that compiles code into the flags it stands for,
+	/// the other subcommands are run by `main` before getting here
+	fn from_command(command: Command) -> Self {
+		match command {
+			Command::Build {
+				path,
+				outputname,
+				build,
+				compile,
+			} => Self {
+				path: Some(path),
+				outputname,
+				build,
+				compile,
+				..Self::default()
+			},
+			#[cfg(feature = "mlua")]
+			Command::Run {
+				path,
+				build,
+				compile,
+			} => Self {
+				path: Some(path),
+				dontsave: true,
+				execute: true,
+				build,
+				compile,
+				..Self::default()
+			},
+			Command::Eval {
+				code,
+				outputname,
+				#[cfg(feature = "mlua")]
+				execute,
+				compile,
+			} => Self {
+				path: Some(PathBuf::from(code)),
+				outputname,
+				pathiscode: true,
+				#[cfg(feature = "mlua")]
+				execute,
+				compile,
+				..Self::default()
+			},
+			Command::Dump {
+				what,
+				path,
+				compile,
+			} => Self {
+				path: Some(path),
+				dontsave: true,
+				tokens: what == DumpKind::Tokens,
+				r#struct: what == DumpKind::Ast,
+				expand: what == DumpKind::Preprocessed,
+				#[cfg(feature = "lsp")]
+				symbols: what == DumpKind::Symbols,
+				compile,
+				..Self::default()
+			},
+			_ => unreachable!("only the subcommands that compile code are turned into flags"),
+		}
+	}
+
+	/// Reads the manifest of the project containing the given path, if there is one,
+	/// and uses its options for the ones that were not given in the command line
+	fn load_manifest(&mut self, project: &Path) -> Result<(), String> {
+		match Manifest::find(project)? {
+			Some(manifest) => {
+				let profile = self.compile.profile.clone();
+				let compile = self.compile.clone();
+				let mut options = manifest.resolve(project, profile.as_deref())?;
+				if self.pathiscode {
+					// the code is printed instead of being saved when there is no output file
+					options.output = None;
+				}
+				self.apply_manifest(options);
+				for dir in manifest.directories_inside(project) {
+					let mut compile = compile.clone();
+					compile.apply_manifest(&manifest.resolve(&dir, profile.as_deref())?);
+					self.directories.push((dir, compile));
+				}
+				Ok(())
+			}
+			None if self.compile.profile.is_some() => Err(format!(
+				"--profile was given but no {MANIFEST_NAME} was found!"
+			)),
+			None => Ok(()),
+		}
+	}
+
+	/// Sets the options that were not given in the command line to the ones of the manifest,
+	/// the flags that are turned on can be turned off with their `--no-` version
+	fn apply_manifest(&mut self, manifest: ManifestOptions) {
+		self.compile.apply_manifest(&manifest);
+		let build = &mut self.build;
+		build.sourcemap = flag(build.sourcemap, build.no_sourcemap, manifest.sourcemap);
+		build.base = build.base.take().or_else(|| {
+			manifest
+				.base
+				.map(|base| base.to_string_lossy().into_owned())
+		});
+		build.layout = build.layout.or(manifest.layout);
+		build.entry = build.entry.take().or(manifest.entry);
+		build.library = flag(build.library, build.no_library, manifest.library);
+		build.include_lua = flag(
+			build.include_lua,
+			build.no_include_lua,
+			manifest.include_lua,
+		);
+		self.outputname = self.outputname.take().or(manifest.output);
+	}
+
+	/// Returns the compiler options chosen by the flags
+	fn options(&self) -> Options {
+		if self.compile.r#continue == Some(ContinueMode::LuaJIT) {
+			println!("Warning: \"LuaJIT continue mode was deprecated and replaced by goto mode\"")
+		}
+		if self.compile.jitbit.is_some() {
+			println!("Warning: \"--jitbit was deprecated and replaced by --bitwise\"");
+		}
+		self.compile_options(&self.compile)
+	}
+
+	/// Returns the options of every file of a directory given the ones of the directory itself
+	fn folder_options(&self, options: &Options) -> FolderOptions {
+		FolderOptions {
+			options: options.clone(),
+			directories: self
+				.directories
+				.iter()
+				.map(|(dir, compile)| (dir.clone(), self.compile_options(compile)))
+				.collect(),
+		}
+	}
+
+	/// Returns the compiler options chosen by the flags, with the given compile flags
+	fn compile_options(&self, compile: &CompileArgs) -> Options {
+		let mut options = Options {
+			env_outputname: self.outputname.clone(),
+			env_tokens: self.tokens,
+			env_struct: self.r#struct,
+			env_expand: self.expand,
+			env_jitbit: {
+				if compile.jitbit.is_some() {
+					compile.jitbit.clone()
+				} else if compile.bitwise == Some(BitwiseMode::Library) {
+					Some(String::from("bit"))
+				} else {
+					None
+				}
+			},
+			env_bitwise: compile.bitwise.unwrap_or_default(),
+			env_continue: compile.r#continue.unwrap_or_default(),
+			env_rawsetglobals: compile.rawsetglobals,
+			env_debug: compile.debug,
+			env_output: if self.pathiscode {
+				self.outputname.is_none()
+			} else {
+				self.output
+			},
+			env_target: compile.target,
+			env_targetos: compile
+				.targetos
+				.clone()
+				.unwrap_or_else(|| String::from(std::env::consts::OS)),
+			#[cfg(feature = "lsp")]
+			env_symbols: self.symbols,
+			#[cfg(not(feature = "lsp"))]
+			env_symbols: false,
+			env_color: compile.color.enabled(),
+			env_sourcemap: self.build.sourcemap,
+			env_types: compile.types.unwrap_or_default(),
+			env_std: compile.std.unwrap_or_default(),
+			env_require: matches!(self.build.layout, Some(Layout::Modules | Layout::Preload)),
+		};
+		options.preset();
+		// the modes given explicitly win over the preset of a target set in the manifest
+		if let Some(bitwise) = compile.bitwise {
+			options.env_bitwise = bitwise;
+			options.env_jitbit = match bitwise {
+				BitwiseMode::Library => options.env_jitbit.or_else(|| Some(String::from("bit"))),
+				_ => compile.jitbit.clone(),
+			};
+		}
+		if let Some(r#continue) = compile.r#continue {
+			options.env_continue = r#continue;
+		}
+		options
+	}
+}
+
+/// Returns if a flag is enabled: it is if it was given, else if the manifest enables it
+/// and its `--no-` version was not given
+fn flag(enabled: bool, disabled: bool, manifest: Option<bool>) -> bool {
+	enabled || !disabled && manifest.unwrap_or_default()
 }
 
+/// Compiles the preprocessed code of a file, returning the code, its statics and the warnings of the type checker
 pub fn compile_code(
 	codes: PPCode,
 	variables: &PPVars,
+	#[cfg(feature = "lsp")] defined: &PPVars,
 	name: &String,
 	scope: usize,
 	options: &Options,
-) -> Result<(String, String), String> {
+) -> Result<(String, String, Vec<Diagnostic>), Vec<Diagnostic>> {
 	let time = Instant::now();
 	let code = preprocess_codes(0, codes, variables, name)?;
 	if options.env_expand {
-		println!("Preprocessed file \"{name}\":\n{}", code.to_string());
+		println!("Preprocessed file \"{name}\":\n{code}");
 	}
 	let tokens: Vec<Token> = scan_code(code, name)?;
 	if options.env_tokens {
 		println!("Scanned tokens of file \"{name}\":\n{tokens:#?}");
 	}
-	let (ctokens, statics) = parse_tokens(
-		tokens,
-		/*if flag!(env_types) != TypesMode::NONE {
-			Some(AHashMap::default())
-		} else {
-			None
-		},*/
-		name, options,
-	)?;
+	#[cfg(feature = "lsp")]
+	if options.env_symbols {
+		println!("{}", SymbolTable::new(name, &tokens, defined).to_json());
+	}
+	let columns = options.env_sourcemap.then(|| line_columns(&tokens));
+	let (ctokens, statics, warnings) = parse_tokens_with_warnings(tokens, name, options)?;
 
 	if options.env_struct {
 		println!("Parsed structure of file \"{name}\":\n{ctokens:#?}");
 	}
 
-	let code = Compiler::new(options, name).compile_tokens(scope, ctokens)?;
+	let mut compiler = Compiler::new(options, name);
+	if let Some(columns) = &columns {
+		compiler = compiler.with_columns(columns);
+	}
+	let code = compiler.compile_tokens(scope, ctokens)?;
 
 	if options.env_output {
 		println!("Compiled Lua code of file \"{name}\":\n{code}");
@@ -202,7 +738,25 @@ pub fn compile_code(
 		name,
 		time.elapsed().as_secs_f32()
 	);
-	Ok((code, statics))
+	Ok((code, statics, warnings))
+}
+
+pub fn print_errors(errors: &[Diagnostic], source: Option<&str>, color: bool) {
+	for error in errors {
+		let file = match source {
+			Some(_) => None,
+			None => fs::read_to_string(&error.file).ok(),
+		};
+		eprintln!("{}", error.render(source.or(file.as_deref()), color));
+	}
+}
+
+fn report_errors(errors: Vec<Diagnostic>, source: Option<&str>, color: bool) -> String {
+	print_errors(&errors, source, color);
+	match errors.len() {
+		1 => String::from("1 error was found!"),
+		n => format!("{n} errors were found!"),
+	}
 }
 
 #[cfg(feature = "mlua")]
@@ -216,11 +770,41 @@ fn execute_lua_code(code: &str) {
 	println!("Code ran in {} seconds!", time.elapsed().as_secs_f32());
 }
 
+fn write_output(
+	output_path: &Path,
+	code: &str,
+	sourcemap: Option<&SourceMap>,
+) -> Result<(), String> {
+	check!(fs::write(output_path, code));
+	if let Some(sourcemap) = sourcemap {
+		let mut sourcemap_path = output_path.as_os_str().to_owned();
+		sourcemap_path.push(".map");
+		check!(fs::write(sourcemap_path, sourcemap.to_json()));
+	}
+	Ok(())
+}
+
+fn extract_sourcemap(
+	enabled: bool,
+	output_path: Option<&Path>,
+	code: String,
+) -> (String, Option<SourceMap>) {
+	if !enabled {
+		return (code, None);
+	}
+	let file = output_path
+		.and_then(Path::file_name)
+		.map_or_else(String::new, |file| file.to_string_lossy().into_owned());
+	let (code, sourcemap) = SourceMap::extract(&code, file);
+	(code, Some(sourcemap))
+}
+
 fn finish(
 	debug: bool,
 	#[cfg(feature = "mlua")] execute: bool,
 	output_path: Option<PathBuf>,
 	code: String,
+	sourcemap: Option<SourceMap>,
 ) -> Result<(), String> {
 	if debug {
 		let new_output = format!(
@@ -228,7 +812,12 @@ fn finish(
 			format_clue!("\t", code.replace('\n', "\n\t"))
 		);
 		if let Some(output_path) = output_path {
-			check!(fs::write(output_path, &new_output));
+			let sourcemap = sourcemap.map(|mut sourcemap| {
+				let header = include_str!("debug.lua").split("{}").next().unwrap();
+				sourcemap.offset(header.matches('\n').count());
+				sourcemap
+			});
+			write_output(&output_path, &new_output, sourcemap.as_ref())?;
 		}
 		#[cfg(feature = "mlua")]
 		if execute {
@@ -247,157 +836,514 @@ fn save_result(
 	dont_save: bool,
 	output_name: Option<PathBuf>,
 	code: String,
-) -> Result<(Option<PathBuf>, String), String> {
-	Ok((
-		if !dont_save {
-			let output_path = match output_name {
-				Some(mut output_path) => {
-					match output_path.extension() {
-						Some(extension) if extension != "lua" => {
-							output_path
-								.set_extension(format_clue!(extension.to_string_lossy(), ".lua"));
-						}
-						None => {
-							output_path.set_extension("lua");
-						}
-						_ => {}
-					}
-					output_path
+	sourcemap: bool,
+) -> Result<(Option<PathBuf>, String, Option<SourceMap>), String> {
+	if dont_save {
+		let (code, sourcemap) = extract_sourcemap(sourcemap, None, code);
+		return Ok((None, code, sourcemap));
+	}
+	let output_path = match output_name {
+		Some(mut output_path) => {
+			match output_path.extension() {
+				Some(extension) if extension != "lua" => {
+					output_path.set_extension(format_clue!(extension.to_string_lossy(), ".lua"));
 				}
-				None => PathBuf::from("main.lua"),
-			};
-			check!(fs::write(&output_path, &code));
-			Some(output_path)
+				None => {
+					output_path.set_extension("lua");
+				}
+				_ => {}
+			}
+			output_path
+		}
+		None => PathBuf::from("main.lua"),
+	};
+	let (code, sourcemap) = extract_sourcemap(sourcemap, Some(&output_path), code);
+	write_output(&output_path, &code, sourcemap.as_ref())?;
+	Ok((Some(output_path), code, sourcemap))
+}
+
+/// Makes `require` load the modules of the default base with `import`
+const PRELOAD_MODULES: &str = "for modname in pairs(_modules) do
+	package.preload[modname] = function() return import(modname) end
+end
+";
+
+/// Returns the code that ends the default base, loading the entry module with `loader` or returning the modules
+fn entry_code(build: &BuildArgs, loader: &str) -> String {
+	match (&build.entry, build.library) {
+		(None, true) => format!(
+			"return setmetatable({{}}, {{__index = function(_, modname) return {loader}(modname) end}})"
+		),
+		(entry, _) => {
+			let entry = entry.as_deref().unwrap_or("main");
+			let file = format!("{}.clue", entry.replace('.', "/"));
+			format!(
+				"if _modules[{entry:?}] then\n\treturn {loader}({entry:?})\nelse\n\terror({:?})\nend",
+				format!("File {file:?} was not found!")
+			)
+		}
+	}
+}
+
+/// Saves every compiled file of a directory in its own Lua file inside the output directory,
+/// with its statics at the start of the file
+fn save_modules(cli: &Cli, modules: Vec<Module>) -> Result<(), String> {
+	if cli.dontsave {
+		return Ok(());
+	}
+	let output_dir = cli
+		.outputname
+		.clone()
+		.unwrap_or_else(|| PathBuf::from("build"));
+	for module in modules {
+		let mut output_path = output_dir.clone();
+		output_path.extend(module.name.split('.'));
+		output_path.set_extension("lua");
+		check!(fs::create_dir_all(output_path.parent().unwrap()));
+		let code = module.statics + &module.code;
+		let (code, sourcemap) = extract_sourcemap(cli.build.sourcemap, Some(&output_path), code);
+		write_output(&output_path, &code, sourcemap.as_ref())?;
+	}
+	Ok(())
+}
+
+fn trace(map: PathBuf, trace: Option<PathBuf>) -> Result<(), String> {
+	let map = match map.extension() {
+		Some(extension) if extension == "map" => map,
+		_ => {
+			let mut map = map.into_os_string();
+			map.push(".map");
+			PathBuf::from(map)
+		}
+	};
+	let json = match fs::read_to_string(&map) {
+		Ok(json) => json,
+		Err(_) => return Err(format!("{} was not found!", map.to_string_lossy())),
+	};
+	let sourcemap =
+		SourceMap::from_json(&json, &map.to_string_lossy()).map_err(|error| error.to_string())?;
+	let trace = match trace {
+		Some(trace) => check!(fs::read_to_string(trace)),
+		None => {
+			let mut trace = String::new();
+			check!(io::stdin().read_to_string(&mut trace));
+			trace
+		}
+	};
+	print!("{}", sourcemap.rewrite_trace(&trace));
+	Ok(())
+}
+
+fn find_all_clue_files(paths: Vec<PathBuf>) -> Result<Vec<PathBuf>, String> {
+	let mut files = Vec::new();
+	for path in if paths.is_empty() {
+		vec![PathBuf::from(".")]
+	} else {
+		paths
+	} {
+		check!(find_files(&path, String::new(), false, &mut files));
+	}
+	Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+fn fmt(paths: Vec<PathBuf>, check: bool) -> Result<(), String> {
+	let files = find_all_clue_files(paths)?;
+	let options = Options::default();
+	let mut failed = 0;
+	let mut unformatted = 0;
+	for file in files {
+		let code = check!(fs::read_to_string(&file));
+		let filename = file.to_string_lossy().into_owned();
+		let formatted = match format_code(&code, &filename, &options) {
+			Ok(formatted) => formatted,
+			Err(errors) => {
+				print_errors(&errors, Some(&code), ColorMode::Auto.enabled());
+				failed += 1;
+				continue;
+			}
+		};
+		if formatted == code {
+			continue;
+		}
+		if check {
+			println!("{filename}");
+			unformatted += 1;
+		} else {
+			check!(fs::write(&file, formatted));
+		}
+	}
+	match (failed, unformatted) {
+		(0, 0) => Ok(()),
+		(0, 1) => Err(String::from("1 file is not formatted!")),
+		(0, n) => Err(format!("{n} files are not formatted!")),
+		(1, _) => Err(String::from("1 file could not be formatted!")),
+		(n, _) => Err(format!("{n} files could not be formatted!")),
+	}
+}
+
+/// Creates a project that can be built with `clue build` in the given directory
+fn new_project(path: &Path) -> Result<(), String> {
+	if fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_some()) {
+		return Err(format!("{} already exists!", path.to_string_lossy()));
+	}
+	check!(fs::create_dir_all(path));
+	check!(fs::write(
+		path.join(MANIFEST_NAME),
+		"[options]\noutput = \"main.lua\"\n\n[profiles.debug]\ndebug = true\nsourcemap = true\n\n[profiles.release]\ndebug = false\n"
+	));
+	check!(fs::write(
+		path.join("main.clue"),
+		"print(\"Hello, world!\");\n"
+	));
+	check!(fs::write(
+		path.join(".gitignore"),
+		format!("/main.lua\n/main.lua.map\n/{CACHE_DIR}/\n")
+	));
+	println!("Created project \"{}\"!", path.to_string_lossy());
+	Ok(())
+}
+
+/// Reads the lint rules from the nearest .cluelint file, searching from the current directory upwards
+fn read_lint_rules() -> Result<LintRules, String> {
+	let mut rules = LintRules::default();
+	let current = check!(std::env::current_dir());
+	if let Some(path) = current
+		.ancestors()
+		.map(|dir| dir.join(".cluelint"))
+		.find(|path| path.is_file())
+	{
+		let config = check!(fs::read_to_string(&path));
+		if let Err(error) = rules.parse_config(&config, &path.to_string_lossy()) {
+			return Err(report_errors(
+				vec![error],
+				Some(&config),
+				ColorMode::Auto.enabled(),
+			));
+		}
+	}
+	Ok(rules)
+}
+
+fn lint(paths: Vec<PathBuf>, allow: Vec<Rule>, json: bool) -> Result<(), String> {
+	let files = find_all_clue_files(paths)?;
+	let mut rules = read_lint_rules()?;
+	for rule in allow {
+		rules.set(rule, false);
+	}
+	let options = Options::default();
+	let color = ColorMode::Auto.enabled();
+	let mut failed = 0;
+	let mut warnings = 0;
+	let mut diagnostics = Vec::new();
+	for file in files {
+		let code = check!(fs::read_to_string(&file));
+		let filename = file.to_string_lossy().into_owned();
+		let found = match lint_code(&code, &filename, &options, &rules) {
+			Ok(found) => {
+				warnings += found.len();
+				found
+			}
+			Err(errors) => {
+				failed += 1;
+				errors
+			}
+		};
+		if json {
+			diagnostics.extend(found);
 		} else {
-			None
-		},
-		code,
-	))
+			print_errors(&found, Some(&code), color);
+		}
+	}
+	if json {
+		let diagnostics: Vec<String> = diagnostics.iter().map(Diagnostic::to_json).collect();
+		println!("[{}]", diagnostics.join(","));
+	}
+	match (failed, warnings) {
+		(0, 0) => Ok(()),
+		(0, 1) => Err(String::from("1 warning was found!")),
+		(0, n) => Err(format!("{n} warnings were found!")),
+		(1, _) => Err(String::from("1 file could not be linted!")),
+		(n, _) => Err(format!("{n} files could not be linted!")),
+	}
+}
+
+/// Preprocesses, scans and parses the *.clue files at `path` without compiling them,
+/// printing the errors and the warnings that were found
+fn check(path: &Path, options: &FolderOptions) -> Result<(), String> {
+	let files = find_all_clue_files(vec![path.to_path_buf()])?;
+	let mut failed = 0;
+	let mut codes = Vec::with_capacity(files.len());
+	let mut variables = PPVars::default();
+	for file in &files {
+		let options = options.get(file);
+		let filename = file.to_string_lossy().into_owned();
+		match read_file(file, &filename, options) {
+			Ok((file_codes, file_variables, warnings)) => {
+				print_errors(&warnings, None, options.env_color);
+				variables.extend(file_variables);
+				codes.push((file_codes, filename, options));
+			}
+			Err(error) => {
+				print_errors(&[error], None, options.env_color);
+				failed += 1;
+			}
+		}
+	}
+	// the macros of every file are known before any file is checked, like when compiling
+	for (file_codes, filename, options) in codes {
+		let parsed = preprocess_codes(0, file_codes, &variables, &filename)
+			.map_err(|error| vec![error])
+			.and_then(|code| scan_code(code, &filename))
+			.and_then(|tokens| parse_tokens_with_warnings(tokens, &filename, options));
+		match parsed {
+			Ok((_, _, warnings)) => print_errors(&warnings, None, options.env_color),
+			Err(errors) => {
+				print_errors(&errors, None, options.env_color);
+				failed += 1;
+			}
+		}
+	}
+	match failed {
+		0 => {
+			println!("No errors were found!");
+			Ok(())
+		}
+		1 => Err(String::from("1 file has errors!")),
+		n => Err(format!("{n} files have errors!")),
+	}
 }
 
 fn main() -> Result<(), String> {
 	std::env::set_var("CLUE_VERSION", crate_version!());
-	let cli = Cli::parse();
+	let mut cli = Cli::parse();
+	if let Some(command) = cli.command.take() {
+		cli = match command {
+			Command::New { path } => return new_project(&path),
+			Command::Trace { map, trace: file } => return trace(map, file),
+			Command::Fmt { paths, check } => return fmt(paths, check),
+			Command::Lint { paths, allow, json } => return lint(paths, allow, json),
+			Command::Check { path, compile } => {
+				let mut cli = Cli {
+					compile,
+					..Cli::default()
+				};
+				cli.load_manifest(&path)?;
+				return check(&path, &cli.folder_options(&cli.options()));
+			}
+			#[cfg(feature = "mlua")]
+			Command::Repl { compile } => {
+				let mut cli = Cli {
+					compile,
+					..Cli::default()
+				};
+				cli.load_manifest(&check!(std::env::current_dir()))?;
+				return Repl::new(cli.options()).run();
+			}
+			#[cfg(feature = "mlua")]
+			Command::Test { path, compile } => {
+				let mut cli = Cli {
+					compile,
+					..Cli::default()
+				};
+				cli.load_manifest(&path)?;
+				return run_tests(&path, cli.folder_options(&cli.options()));
+			}
+			command => Cli::from_command(command),
+		};
+	}
 	if cli.license {
 		print!(include_str!("../LICENSE"));
 		return Ok(());
-	} /*else if cli.types.is_some() {
-	  //TEMPORARY PLACEHOLDER UNTIL 4.0
-	  return Err(String::from("Type checking is not supported yet!"));
-  }*/
-
-	if cli.r#continue == ContinueMode::LuaJIT {
-		println!("Warning: \"LuaJIT continue mode was deprecated and replaced by goto mode\"")
-	}
-
-	let mut options = Options {
-		env_outputname: cli.outputname.clone(),
-		env_tokens: cli.tokens,
-		env_struct: cli.r#struct,
-		env_expand: cli.expand,
-		env_jitbit: {
-			if cli.jitbit.is_some() {
-				println!("Warning: \"--jitbit was deprecated and replaced by --bitwise\"");
-				cli.jitbit
-			} else if cli.bitwise == BitwiseMode::Library {
-				Some(String::from("bit"))
-			} else {
-				None
+	}
+
+	let mut path = cli.path.clone().unwrap();
+	if !cli.pathiscode && !path.is_dir() {
+		match path.extension() {
+			Some(extension) if extension != "clue" => {
+				path.set_extension(format_clue!(extension.to_string_lossy(), ".clue"));
 			}
-		},
-		env_bitwise: cli.bitwise,
-		env_continue: cli.r#continue,
-		env_rawsetglobals: cli.rawsetglobals,
-		env_debug: cli.debug,
-		env_output: if cli.pathiscode {
-			cli.outputname.is_none()
-		} else {
-			cli.output
-		},
-		env_target: cli.target,
-		env_targetos: cli.targetos,
-		#[cfg(feature = "lsp")]
-		env_symbols: cli.symbols,
-		#[cfg(not(feature = "lsp"))]
-		env_symbols: false,
+			None => {
+				path.set_extension("clue");
+			}
+			_ => {}
+		}
+	}
+	let project = if cli.pathiscode {
+		check!(std::env::current_dir())
+	} else {
+		path.clone()
 	};
-	options.preset();
+	cli.load_manifest(&project)?;
+	cli.build.check_layout()?;
+	let options = cli.options();
 
 	//let mut code = String::with_capacity(512);
 
 	/*if let Some(bit) = &options.env_jitbit {
 		check!(writeln!(&mut code, "local {bit} = require(\"bit\");"));
 	}*/
-	/*if flag!(env_types) != TypesMode::NONE {
-		*check!(LUA_G.write()) = match flag!(env_std) {
-			LuaSTD::LUA54 => Some(AHashMap::from_iter([(String::from("print"), LuaType::NIL)])), //PLACEHOLDER
-			_ => Some(AHashMap::default()),
-		};
-	}*/
-	let mut path = cli.path.unwrap();
 	if cli.pathiscode {
 		let filename = String::from("(command line)");
-		let mut code = path.to_string_lossy().into_owned();
+		let source = path.to_string_lossy().into_owned();
+		let mut code = source.clone();
 		let code = unsafe { code.as_bytes_mut() };
-		let preprocessed_code = preprocess_code(code, 1, false, &filename, &options)?;
-		let (code, statics) = compile_code(
+		let preprocessed_code = preprocess_code(code, 1, false, &filename, &options)
+			.map_err(|error| report_errors(vec![error], Some(&source), options.env_color))?;
+		print_errors(&preprocessed_code.4, Some(&source), options.env_color);
+		let (code, statics, warnings) = compile_code(
 			preprocessed_code.0,
 			&preprocessed_code.1,
+			#[cfg(feature = "lsp")]
+			&preprocessed_code.1,
 			&filename,
 			0,
 			&options,
-		)?;
-		let code = code + &statics;
+		)
+		.map_err(|errors| report_errors(errors, Some(&source), options.env_color))?;
+		print_errors(&warnings, Some(&source), options.env_color);
+		let (code, sourcemap) = extract_sourcemap(
+			cli.build.sourcemap,
+			cli.outputname.as_deref(),
+			code + &statics,
+		);
 		#[cfg(feature = "mlua")]
 		if cli.execute {
 			execute_lua_code(&code)
 		}
 		return if let Some(outputname) = cli.outputname.clone() {
-			check!(fs::write(&outputname, &code));
+			write_output(&outputname, &code, sourcemap.as_ref())?;
 			#[cfg(feature = "mlua")]
-			return finish(cli.debug, cli.execute, Some(outputname), code);
+			return finish(
+				cli.compile.debug,
+				cli.execute,
+				Some(outputname),
+				code,
+				sourcemap,
+			);
 			#[cfg(not(feature = "mlua"))]
-			finish(cli.debug, Some(outputname), code)
+			finish(cli.compile.debug, Some(outputname), code, sourcemap)
 		} else {
 			Ok(())
 		};
 	}
-	let (output_path, code) = if path.is_dir() {
-		let (output, statics) = compile_folder(path, String::new(), options)?;
+	if cli.build.watch {
+		let modules_dir = match &options.env_outputname {
+			Some(output) => output.parent().map_or_else(PathBuf::new, Path::to_path_buf),
+			None => check!(std::env::current_dir()),
+		};
+		let base = cli.build.base.as_ref().map(PathBuf::from);
+		let watcher = check!(Watcher::new(
+			path.clone(),
+			base,
+			modules_dir,
+			cli.build.include_lua
+		));
+		return watcher.watch(|| compile_path(&path, &cli, &options));
+	}
+	compile_path(&path, &cli, &options)
+}
 
-		let code = match cli.base {
-			Some(filename) => {
-				let base = match fs::read(filename) {
-					Ok(base) => base,
-					Err(_) => return Err(String::from("The given custom base was not found!")),
-				};
-				check!(std::str::from_utf8(&base))
-					.to_owned()
-					.replace("--STATICS\n", &statics)
-					.replace('§', &output)
+/// Compiles the directory or file at `path`, then saves and runs the result as requested
+fn compile_path(path: &Path, cli: &Cli, options: &Options) -> Result<(), String> {
+	let (output_path, code, sourcemap) = if path.is_dir() {
+		// the files would not print anything if they were not compiled
+		let printing = options.env_tokens
+			|| options.env_struct
+			|| options.env_expand
+			|| options.env_output
+			|| options.env_symbols;
+		let scope = match cli.build.layout {
+			Some(Layout::Modules) => 0,
+			_ => 2,
+		};
+		let cache =
+			(!cli.build.no_cache && !printing).then(|| Cache::new(CACHE_DIR, path, scope, options));
+		if cli.build.layout == Some(Layout::Modules) {
+			#[cfg(feature = "mlua")]
+			if cli.execute {
+				return Err(String::from(
+					"A directory compiled with --layout modules cannot be executed!",
+				));
 			}
-			None => include_str!("base.lua")
+			let modules = compile_folder(
+				path,
+				String::new(),
+				cli.folder_options(options),
+				cache,
+				scope,
+				cli.build.include_lua,
+			)?;
+			return save_modules(cli, modules);
+		}
+		let (output, statics) = bundle(&compile_folder(
+			path,
+			String::new(),
+			cli.folder_options(options),
+			cache,
+			scope,
+			cli.build.include_lua,
+		)?);
+
+		let code = if cli.build.layout == Some(Layout::Preload) {
+			include_str!("preload.lua")
+				.replace("--ENTRY", &entry_code(&cli.build, "require"))
 				.replace("--STATICS\n", &statics)
-				.replace('§', &output),
-		};
-		save_result(cli.dontsave, cli.outputname, code)?
-	} else if {
-		match path.extension() {
-			Some(extension) if extension != "clue" => {
-				path.set_extension(format_clue!(extension.to_string_lossy(), ".clue"));
+				.replace('§', &output)
+		} else {
+			let base = match &cli.build.base {
+				Some(filename) => {
+					let base = match fs::read(filename) {
+						Ok(base) => base,
+						Err(_) => return Err(String::from("The given custom base was not found!")),
+					};
+					check!(String::from_utf8(base))
+				}
+				None => String::from(include_str!("base.lua")),
+			};
+			if (cli.build.entry.is_some() || cli.build.library) && !base.contains("--ENTRY") {
+				return Err(String::from(
+					"--entry and --library need a custom base with the --ENTRY marker!",
+				));
 			}
-			None => {
-				path.set_extension("clue");
+			let mut entry = entry_code(&cli.build, "import");
+			if cli.build.include_lua {
+				// the Lua files load each other with require
+				entry = String::from(PRELOAD_MODULES) + &entry;
 			}
-			_ => {}
-		}
-		path.is_file()
-	} {
+			base.replace("--ENTRY", &entry)
+				.replace("--STATICS\n", &statics)
+				.replace('§', &output)
+		};
+		save_result(
+			cli.dontsave,
+			cli.outputname.clone(),
+			code,
+			cli.build.sourcemap,
+		)?
+	} else if path.is_file() {
 		let name = path.file_name().unwrap().to_string_lossy().into_owned();
-		let (rawcode, variables) = read_file(path, &name, &options)?;
-		let (output, statics) = compile_code(rawcode, &variables, &name, 0, &options)?;
+		let source = fs::read_to_string(path).ok();
+		let (rawcode, variables, warnings) = read_file(path, &name, options)
+			.map_err(|error| report_errors(vec![error], source.as_deref(), options.env_color))?;
+		print_errors(&warnings, source.as_deref(), options.env_color);
+		let (output, statics, warnings) = compile_code(
+			rawcode,
+			&variables,
+			#[cfg(feature = "lsp")]
+			&variables,
+			&name,
+			0,
+			options,
+		)
+		.map_err(|errors| report_errors(errors, source.as_deref(), options.env_color))?;
+		print_errors(&warnings, source.as_deref(), options.env_color);
 		let code = statics + &output;
-		save_result(cli.dontsave, cli.outputname, code)?
+		save_result(
+			cli.dontsave,
+			cli.outputname.clone(),
+			code,
+			cli.build.sourcemap,
+		)?
 	} else {
 		return Err(format!(
 			"{} was not found!",
@@ -406,18 +1352,278 @@ fn main() -> Result<(), String> {
 	};
 
 	#[cfg(feature = "mlua")]
-	return finish(cli.debug, cli.execute, output_path, code);
+	return finish(cli.compile.debug, cli.execute, output_path, code, sourcemap);
 	#[cfg(not(feature = "mlua"))]
-	finish(cli.debug, output_path, code)
+	finish(cli.compile.debug, output_path, code, sourcemap)
 }
 
 #[cfg(test)]
 mod tests {
-	use crate::compile_folder;
-	use clue_core::env::Options;
+	use crate::{bundle, check, compile_folder, compile_path, Cli, FolderOptions, ManifestOptions};
+	use clap::Parser;
+	use clue_core::env::{BitwiseMode, ContinueMode, LuaVersion, Options};
+	use std::fs;
+	use std::path::{Path, PathBuf};
+
+	/// Creates a project whose src/main.clue imports src/lib/util.clue
+	fn project(name: &str) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("clue-{name}-{}", std::process::id()));
+		fs::create_dir_all(dir.join("src/lib")).unwrap();
+		fs::write(
+			dir.join("src/main.clue"),
+			"@import \"lib.util\" => util\nreturn util.x + 1",
+		)
+		.unwrap();
+		fs::write(dir.join("src/lib/util.clue"), "return {x = 1}").unwrap();
+		dir
+	}
+
+	/// Compiles the src directory of the project with the given flags
+	fn build(dir: &Path, output: &str, flags: &[&str]) -> Result<(), String> {
+		let src = dir.join("src");
+		let output = dir.join(output);
+		let mut args = vec![
+			"clue",
+			src.to_str().unwrap(),
+			output.to_str().unwrap(),
+			"--no-cache",
+		];
+		args.extend(flags);
+		let cli = Cli::try_parse_from(args).map_err(|error| error.to_string())?;
+		cli.build.check_layout()?;
+		compile_path(&src, &cli, &cli.options())
+	}
 
 	#[test]
 	fn compilation_success() {
-		compile_folder("../examples/", String::new(), Options::default()).unwrap();
+		compile_folder(
+			"../examples/",
+			String::new(),
+			Options::default(),
+			None,
+			2,
+			false,
+		)
+		.unwrap();
+	}
+
+	#[test]
+	fn deterministic_output() {
+		let compile = || {
+			let modules = compile_folder(
+				"../examples/",
+				String::new(),
+				Options::default(),
+				None,
+				2,
+				false,
+			)
+			.unwrap();
+			bundle(&modules)
+		};
+		let first = compile();
+		for _ in 0..5 {
+			assert_eq!(first, compile());
+		}
+	}
+
+	#[test]
+	fn directory_options() {
+		let dir = std::env::temp_dir().join(format!("clue-directories-{}", std::process::id()));
+		std::fs::create_dir_all(dir.join("server")).unwrap();
+		std::fs::write(dir.join("main.clue"), "x = a & b").unwrap();
+		std::fs::write(dir.join("server/util.clue"), "x = a & b").unwrap();
+		let options = FolderOptions {
+			options: Options::default(),
+			directories: vec![(
+				dir.join("server").canonicalize().unwrap(),
+				Options {
+					env_jitbit: Some(String::from("bit")),
+					env_bitwise: BitwiseMode::Library,
+					..Options::default()
+				},
+			)],
+		};
+		let modules = compile_folder(&dir, String::new(), options, None, 2, false).unwrap();
+		assert_eq!(modules[0].name, "main");
+		assert!(!modules[0].code.contains("bit.band"));
+		assert_eq!(modules[1].name, "server.util");
+		assert!(modules[1].code.contains("bit.band"));
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn modules_layout() {
+		let dir = project("modules");
+		build(&dir, "build", &["--layout", "modules"]).unwrap();
+		let main = fs::read_to_string(dir.join("build/main.lua")).unwrap();
+		assert!(main.contains("require(\"lib.util\")"), "{main}");
+		assert!(dir.join("build/lib/util.lua").is_file());
+		for flags in [
+			["--entry", "main"].as_slice(),
+			&["--library"],
+			&["--base", "base.lua"],
+		] {
+			let flags = [&["--layout", "modules"], flags].concat();
+			assert!(build(&dir, "build", &flags).is_err());
+		}
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn entry_and_library() {
+		let dir = project("entry");
+		build(&dir, "lib.lua", &["--entry", "lib.util", "--library"]).unwrap();
+		let code = fs::read_to_string(dir.join("lib.lua")).unwrap();
+		assert!(code.contains("if _modules[\"lib.util\"] then\n\treturn import(\"lib.util\")"));
+		#[cfg(feature = "mlua")]
+		{
+			let lua = mlua::Lua::new();
+			let util: mlua::Table = lua.load(&code).eval().unwrap();
+			assert_eq!(util.get::<_, i64>("x").unwrap(), 1);
+		}
+		build(&dir, "lib.lua", &["--library"]).unwrap();
+		let code = fs::read_to_string(dir.join("lib.lua")).unwrap();
+		assert!(code.contains(
+			"return setmetatable({}, {__index = function(_, modname) return import(modname) end})"
+		));
+		#[cfg(feature = "mlua")]
+		{
+			let lua = mlua::Lua::new();
+			let modules: mlua::Table = lua.load(&code).eval().unwrap();
+			assert_eq!(modules.get::<_, i64>("main").unwrap(), 2);
+		}
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn custom_base() {
+		let dir = project("base");
+		let base = dir.join("base.lua");
+		fs::write(
+			&base,
+			"--STATICS\nlocal _modules = {§}\nreturn _modules.main()\n",
+		)
+		.unwrap();
+		let flags = ["--layout", "bundle", "--base", base.to_str().unwrap()];
+		build(&dir, "main.lua", &flags).unwrap();
+		let code = fs::read_to_string(dir.join("main.lua")).unwrap();
+		assert!(code.starts_with("local _modules = {\n\t[\"lib.util\"] = function(...)"));
+		assert!(code.ends_with("return _modules.main()\n"));
+		let flags = [&flags[..], &["--entry", "lib.util"]].concat();
+		assert!(build(&dir, "main.lua", &flags).is_err());
+		fs::write(
+			&base,
+			"--STATICS\nlocal _modules\nlocal function import(name) return _modules[name]() end\n_modules = {§}\n--ENTRY",
+		)
+		.unwrap();
+		build(&dir, "main.lua", &flags).unwrap();
+		let code = fs::read_to_string(dir.join("main.lua")).unwrap();
+		assert!(code.contains("\treturn import(\"lib.util\")"));
+		#[cfg(feature = "mlua")]
+		{
+			let lua = mlua::Lua::new();
+			let util: mlua::Table = lua.load(&code).eval().unwrap();
+			assert_eq!(util.get::<_, i64>("x").unwrap(), 1);
+		}
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn preload_layout() {
+		let dir = project("preload");
+		build(&dir, "main.lua", &["--layout", "preload"]).unwrap();
+		let code = fs::read_to_string(dir.join("main.lua")).unwrap();
+		assert!(code.contains("\tpackage.preload[modname] = loader\n"));
+		assert!(code.contains("return require(\"main\")"));
+		#[cfg(feature = "mlua")]
+		{
+			let lua = mlua::Lua::new();
+			assert_eq!(lua.load(&code).eval::<i64>().unwrap(), 2);
+			let preload: mlua::Table = lua.load("return package.preload").eval().unwrap();
+			assert!(preload.contains_key("lib.util").unwrap());
+		}
+		let flags = ["--layout", "preload", "--base", "base.lua"];
+		assert!(build(&dir, "main.lua", &flags).is_err());
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn flags_override_manifest() {
+		let mut cli = Cli::parse_from(["clue", "src", "--continue", "simple"]);
+		cli.apply_manifest(ManifestOptions {
+			target: Some(LuaVersion::Lua54),
+			..ManifestOptions::default()
+		});
+		let options = cli.options();
+		assert_eq!(options.env_continue, ContinueMode::Simple);
+		assert_eq!(options.env_bitwise, BitwiseMode::Vanilla);
+		let mut cli = Cli::parse_from(["clue", "src", "--bitwise", "library"]);
+		cli.apply_manifest(ManifestOptions {
+			target: Some(LuaVersion::Lua54),
+			..ManifestOptions::default()
+		});
+		assert_eq!(cli.options().env_jitbit.as_deref(), Some("bit"));
+		let manifest = ManifestOptions {
+			debug: Some(true),
+			..ManifestOptions::default()
+		};
+		let mut cli = Cli::parse_from(["clue", "src", "--no-debug"]);
+		cli.apply_manifest(manifest.clone());
+		assert!(!cli.options().env_debug);
+		let mut cli = Cli::parse_from(["clue", "src", "--no-debug", "--debug"]);
+		cli.apply_manifest(manifest.clone());
+		assert!(cli.options().env_debug);
+		let mut cli = Cli::parse_from(["clue", "src"]);
+		cli.apply_manifest(manifest);
+		assert!(cli.options().env_debug);
+	}
+	#[test]
+	fn include_lua_files() {
+		let dir = std::env::temp_dir().join(format!("clue-lua-{}", std::process::id()));
+		std::fs::create_dir_all(dir.join("lib")).unwrap();
+		std::fs::write(dir.join("main.clue"), "print(1);").unwrap();
+		std::fs::write(dir.join("main.lua"), "print(2)").unwrap();
+		std::fs::write(dir.join("lib/util.lua"), "return {\n  x = 1\n}").unwrap();
+		let names = |include_lua| {
+			compile_folder(
+				&dir,
+				String::new(),
+				Options::default(),
+				None,
+				2,
+				include_lua,
+			)
+			.unwrap()
+			.into_iter()
+			.map(|module| (module.name, module.code))
+			.collect::<Vec<_>>()
+		};
+		assert_eq!(names(false).len(), 1);
+		let modules = names(true);
+		assert_eq!(
+			modules[0],
+			(
+				String::from("lib.util"),
+				String::from("return {\n  x = 1\n}")
+			)
+		);
+		assert_eq!(modules[1].0, "main");
+		assert_eq!(modules.len(), 2);
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn check_parses_only() {
+		let dir = project("check");
+		let src = dir.join("src");
+		check(&src, &Options::default().into()).unwrap();
+		assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+		fs::write(src.join("broken.clue"), "local = 1").unwrap();
+		assert_eq!(
+			check(&src, &Options::default().into()),
+			Err(String::from("1 file has errors!"))
+		);
+		fs::remove_dir_all(dir).unwrap();
 	}
 }
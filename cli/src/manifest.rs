@@ -0,0 +1,241 @@
+use clap::ValueEnum;
+use clue_core::env::{BitwiseMode, ContinueMode, LuaSTD, LuaVersion, TypesMode};
+use serde::{de::Error, Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Layout;
+
+/// The name of the project manifest
+pub const MANIFEST_NAME: &str = "clue.toml";
+
+/// Parses an enum the same way clap does for the command line flags
+fn value_enum<'de, D: Deserializer<'de>, T: ValueEnum>(
+	deserializer: D,
+) -> Result<Option<T>, D::Error> {
+	let value = String::deserialize(deserializer)?;
+	T::from_str(&value, true)
+		.map(Some)
+		.map_err(D::Error::custom)
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// The compiler options that can be set in a manifest, `None` if they are not set
+pub struct ManifestOptions {
+	#[serde(deserialize_with = "value_enum")]
+	pub target: Option<LuaVersion>,
+
+	#[serde(deserialize_with = "value_enum")]
+	pub bitwise: Option<BitwiseMode>,
+
+	#[serde(rename = "continue", deserialize_with = "value_enum")]
+	pub r#continue: Option<ContinueMode>,
+
+	pub rawsetglobals: Option<bool>,
+	pub debug: Option<bool>,
+	pub sourcemap: Option<bool>,
+	pub base: Option<PathBuf>,
+	pub targetos: Option<String>,
+	pub output: Option<PathBuf>,
+
+	#[serde(deserialize_with = "value_enum")]
+	pub types: Option<TypesMode>,
+
+	#[serde(deserialize_with = "value_enum")]
+	pub std: Option<LuaSTD>,
+
+	#[serde(deserialize_with = "value_enum")]
+	pub layout: Option<Layout>,
+
+	pub entry: Option<String>,
+	pub library: Option<bool>,
+
+	#[serde(rename = "include-lua")]
+	pub include_lua: Option<bool>,
+}
+
+impl ManifestOptions {
+	/// Replaces the options with the ones set in `other`
+	fn merge(&mut self, other: &ManifestOptions) {
+		macro_rules! merge {
+			($($field:ident),*) => {$(
+				if other.$field.is_some() {
+					self.$field = other.$field.clone();
+				}
+			)*};
+		}
+		merge!(
+			target,
+			bitwise,
+			r#continue,
+			rawsetglobals,
+			debug,
+			sourcemap,
+			base,
+			targetos,
+			output,
+			types,
+			std,
+			layout,
+			entry,
+			library,
+			include_lua
+		);
+	}
+}
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+/// The content of a `clue.toml` file: the options of the project,
+/// the named profiles chosen with `--profile` and the overrides for the paths inside some directories
+pub struct Manifest {
+	#[serde(skip)]
+	dir: PathBuf,
+
+	options: ManifestOptions,
+	profiles: HashMap<String, ManifestOptions>,
+	directories: HashMap<PathBuf, ManifestOptions>,
+}
+
+impl Manifest {
+	/// Parses a manifest, the relative paths inside it are relative to `dir`
+	pub fn parse(manifest: &str, dir: impl Into<PathBuf>) -> Result<Self, String> {
+		let mut manifest: Self = toml::from_str(manifest).map_err(|error| error.to_string())?;
+		manifest.dir = dir.into();
+		Ok(manifest)
+	}
+
+	/// Reads the nearest manifest, searching from the given path upwards
+	pub fn find(path: &Path) -> Result<Option<Self>, String> {
+		let path = match path.canonicalize() {
+			Ok(path) => path,
+			Err(_) => return Ok(None),
+		};
+		let Some(file) = path
+			.ancestors()
+			.map(|dir| dir.join(MANIFEST_NAME))
+			.find(|file| file.is_file())
+		else {
+			return Ok(None);
+		};
+		let manifest = fs::read_to_string(&file).map_err(|error| error.to_string())?;
+		Self::parse(&manifest, file.parent().unwrap())
+			.map(Some)
+			.map_err(|error| format!("{}: {error}", file.to_string_lossy()))
+	}
+
+	/// Returns the options for compiling `path` with the given profile, if any.
+	/// The options of the profile override the ones of the project
+	/// and are overridden by the directories containing `path`, the innermost last
+	pub fn resolve(&self, path: &Path, profile: Option<&str>) -> Result<ManifestOptions, String> {
+		let mut options = self.options.clone();
+		if let Some(profile) = profile {
+			match self.profiles.get(profile) {
+				Some(profile) => options.merge(profile),
+				None => {
+					return Err(format!(
+						"Profile \"{profile}\" was not found in the manifest!"
+					))
+				}
+			}
+		}
+		let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+		let mut directories: Vec<(PathBuf, &ManifestOptions)> = self
+			.directories
+			.iter()
+			.filter_map(|(dir, options)| {
+				let dir = self.dir.join(dir).canonicalize().ok()?;
+				path.starts_with(&dir).then_some((dir, options))
+			})
+			.collect();
+		directories.sort_by_key(|(dir, _)| dir.components().count());
+		for (_, directory) in directories {
+			options.merge(directory);
+		}
+		for file in [&mut options.base, &mut options.output]
+			.into_iter()
+			.flatten()
+		{
+			*file = self.dir.join(&*file);
+		}
+		Ok(options)
+	}
+
+	/// Returns the directories with their own options that are inside `path`, the outermost first
+	pub fn directories_inside(&self, path: &Path) -> Vec<PathBuf> {
+		let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+		let mut directories: Vec<PathBuf> = self
+			.directories
+			.keys()
+			.filter_map(|dir| {
+				let dir = self.dir.join(dir).canonicalize().ok()?;
+				(dir != path && dir.starts_with(&path)).then_some(dir)
+			})
+			.collect();
+		directories.sort_by_key(|dir| dir.components().count());
+		directories
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn resolve_options() {
+		let dir = std::env::temp_dir().join(format!("clue-manifest-{}", std::process::id()));
+		fs::create_dir_all(dir.join("src/server")).unwrap();
+		let manifest = Manifest::parse(
+			r#"
+			[options]
+			target = "luajit"
+			continue = "goto"
+			output = "build/main"
+
+			[profiles.release]
+			debug = false
+
+			[profiles.debug]
+			debug = true
+
+			[directories.src]
+			targetos = "windows"
+
+			[directories."src/server"]
+			targetos = "linux"
+			"#,
+			&dir,
+		)
+		.unwrap();
+
+		let options = manifest.resolve(&dir, None).unwrap();
+		assert_eq!(options.target, Some(LuaVersion::LuaJIT));
+		assert_eq!(options.r#continue, Some(ContinueMode::Goto));
+		assert_eq!(options.output, Some(dir.join("build/main")));
+		assert_eq!(options.debug, None);
+		assert_eq!(options.targetos, None);
+
+		let options = manifest.resolve(&dir.join("src"), Some("debug")).unwrap();
+		assert_eq!(options.debug, Some(true));
+		assert_eq!(options.targetos.as_deref(), Some("windows"));
+		let options = manifest.resolve(&dir.join("src/server"), None).unwrap();
+		assert_eq!(options.targetos.as_deref(), Some("linux"));
+		let server = dir.join("src/server").canonicalize().unwrap();
+		assert_eq!(
+			manifest.directories_inside(&dir),
+			[server.parent().unwrap().to_path_buf(), server.clone()]
+		);
+		assert_eq!(manifest.directories_inside(&dir.join("src")), [server]);
+		assert!(manifest
+			.directories_inside(&dir.join("src/server"))
+			.is_empty());
+
+		assert!(manifest.resolve(&dir, Some("test")).is_err());
+		assert!(Manifest::parse("[options]\ntarget = \"lua6\"", &dir).is_err());
+		assert!(Manifest::parse("[options]\noptimize = true", &dir).is_err());
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
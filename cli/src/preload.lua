@@ -0,0 +1,6 @@
+--STATICS
+local _modules = {§}
+for modname, loader in pairs(_modules) do
+	package.preload[modname] = loader
+end
+--ENTRY
\ No newline at end of file
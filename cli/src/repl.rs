@@ -0,0 +1,208 @@
+use clue_core::{
+	check,
+	code::Code,
+	compiler::Compiler,
+	diagnostic::Diagnostic,
+	env::Options,
+	parser::{parse_tokens, ComplexToken, Expression},
+	preprocessor::{preprocess_code, preprocess_codes, PPVars},
+	scanner::scan_code,
+};
+use mlua::{Function, Lua, MultiValue};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use crate::print_errors;
+
+/// An interactive session that compiles every input and runs it in the same Lua state,
+/// the preprocessor variables and macros defined by an input can be used by the next ones.
+/// Every input is a different chunk, so its top-level locals are made global to keep them
+pub struct Repl {
+	lua: Lua,
+	variables: PPVars,
+	options: Options,
+	filename: String,
+}
+
+/// Checks if the code has unclosed brackets, strings or comments and more lines are needed
+fn is_incomplete(code: &str) -> bool {
+	let mut depth = 0isize;
+	let mut chars = code.chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			'{' | '(' | '[' => depth += 1,
+			'}' | ')' | ']' => depth -= 1,
+			'"' | '\'' | '`' => loop {
+				match chars.next() {
+					None => return true,
+					Some('\\') => {
+						chars.next();
+					}
+					Some(end) if end == c => break,
+					Some(_) => {}
+				}
+			},
+			'/' if chars.next_if_eq(&'/').is_some() => {
+				while chars.next_if(|c| *c != '\n').is_some() {}
+			}
+			'/' if chars.next_if_eq(&'*').is_some() => loop {
+				match chars.next() {
+					None => return true,
+					Some('*') if chars.next_if_eq(&'/').is_some() => break,
+					Some(_) => {}
+				}
+			},
+			_ => {}
+		}
+	}
+	depth > 0
+}
+
+/// Turns the top-level local variables and functions into global ones
+fn globalize(ctokens: &mut Expression) {
+	for ctoken in ctokens {
+		match ctoken {
+			ComplexToken::VARIABLE { local, values, .. } => {
+				*local = false;
+				// a global without a value would not be valid Lua
+				if values.is_empty() {
+					values.push(VecDeque::from([ComplexToken::SYMBOL(String::from("nil"))]));
+				}
+			}
+			ComplexToken::FUNCTION { local, .. } => *local = false,
+			_ => {}
+		}
+	}
+}
+
+impl Repl {
+	pub fn new(options: Options) -> Self {
+		Self {
+			lua: Lua::new(),
+			variables: PPVars::new(),
+			options,
+			filename: String::from("(repl)"),
+		}
+	}
+
+	/// Reads the inputs from stdin until it is closed,
+	/// an empty line compiles the input even if it looks incomplete
+	pub fn run(mut self) -> Result<(), String> {
+		println!(
+			"Clue {} REPL, press Ctrl+D to exit",
+			env!("CARGO_PKG_VERSION")
+		);
+		let mut input = String::new();
+		loop {
+			print!("{}", if input.is_empty() { "> " } else { ". " });
+			check!(io::stdout().flush());
+			let mut line = String::new();
+			if check!(io::stdin().lock().read_line(&mut line)) == 0 {
+				println!();
+				return Ok(());
+			}
+			let forced = line.trim().is_empty();
+			input += &line;
+			if input.trim().is_empty() {
+				input.clear();
+			} else if forced || !is_incomplete(&input) {
+				let input = std::mem::take(&mut input);
+				self.eval(&input);
+			}
+		}
+	}
+
+	fn preprocess(&mut self, input: &str) -> Result<Code, Diagnostic> {
+		let mut code = input.as_bytes().to_vec();
+		let (codes, variables, _, _, warnings) =
+			preprocess_code(&mut code, 1, false, &self.filename, &self.options)?;
+		print_errors(&warnings, Some(input), self.options.env_color);
+		self.variables.extend(variables);
+		preprocess_codes(0, codes, &self.variables, &self.filename)
+	}
+
+	fn compile(&self, code: Code) -> Result<String, Vec<Diagnostic>> {
+		let tokens = scan_code(code, &self.filename)?;
+		let (mut ctokens, statics) = parse_tokens(tokens, &self.filename, &self.options)?;
+		globalize(&mut ctokens);
+		let code = Compiler::new(&self.options, &self.filename).compile_tokens(0, ctokens)?;
+		Ok(statics + &code)
+	}
+
+	fn load(&self, code: &str) -> mlua::Result<Function<'_>> {
+		self.lua.load(code).set_name("=repl").into_function()
+	}
+
+	/// Compiles and runs an input, printing its values if it is an expression
+	fn eval(&mut self, input: &str) {
+		let code = match self.preprocess(input) {
+			Ok(code) => code,
+			Err(error) => return print_errors(&[error], Some(input), self.options.env_color),
+		};
+		// the values are passed through select so that the whole input must be a list of expressions
+		let mut expression = Code::from(("return select(1, ", 1, 0));
+		expression.append(code.clone());
+		expression.append(Code::from(("\n)", 1, 0)));
+		let function = match self
+			.compile(expression)
+			.ok()
+			.and_then(|expression| self.load(&expression).ok())
+		{
+			Some(function) => function,
+			None => match self.compile(code) {
+				Ok(code) => match self.load(&code) {
+					Ok(function) => function,
+					Err(error) => return eprintln!("{error}"),
+				},
+				Err(errors) => return print_errors(&errors, Some(input), self.options.env_color),
+			},
+		};
+		let result = function.call::<_, MultiValue>(()).and_then(|values| {
+			if values.is_empty() {
+				return Ok(());
+			}
+			let print: Function = self.lua.globals().get("print")?;
+			print.call(values)
+		});
+		if let Err(error) = result {
+			eprintln!("{error}");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn incomplete_inputs() {
+		assert!(!is_incomplete("print(1)"));
+		assert!(is_incomplete("fn add(a, b) {"));
+		assert!(is_incomplete("local t = {\n\t1, 2,"));
+		assert!(!is_incomplete("print(\"{\") // {"));
+		assert!(is_incomplete("local s = `multi\nline"));
+		assert!(is_incomplete("/* comment"));
+		assert!(!is_incomplete("local t = {\n\t1,\n}"));
+	}
+
+	#[test]
+	fn persistent_state() {
+		let mut repl = Repl::new(Options::default());
+		repl.eval("@define N 20\nglobal x = $N + 1");
+		repl.eval("@macro DOUBLE(a) { ($a * 2) }\nglobal y = $DOUBLE!(x)");
+		let y: i64 = repl.lua.globals().get("y").unwrap();
+		assert_eq!(y, 42);
+	}
+
+	#[test]
+	fn persistent_locals() {
+		let mut repl = Repl::new(Options::default());
+		repl.eval("local a = 5");
+		repl.eval("local fn double(n) { return n * 2 }");
+		repl.eval("local b");
+		repl.eval("global c = double(a)");
+		let c: i64 = repl.lua.globals().get("c").unwrap();
+		assert_eq!(c, 10);
+		assert!(repl.lua.load("return b == nil").eval::<bool>().unwrap());
+	}
+}
@@ -0,0 +1,16 @@
+local module, test = ...
+local tests = import(module)
+if type(tests) ~= "table" then
+	error("Module \"" .. module .. "\" does not return a table of tests!")
+end
+if test == nil then
+	local names = {}
+	for name, value in pairs(tests) do
+		if type(name) == "string" and type(value) == "function" then
+			names[#names + 1] = name
+		end
+	end
+	table.sort(names)
+	return names
+end
+tests[test]()
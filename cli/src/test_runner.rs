@@ -0,0 +1,178 @@
+use clue_core::{check, sourcemap::SourceMap};
+use mlua::{Function, Lua};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::{
+	cache::{Cache, CACHE_DIR},
+	threads::{bundle, compile_folder, FolderOptions},
+};
+
+/// The name the bundle with the tests is loaded with, the errors that point to it are mapped back to the Clue files
+const CHUNK: &str = "tests.lua";
+
+/// Adds the module names of the *_test.clue files inside `path` to `modules`,
+/// `dir` is the directory the names are relative to
+fn find_test_modules(dir: &Path, path: &Path, modules: &mut Vec<String>) -> io::Result<()> {
+	let mut entries = fs::read_dir(path)?
+		.map(|entry| entry.map(|entry| entry.path()))
+		.collect::<io::Result<Vec<_>>>()?;
+	entries.sort();
+	for entry in entries {
+		if entry.is_dir() {
+			find_test_modules(dir, &entry, modules)?;
+		} else if entry
+			.file_name()
+			.is_some_and(|name| name.to_string_lossy().ends_with("_test.clue"))
+		{
+			let module = entry.strip_prefix(dir).unwrap().with_extension("");
+			let module: Vec<_> = module
+				.components()
+				.map(|component| component.as_os_str().to_string_lossy())
+				.collect();
+			modules.push(module.join("."));
+		}
+	}
+	Ok(())
+}
+
+fn load<'lua>(lua: &'lua Lua, code: &str) -> mlua::Result<Function<'lua>> {
+	lua.load(code).set_name(format!("={CHUNK}")).into_function()
+}
+
+/// Returns the names of the tests in the table returned by the module, sorted
+fn list_tests(code: &str, module: &str) -> mlua::Result<Vec<String>> {
+	let lua = Lua::new();
+	let tests = load(&lua, code)?.call(module)?;
+	Ok(tests)
+}
+
+/// Runs a single test in a new Lua state
+fn run_test(code: &str, module: &str, test: &str) -> mlua::Result<()> {
+	let lua = Lua::new();
+	load(&lua, code)?.call::<_, ()>((module, test))?;
+	Ok(())
+}
+
+/// Compiles the directory into a bundle that runs the test given as argument,
+/// or returns the names of the tests if only the module is given.
+/// If `cached` is true the compiled files are cached, apart from the ones of `clue build` since sourcemaps are on
+fn test_bundle(
+	path: &Path,
+	mut options: FolderOptions,
+	cached: bool,
+) -> Result<(String, SourceMap), String> {
+	for options in options.iter_mut() {
+		options.env_sourcemap = true;
+	}
+	let cache = cached.then(|| Cache::new(CACHE_DIR, path, 2, &options.options));
+	let (output, statics) = bundle(&compile_folder(
+		path,
+		String::new(),
+		options,
+		cache,
+		2,
+		false,
+	)?);
+	let code = include_str!("base.lua")
+		.replace("--ENTRY", include_str!("test.lua"))
+		.replace("--STATICS\n", &statics)
+		.replace('§', &output);
+	Ok(SourceMap::extract(&code, CHUNK))
+}
+
+/// Compiles the directory and runs every test of its *_test.clue files.
+/// A test file must return a table of functions, each one is a test that fails if it raises an error
+pub fn run_tests(path: &Path, options: FolderOptions) -> Result<(), String> {
+	if !path.is_dir() {
+		return Err(format!(
+			"{} is not a directory!",
+			path.to_string_lossy().into_owned()
+		));
+	}
+	let mut modules = Vec::new();
+	check!(find_test_modules(path, path, &mut modules));
+	if modules.is_empty() {
+		println!("No *_test.clue files were found!");
+		return Ok(());
+	}
+
+	let (code, sourcemap) = test_bundle(path, options, true)?;
+
+	let mut passed = 0;
+	let mut failed = 0;
+	for module in modules {
+		let tests = match list_tests(&code, &module) {
+			Ok(tests) => tests,
+			Err(error) => {
+				println!("test {module} ... FAILED");
+				println!("{}", sourcemap.rewrite_trace(&error.to_string()));
+				failed += 1;
+				continue;
+			}
+		};
+		for test in tests {
+			match run_test(&code, &module, &test) {
+				Ok(()) => {
+					println!("test {module}::{test} ... ok");
+					passed += 1;
+				}
+				Err(error) => {
+					println!("test {module}::{test} ... FAILED");
+					println!("{}", sourcemap.rewrite_trace(&error.to_string()));
+					failed += 1;
+				}
+			}
+		}
+	}
+
+	println!("{passed} passed, {failed} failed");
+	match failed {
+		0 => Ok(()),
+		1 => Err(String::from("1 test failed!")),
+		n => Err(format!("{n} tests failed!")),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clue_core::env::Options;
+
+	#[test]
+	fn run_test_files() {
+		let dir = std::env::temp_dir().join(format!("clue-test-{}", std::process::id()));
+		fs::create_dir_all(dir.join("math")).unwrap();
+		fs::write(
+			dir.join("math/add.clue"),
+			"return fn(a, b) { return a + b }",
+		)
+		.unwrap();
+		fs::write(
+			dir.join("math/add_test.clue"),
+			"local add = import(\"math.add\")\n\
+			return {\n\
+				works = fn() { assert(add(1, 2) == 3) },\n\
+				fails = fn() { assert(add(1, 2) == 4, \"wrong sum\") },\n\
+			}",
+		)
+		.unwrap();
+
+		let mut modules = Vec::new();
+		find_test_modules(&dir, &dir, &mut modules).unwrap();
+		assert_eq!(modules, ["math.add_test"]);
+
+		let (code, sourcemap) = test_bundle(&dir, Options::default().into(), false).unwrap();
+		assert_eq!(
+			list_tests(&code, "math.add_test").unwrap(),
+			["fails", "works"]
+		);
+		run_test(&code, "math.add_test", "works").unwrap();
+		let error = run_test(&code, "math.add_test", "fails").unwrap_err();
+		let error = sourcemap.rewrite_trace(&error.to_string());
+		assert!(error.contains("add_test.clue:4: wrong sum"), "{error}");
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
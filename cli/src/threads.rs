@@ -7,50 +7,107 @@ use crossbeam_queue::SegQueue;
 use flume::Sender;
 use std::cmp;
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread::{self, JoinHandle};
 
-use crate::compile_code;
+use crate::{cache::Cache, compile_code, print_errors};
 
-type CodeQueue = SegQueue<(PPCode, String, String)>;
+type CodeQueue = SegQueue<(PPCode, PPVars, String, String)>;
 
 struct PreprocessorAnalyzerData {
 	errored: bool,
-	codes: (PPCode, String, String),
+	codes: (PPCode, PPVars, String, String),
 	pub variables: PPVars,
 }
 
 struct ThreadData {
 	errored: bool,
+	realname: String,
 	output: String,
 	static_vars: String,
 }
 
-fn check_for_files(
-	path: PathBuf,
+/// The options of the files of a directory,
+/// the files inside one of `directories` use the options of the innermost one
+pub struct FolderOptions {
+	pub options: Options,
+
+	/// The directories with their own options, the outermost first
+	pub directories: Vec<(PathBuf, Options)>,
+}
+
+impl FolderOptions {
+	/// Returns the options of the given file
+	pub fn get(&self, file: &Path) -> &Options {
+		let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+		self.directories
+			.iter()
+			.rev()
+			.find(|(dir, _)| file.starts_with(dir))
+			.map_or(&self.options, |(_, options)| options)
+	}
+
+	/// Returns the options of every file, to change all of them
+	#[cfg(feature = "mlua")]
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Options> {
+		let directories = self.directories.iter_mut().map(|(_, options)| options);
+		std::iter::once(&mut self.options).chain(directories)
+	}
+}
+
+impl From<Options> for FolderOptions {
+	fn from(options: Options) -> Self {
+		Self {
+			options,
+			directories: Vec::new(),
+		}
+	}
+}
+
+/// A compiled file of a directory
+pub struct Module {
+	/// The path of the file inside the directory with dots instead of slashes and no extension
+	pub name: String,
+
+	/// The compiled code
+	pub code: String,
+
+	/// The statics of the file
+	pub statics: String,
+}
+
+/// Collects the *.clue files inside the path sorted by name, each one with its path inside it after `rpath`.
+/// If `include_lua` is true the *.lua files are collected too, unless there is a Clue file
+/// with the same name, which could have been compiled to it. A path that is not a directory is collected as it is
+pub fn find_files(
+	path: &Path,
 	rpath: String,
-) -> Result<SegQueue<(PathBuf, String)>, std::io::Error> {
-	let files = SegQueue::new();
-	for entry in fs::read_dir(&path)? {
-		let entry = entry?;
-		let name = entry
-			.path()
-			.file_name()
-			.unwrap()
-			.to_string_lossy()
-			.into_owned();
-		let filepath = path.join(&name);
-		let realname = rpath.clone() + &name;
-		if filepath.is_dir() {
-			for file in check_for_files(filepath, realname + ".")? {
-				files.push(file)
-			}
-		} else if filepath.extension().map_or(false, |extension| extension == "clue") {
-			files.push((filepath, realname));
+	include_lua: bool,
+	files: &mut Vec<(PathBuf, String)>,
+) -> io::Result<()> {
+	if !path.is_dir() {
+		let name = path.file_name().unwrap_or_default().to_string_lossy();
+		files.push((path.to_path_buf(), rpath + &name));
+		return Ok(());
+	}
+	let mut entries = fs::read_dir(path)?
+		.map(|entry| entry.map(|entry| entry.path()))
+		.collect::<io::Result<Vec<PathBuf>>>()?;
+	entries.sort();
+	for entry in entries {
+		let realname = rpath.clone() + &entry.file_name().unwrap().to_string_lossy();
+		if entry.is_dir() {
+			find_files(&entry, realname + ".", include_lua, files)?;
+		} else if entry.extension().is_some_and(|extension| {
+			extension == "clue"
+				|| (include_lua && extension == "lua" && !entry.with_extension("clue").is_file())
+		}) {
+			files.push((entry, realname));
 		}
 	}
-	Ok(files)
+	Ok(())
 }
 
 fn wait_threads(threads: Vec<JoinHandle<()>>) {
@@ -59,21 +116,61 @@ fn wait_threads(threads: Vec<JoinHandle<()>>) {
 	}
 }
 
+/// Joins the compiled files into the code of the `_modules` table of the base and their statics
+pub fn bundle(modules: &[Module]) -> (String, String) {
+	let mut output = String::with_capacity(modules.len() * 512) + "\n";
+	let mut statics = String::with_capacity(512);
+	for module in modules {
+		output += &format_clue!(
+			"\t[\"",
+			module.name,
+			"\"] = function(...)\n",
+			module.code,
+			"\n\tend,\n"
+		);
+		statics += &module.statics;
+	}
+	(output, statics)
+}
+
+/// Compiles every file of the directory, sorted by name.
+/// `scope` is the indentation level of the compiled code,
+/// if `include_lua` is true the *.lua files are added as they are
 pub fn compile_folder(
 	file_path: impl Into<PathBuf>,
 	rpath: String,
-	options: Options,
-) -> Result<(String, String), String> {
-	let files = check!(check_for_files(file_path.into(), rpath));
+	options: impl Into<FolderOptions>,
+	cache: Option<Cache>,
+	scope: usize,
+	include_lua: bool,
+) -> Result<Vec<Module>, String> {
+	let mut found = Vec::new();
+	check!(find_files(
+		&file_path.into(),
+		rpath,
+		include_lua,
+		&mut found
+	));
+	let files = SegQueue::new();
+	let mut lua_files = Vec::new();
+	for (filepath, realname) in found {
+		if filepath
+			.extension()
+			.is_some_and(|extension| extension == "lua")
+		{
+			lua_files.push((filepath, realname));
+		} else {
+			files.push((filepath, realname));
+		}
+	}
 	let files_len = files.len();
 	let threads_count = cmp::min(files_len, num_cpus::get() * 2);
 	let codes = SegQueue::new();
 	let files = Arc::new(files);
-	let options = Arc::new(options);
+	let options = Arc::new(options.into());
 	let mut errored = 0;
 	let mut variables = vec![];
-	let mut output = String::with_capacity(files_len * 512) + "\n";
-	let mut statics = String::with_capacity(512);
+	let mut modules = Vec::with_capacity(files_len);
 
 	let (tx, rx) = flume::unbounded();
 
@@ -93,7 +190,10 @@ pub fn compile_folder(
 
 	wait_threads(threads);
 
-	while let Ok(data) = rx.try_recv() {
+	// the files are sorted so that the output is the same every time
+	let mut files: Vec<PreprocessorAnalyzerData> = rx.try_iter().collect();
+	files.sort_by(|a, b| a.codes.3.cmp(&b.codes.3));
+	for data in files {
 		if data.errored {
 			errored += 1;
 			continue;
@@ -119,32 +219,52 @@ pub fn compile_folder(
 	let mut threads = Vec::with_capacity(threads_count);
 	let (tx, rx) = flume::unbounded();
 	let codes = Arc::new(codes);
+	let cache = Arc::new(cache);
 
 	for _ in 0..threads_count {
 		let tx = tx.clone();
 		let options = options.clone();
 		let codes = codes.clone();
 		let variables = variables.clone();
+		let cache = cache.clone();
 
-		let thread = thread::spawn(move || compile_file_dir(tx, &options, codes, variables));
+		let thread =
+			thread::spawn(move || compile_file_dir(tx, &options, codes, variables, &cache, scope));
 
 		threads.push(thread);
 	}
 
 	wait_threads(threads);
 
-	while let Ok(data) = rx.try_recv() {
+	let mut files: Vec<ThreadData> = rx.try_iter().collect();
+	for (filepath, realname) in lua_files {
+		files.push(ThreadData {
+			errored: false,
+			realname,
+			output: check!(fs::read_to_string(filepath)),
+			static_vars: String::new(),
+		});
+	}
+	files.sort_by(|a, b| a.realname.cmp(&b.realname));
+	for data in files {
 		if data.errored {
 			errored += 1;
 			continue;
 		}
 
-		output += &data.output;
-		statics += &data.static_vars;
+		modules.push(Module {
+			name: data.realname.rsplit_once('.').unwrap().0.to_owned(),
+			code: data.output,
+			statics: data.static_vars,
+		});
+	}
+
+	if let (0, Some(cache)) = (errored, cache.as_ref()) {
+		check!(cache.prune());
 	}
 
 	match errored {
-		0 => Ok((output.chars().collect(), statics.chars().collect())),
+		0 => Ok(modules),
 		1 => Err(String::from("1 file failed to compile!")),
 		n => Err(format!("{n} files failed to compile!")),
 	}
@@ -153,7 +273,7 @@ pub fn compile_folder(
 fn preprocess_file_dir(
 	files: Arc<SegQueue<(PathBuf, String)>>,
 	tx: Sender<PreprocessorAnalyzerData>,
-	options: &Options,
+	options: &FolderOptions,
 ) {
 	loop {
 		let (filename, filepath, realname) = match files.pop() {
@@ -164,8 +284,9 @@ fn preprocess_file_dir(
 				realname
 			),
 		};
+		let options = options.get(&filepath);
 
-		let (file_codes, file_variables) = match read_file(filepath, &filename, options) {
+		let (file_codes, file_variables, warnings) = match read_file(filepath, &filename, options) {
 			Ok(t) => t,
 			Err(e) => {
 				tx.send(PreprocessorAnalyzerData {
@@ -174,14 +295,21 @@ fn preprocess_file_dir(
 					variables: Default::default(),
 				})
 				.unwrap();
-				eprintln!("Error: {e}");
+				print_errors(&[e], None, options.env_color);
 				continue;
 			}
 		};
+		print_errors(&warnings, None, options.env_color);
 
+		// the variables defined by each file are only needed for its symbol table
+		let defined = if options.env_symbols {
+			file_variables.clone()
+		} else {
+			PPVars::default()
+		};
 		tx.send(PreprocessorAnalyzerData {
 			errored: false,
-			codes: (file_codes, filename, realname),
+			codes: (file_codes, defined, filename, realname),
 			variables: file_variables,
 		})
 		.unwrap();
@@ -190,41 +318,75 @@ fn preprocess_file_dir(
 
 fn compile_file_dir(
 	tx: Sender<ThreadData>,
-	options: &Options,
+	options: &FolderOptions,
 	codes: Arc<CodeQueue>,
 	variables: Arc<AHashMap<Code, PPVar>>,
+	cache: &Option<Cache>,
+	scope: usize,
 ) {
 	loop {
-		let (codes, filename, realname) = match codes.pop() {
+		// the variables defined by the file are only used to print its symbol table
+		#[cfg_attr(not(feature = "lsp"), allow(unused_variables))]
+		let (codes, defined, filename, realname) = match codes.pop() {
 			None => break,
 			Some(codes) => codes,
 		};
+		let options = options.get(Path::new(&filename));
+
+		let key = cache
+			.as_ref()
+			.map(|cache| cache.key(&realname, &codes, &variables, options));
+		if let Some((output, static_vars)) = cache
+			.as_ref()
+			.zip(key.as_ref())
+			.and_then(|(cache, key)| cache.get(key))
+		{
+			tx.send(ThreadData {
+				errored: false,
+				realname,
+				output,
+				static_vars,
+			})
+			.unwrap();
+			continue;
+		}
 
-		let (code, static_vars) = match compile_code(codes, &variables, &filename, 2, options) {
+		let (code, static_vars, warnings) = match compile_code(
+			codes,
+			&variables,
+			#[cfg(feature = "lsp")]
+			&defined,
+			&filename,
+			scope,
+			options,
+		) {
 			Ok(t) => t,
-			Err(e) => {
+			Err(errors) => {
 				tx.send(ThreadData {
 					errored: true,
+					realname,
 					output: "".to_owned(),
 					static_vars: "".to_owned(),
 				})
 				.unwrap();
-				eprintln!("Error: {e}");
+				print_errors(&errors, None, options.env_color);
 				continue;
 			}
 		};
 
-		let string = format_clue!(
-			"\t[\"",
-			realname.strip_suffix(".clue").unwrap(),
-			"\"] = function(...)\n",
-			code,
-			"\n\tend,\n"
-		);
+		print_errors(&warnings, None, options.env_color);
+
+		// the files with warnings are not cached, so that the warnings are printed again by the next builds
+		if let Some((cache, key)) = cache.as_ref().zip(key).filter(|_| warnings.is_empty()) {
+			if let Err(error) = cache.insert(&key, &code, &static_vars) {
+				println!("Warning: \"the compiled file could not be cached: {error}\"");
+			}
+		}
 
 		tx.send(ThreadData {
 			errored: false,
-			output: string,
+			realname,
+			output: code,
 			static_vars,
 		})
 		.unwrap();
@@ -0,0 +1,166 @@
+use ahash::AHashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::threads::find_files;
+
+/// How often the watched files are checked
+const INTERVAL: Duration = Duration::from_millis(500);
+
+/// The state of a watched file, `None` if it does not exist
+type FileState = Option<(SystemTime, u64)>;
+
+/// Keeps track of the files a compilation depends on: the *.clue files,
+/// the *.lua files if they are bundled, the custom base and the Lua modules loaded with `@import`
+pub struct Watcher {
+	path: PathBuf,
+	base: Option<PathBuf>,
+	modules_dir: PathBuf,
+	include_lua: bool,
+	files: AHashMap<PathBuf, FileState>,
+}
+
+fn hash_file(path: &Path) -> io::Result<u64> {
+	let mut hasher = DefaultHasher::new();
+	fs::read(path)?.hash(&mut hasher);
+	Ok(hasher.finish())
+}
+
+/// Returns the modules loaded by the `@import` directives of the code,
+/// they are found the same way the preprocessor does
+fn imported_modules(code: &str, modules_dir: &Path) -> Vec<PathBuf> {
+	code.split("@import")
+		.skip(1)
+		.filter_map(|directive| {
+			let directive = directive.trim_start();
+			let quote = directive
+				.chars()
+				.next()
+				.filter(|c| matches!(c, '"' | '\'' | '`'))?;
+			let (module, _) = directive[1..].split_once(quote)?;
+			let mut path = modules_dir.to_path_buf();
+			path.extend(module.split('.'));
+			path.set_extension("lua");
+			Some(path)
+		})
+		.collect()
+}
+
+impl Watcher {
+	/// Creates a watcher for the given file or directory, reading the current state of its files.
+	/// `modules_dir` is the directory where the modules loaded with `@import` are searched
+	pub fn new(
+		path: PathBuf,
+		base: Option<PathBuf>,
+		modules_dir: PathBuf,
+		include_lua: bool,
+	) -> io::Result<Self> {
+		let mut watcher = Self {
+			path,
+			base,
+			modules_dir,
+			include_lua,
+			files: AHashMap::new(),
+		};
+		watcher.files = watcher.scan()?;
+		Ok(watcher)
+	}
+
+	fn scan(&self) -> io::Result<AHashMap<PathBuf, FileState>> {
+		let mut found = Vec::new();
+		find_files(&self.path, String::new(), self.include_lua, &mut found)?;
+		let mut paths: Vec<PathBuf> = found.into_iter().map(|(path, _)| path).collect();
+		let mut modules = Vec::new();
+		for path in &paths {
+			if let Ok(code) = fs::read_to_string(path) {
+				modules.extend(imported_modules(&code, &self.modules_dir));
+			}
+		}
+		paths.extend(modules);
+		paths.extend(self.base.clone());
+		let mut files = AHashMap::with_capacity(paths.len());
+		for path in paths {
+			let modified = fs::metadata(&path).and_then(|metadata| metadata.modified());
+			let state = match (modified, self.files.get(&path)) {
+				(Err(_), _) => None,
+				// the content is only read again if the file was touched
+				(Ok(modified), Some(&Some((last, hash)))) if modified == last => {
+					Some((modified, hash))
+				}
+				(Ok(modified), _) => hash_file(&path).ok().map(|hash| (modified, hash)),
+			};
+			files.insert(path, state);
+		}
+		Ok(files)
+	}
+
+	/// Checks if any file was created, deleted or had its content changed since the last check
+	pub fn changed(&mut self) -> io::Result<bool> {
+		let files = self.scan()?;
+		let changed = files.len() != self.files.len()
+			|| files.iter().any(|(path, state)| {
+				let hash = state.map(|(_, hash)| hash);
+				self.files.get(path).map(|last| last.map(|(_, hash)| hash)) != Some(hash)
+			});
+		self.files = files;
+		Ok(changed)
+	}
+
+	/// Calls `build` now and every time the watched files change, printing its errors without stopping
+	pub fn watch(mut self, mut build: impl FnMut() -> Result<(), String>) -> Result<(), String> {
+		loop {
+			if let Err(error) = build() {
+				eprintln!("Error: {error}");
+			}
+			println!(
+				"Watching \"{}\" for changes...",
+				self.path.to_string_lossy()
+			);
+			loop {
+				thread::sleep(INTERVAL);
+				match self.changed() {
+					Ok(true) => break,
+					Ok(false) => {}
+					Err(error) => eprintln!("Error: {error}"),
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn detect_changes() {
+		let dir = std::env::temp_dir().join(format!("clue-watch-{}", std::process::id()));
+		fs::create_dir_all(dir.join("sub")).unwrap();
+		let main = dir.join("main.clue");
+		fs::write(&main, "@import \"lib.util\"\nprint(1)").unwrap();
+		let mut watcher = Watcher::new(dir.clone(), None, dir.clone(), false).unwrap();
+		assert!(!watcher.changed().unwrap());
+
+		// rewriting the same content does not count as a change
+		thread::sleep(Duration::from_millis(20));
+		fs::write(&main, "@import \"lib.util\"\nprint(1)").unwrap();
+		assert!(!watcher.changed().unwrap());
+
+		fs::write(&main, "@import \"lib.util\"\nprint(2)").unwrap();
+		assert!(watcher.changed().unwrap());
+		fs::write(dir.join("sub/other.clue"), "").unwrap();
+		assert!(watcher.changed().unwrap());
+		fs::create_dir_all(dir.join("lib")).unwrap();
+		fs::write(dir.join("lib/util.lua"), "return {}").unwrap();
+		assert!(watcher.changed().unwrap());
+		fs::write(dir.join("ignored.lua"), "").unwrap();
+		assert!(!watcher.changed().unwrap());
+
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -1,5 +1,8 @@
 use ahash::AHashMap;
-use clue::{code::*, compiler::*, env::Options, parser::*, preprocessor::*, scanner::*};
+use clue::{
+	code::*, compiler::*, diagnostic::Diagnostic, env::Options, parser::*, preprocessor::*,
+	scanner::*,
+};
 use clue_core as clue;
 use criterion::{criterion_group, criterion_main, Criterion};
 use crossbeam_queue::SegQueue;
@@ -31,7 +34,7 @@ fn compile_code(
 	name: &String,
 	scope: usize,
 	options: &Options,
-) -> Result<(String, String), String> {
+) -> Result<(String, String), Vec<Diagnostic>> {
 	let (mut codes, size) = codes;
 	let code = if codes.len() == 1 {
 		codes.pop_back().unwrap().0
@@ -66,12 +69,14 @@ fn compile_file_dir(
 
 		let (code, _static_vars) = match compile_code(codes, &variables, &realname, 2, options) {
 			Ok(t) => t,
-			Err(e) => {
+			Err(errors) => {
 				tx.send(ThreadData {
 					output: "".to_string(),
 				})
 				.unwrap();
-				eprintln!("Error: {}", e);
+				for error in errors {
+					eprintln!("{error}");
+				}
 				continue;
 			}
 		};
@@ -95,7 +100,7 @@ fn preprocess_file_dir(
 			),
 		};
 
-		let (file_codes, file_variables) = match read_file(filepath, &filename, options) {
+		let (file_codes, file_variables, _) = match read_file(filepath, &filename, options) {
 			Ok(t) => t,
 			Err(e) => {
 				tx.send(PreprocessorAnalyzerData {
@@ -135,7 +140,10 @@ fn check_for_files(
 			for file in check_for_files(filepath, realname + ".")? {
 				files.push(file)
 			}
-		} else if filepath.extension().map_or(false, |extension| extension == "clue") {
+		} else if filepath
+			.extension()
+			.is_some_and(|extension| extension == "clue")
+		{
 			files.push((filepath, realname));
 		}
 	}
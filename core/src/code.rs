@@ -9,6 +9,7 @@ use std::{
 		VecDeque,
 	},
 	ffi::OsString,
+	fmt,
 	hash::Hash,
 };
 
@@ -119,13 +120,13 @@ impl IntoIterator for Code {
 	}
 }
 
-impl ToString for Code {
-	fn to_string(&self) -> String {
+impl fmt::Display for Code {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		let mut result = String::with_capacity(self.len());
 		for c in self.clone().chars() {
 			result.push(c);
 		}
-		result
+		f.write_str(&result)
 	}
 }
 
@@ -281,7 +282,7 @@ impl Code {
 	}
 
 	/// Returns an iterator over the characters in the [`Code`] without consuming it.
-	pub fn iter(&self) -> Iter<CodeChar> {
+	pub fn iter(&self) -> Iter<'_, CodeChar> {
 		self.list.iter()
 	}
 
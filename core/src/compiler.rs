@@ -3,23 +3,26 @@
 //! The compiler module handles the compilation of a list of [`ComplexToken`] ([`Expression`]) into a Lua code.
 //! It exposes the [`Compiler`] struct which is used to compile to Lua.
 
+use ahash::AHashMap;
 use std::fmt::Write;
 use std::iter::{Iterator, Peekable};
 
 use crate::{
+	diagnostic::Diagnostic,
 	env::{ContinueMode, Options},
 	format_clue,
 	parser::{CodeBlock, ComplexToken, ComplexToken::*, Expression, FunctionArgs},
 	scanner::TokenType::*,
+	sourcemap,
 };
 
 /// The Compiler struct is used to compile a list of [`ComplexToken`] ([`Expression`]) into a lua code.
 ///
 /// # Example
 /// ```rust
-/// use clue_core::{compiler::*, env::Options, parser::*, scanner::*, Clue};
+/// use clue_core::{compiler::*, diagnostic::Diagnostic, env::Options, parser::*, scanner::*, Clue};
 ///
-/// fn main() -> Result<(), String> {
+/// fn main() -> Result<(), Vec<Diagnostic>> {
 ///     let options = Options::default();
 ///     let filename = String::from("file.clue");
 ///     let compiler = Compiler::new(&options, &filename);
@@ -35,6 +38,7 @@ use crate::{
 pub struct Compiler<'a> {
 	options: &'a Options,
 	filename: &'a String,
+	columns: Option<&'a AHashMap<usize, usize>>,
 }
 
 impl<'a> Compiler<'a> {
@@ -47,7 +51,33 @@ impl<'a> Compiler<'a> {
 	/// let compiler = Compiler::new(&options, &String::from("file.clue"));
 	/// ```
 	pub const fn new(options: &'a Options, filename: &'a String) -> Self {
-		Self { options, filename }
+		Self {
+			options,
+			filename,
+			columns: None,
+		}
+	}
+
+	/// Sets the column of every line of the code, used by the sourcemap markers.
+	/// Without them every statement is mapped to the first column of its line
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{code::Code, compiler::Compiler, env::Options, scanner::scan_code, sourcemap::line_columns};
+	///
+	/// let options = Options::default();
+	/// let filename = String::from("file.clue");
+	/// let tokens = scan_code(Code::from(("print(1)", 1, 1)), &filename).unwrap();
+	/// let columns = line_columns(&tokens);
+	/// let compiler = Compiler::new(&options, &filename).with_columns(&columns);
+	/// ```
+	pub const fn with_columns(mut self, columns: &'a AHashMap<usize, usize>) -> Self {
+		self.columns = Some(columns);
+		self
+	}
+
+	fn unexpected(&self, msg: impl Into<String>) -> Diagnostic {
+		Diagnostic::error("C0001", msg, self.filename.as_str())
 	}
 
 	fn indentate(&self, scope: usize) -> String {
@@ -71,8 +101,8 @@ impl<'a> Compiler<'a> {
 		&self,
 		list: Vec<T>,
 		separator: &str,
-		tostring: &mut impl FnMut(T) -> Result<String, String>,
-	) -> Result<String, String> {
+		tostring: &mut impl FnMut(T) -> Result<String, Diagnostic>,
+	) -> Result<String, Diagnostic> {
 		let mut result = String::new();
 		let end = list.len();
 		let mut start = 0usize;
@@ -86,11 +116,15 @@ impl<'a> Compiler<'a> {
 		Ok(result)
 	}
 
-	fn compile_identifiers(&self, names: Vec<String>) -> Result<String, String> {
+	fn compile_identifiers(&self, names: Vec<String>) -> Result<String, Diagnostic> {
 		self.compile_list(names, ", ", &mut Ok)
 	}
 
-	fn compile_expressions(&self, scope: usize, values: Vec<Expression>) -> Result<String, String> {
+	fn compile_expressions(
+		&self,
+		scope: usize,
+		values: Vec<Expression>,
+	) -> Result<String, Diagnostic> {
 		self.compile_list(values, ", ", &mut |expr| {
 			self.compile_expression(scope, expr)
 		})
@@ -101,7 +135,7 @@ impl<'a> Compiler<'a> {
 		scope: usize,
 		args: FunctionArgs,
 		code: CodeBlock,
-	) -> Result<(String, String), String> {
+	) -> Result<(String, String), Diagnostic> {
 		let mut code = self.compile_code_block(scope + self.options.env_debug as usize, "", code)?;
 		let args = self.compile_list(args, ", ", &mut |(arg, default)| {
 			if let Some((default, line)) = default {
@@ -179,42 +213,58 @@ impl<'a> Compiler<'a> {
 		scope: usize,
 		start: &str,
 		block: CodeBlock,
-	) -> Result<String, String> {
+	) -> Result<String, Diagnostic> {
 		let pre = self.indentate(scope);
 		let code = self.compile_tokens(scope + 1, block.code)?;
 		let debug = self.compile_debug_line(block.start, scope + 1, true);
+		let start_marker = self.compile_source_marker(block.start);
+		let end_marker = self.compile_source_marker(block.end);
 		Ok(if self.options.env_debug {
 			format!(
-				"{}\n{}\t{}--{}->{}\n{}\n{}",
-				start, pre, debug, block.start, block.end, code, pre
+				"{}{}\n{}\t{}--{}->{}\n{}\n{}{}",
+				start, start_marker, pre, debug, block.start, block.end, code, pre, end_marker
 			)
 		} else {
-			format_clue!(start, "\n", code, "\n", pre)
+			format_clue!(start, start_marker, "\n", code, "\n", pre, end_marker)
 		})
 	}
 
+	fn compile_source_marker(&self, line: usize) -> String {
+		if self.options.env_sourcemap {
+			let column = self
+				.columns
+				.and_then(|columns| columns.get(&line).copied())
+				.unwrap_or(1);
+			sourcemap::marker(self.filename, line, column)
+		} else {
+			String::new()
+		}
+	}
+
 	fn compile_debug_comment(&self, line: usize) -> String {
+		let marker = self.compile_source_marker(line);
 		if self.options.env_debug {
-			format!(" --{line}")
+			format!(" --{line}{marker}")
 		} else {
-			String::new()
+			marker
 		}
 	}
 
 	fn compile_debug_line(&self, line: usize, scope: usize, indentate_last: bool) -> String {
+		let marker = self.compile_source_marker(line);
 		if self.options.env_debug {
 			let debug = format_clue!("_clueline = ", line.to_string(), ";");
 			if indentate_last {
-				format_clue!(debug, "\n", self.indentate(scope))
+				format_clue!(debug, "\n", self.indentate(scope), marker)
 			} else {
-				format_clue!("\n", self.indentate(scope), debug)
+				format_clue!("\n", self.indentate(scope), debug, marker)
 			}
 		} else {
-			String::new()
+			marker
 		}
 	}
 
-	fn compile_identifier(&self, scope: usize, expr: Expression) -> Result<String, String> {
+	fn compile_identifier(&self, scope: usize, expr: Expression) -> Result<String, Diagnostic> {
 		let mut result = String::with_capacity(32);
 		for t in expr {
 			result += &match t {
@@ -223,13 +273,13 @@ impl<'a> Compiler<'a> {
 				CALL(args) => {
 					format_clue!("(", self.compile_expressions(scope, args.clone())?, ")")
 				}
-				_ => return Err(String::from("Unexpected ComplexToken found")),
+				_ => return Err(self.unexpected("Unexpected ComplexToken found")),
 			}
 		}
 		Ok(result)
 	}
 
-	fn compile_expression(&self, mut scope: usize, expr: Expression) -> Result<String, String> {
+	fn compile_expression(&self, mut scope: usize, expr: Expression) -> Result<String, Diagnostic> {
 		let mut result = String::with_capacity(64);
 		for t in expr {
 			result += &match t {
@@ -295,7 +345,7 @@ impl<'a> Compiler<'a> {
 				IDENT { expr, .. } => self.compile_identifier(scope, expr)?,
 				CALL(args) => format!("({})", self.compile_expressions(scope, args)?),
 				EXPR(expr) => format!("({})", self.compile_expression(scope, expr)?),
-				_ => return Err(String::from("Unexpected ComplexToken found")),
+				_ => return Err(self.unexpected("Unexpected ComplexToken found")),
 			}
 		}
 		Ok(result)
@@ -307,7 +357,7 @@ impl<'a> Compiler<'a> {
 		condition: Expression,
 		code: CodeBlock,
 		next: Option<Box<ComplexToken>>,
-	) -> Result<String, String> {
+	) -> Result<String, Diagnostic> {
 		let condition = self.compile_expression(scope, condition)?;
 		let code = self.compile_code_block(scope, "then", code)?;
 		let next = if let Some(next) = next {
@@ -319,7 +369,7 @@ impl<'a> Compiler<'a> {
 						next,
 					} => self.compile_elseif_chain(scope, condition, code, next)?,
 					DO_BLOCK(code) => self.compile_code_block(scope, "", code)?,
-					_ => return Err(String::from("Unexpected ComplexToken found")),
+					_ => return Err(self.unexpected("Unexpected ComplexToken found")),
 				}
 		} else {
 			String::new()
@@ -334,9 +384,9 @@ impl<'a> Compiler<'a> {
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::{compiler::*, env::Options, parser::*, scanner::*, Clue};
+	/// use clue_core::{compiler::*, diagnostic::Diagnostic, env::Options, parser::*, scanner::*, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///     let options = Options::default();
 	///     let filename = String::from("file.clue");
 	///     let compiler = Compiler::new(&options, &filename);
@@ -349,7 +399,7 @@ impl<'a> Compiler<'a> {
 	///     Ok(())
 	/// }
 	/// ```
-	pub fn compile_tokens(&self, scope: usize, ctokens: Expression) -> Result<String, String> {
+	pub fn compile_tokens(&self, scope: usize, ctokens: Expression) -> Result<String, Diagnostic> {
 		let mut result = self.indentate(scope);
 		let ctokens = &mut ctokens.into_iter().peekable();
 		while let Some(t) = ctokens.next() {
@@ -382,7 +432,7 @@ impl<'a> Compiler<'a> {
 								}
 							};
 							write!(result, "rawset(_G, \"{name}\", {value});{line}{end}")
-								.map_err(|e| e.to_string())?
+								.map_err(|e| self.unexpected(e.to_string()))?
 						}
 						result
 					} else {
@@ -430,7 +480,7 @@ impl<'a> Compiler<'a> {
 								EXPONENTIATE => " ^ ",
 								CONCATENATE => " .. ",
 								MODULATE => " % ",
-								_ => return Err(String::from("Unexpected alter type found")),
+								_ => return Err(self.unexpected("Unexpected alter type found")),
 							}
 						}) + &self.compile_expression(scope, expr)?)
 					})?;
@@ -733,7 +783,7 @@ impl<'a> Compiler<'a> {
 					)
 				}
 				BREAK_LOOP => String::from("break;") + &self.indentate_if(ctokens, scope),
-				_ => return Err(String::from("Unexpected ComplexToken found")),
+				_ => return Err(self.unexpected("Unexpected ComplexToken found")),
 			}
 		}
 		Ok(result)
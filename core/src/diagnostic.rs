@@ -0,0 +1,403 @@
+//! The `diagnostic` module contains the [`Diagnostic`] type returned by every step of the compilation process
+//!
+//! A [`Diagnostic`] carries its [`Severity`], the file and [`Span`] it refers to, the message,
+//! a stable code and optional notes, so tools embedding Clue can place errors without parsing text.
+//! [`Diagnostic::render`] turns it into a rustc-style report with the offending source line underlined.
+//!
+//! The codes are grouped by the step that produces them and never change meaning:
+//!
+//! | Code  | Meaning                                              |
+//! |-------|------------------------------------------------------|
+//! | F0001 | A file could not be read or written                  |
+//! | F0002 | Invalid source map                                   |
+//! | F0003 | Invalid lint configuration                           |
+//! | P0001 | Invalid character                                    |
+//! | P0002 | Expected something else (preprocessor)               |
+//! | P0003 | Expected something before something else             |
+//! | P0004 | Unterminated string (preprocessor)                   |
+//! | P0005 | Unknown directive or `@if` function                  |
+//! | P0006 | Invalid, incompatible or deprecated `@version`       |
+//! | P0007 | Error raised by `@error`                             |
+//! | P0008 | Preprocessor variable not found                      |
+//! | P0009 | Macro called incorrectly                             |
+//! | P0010 | Too many nested variables or macros                  |
+//! | S0001 | Unexpected character                                 |
+//! | S0002 | Malformed number                                     |
+//! | S0003 | Unterminated string                                  |
+//! | S0004 | Reserved Lua keyword used as a name                  |
+//! | E0001 | Expected something else (parser)                     |
+//! | E0002 | Expected something before something else (parser)    |
+//! | E0003 | Unexpected token                                     |
+//! | E0004 | Invalid use of an operator                           |
+//! | E0005 | Invalid metatable or metamethod                      |
+//! | E0006 | Invalid enum value                                   |
+//! | E0007 | Misplaced `default` case                             |
+//! | E0008 | Invalid safe indexing                                |
+//! | E0009 | Declaration missing `local`, `global` or `static`    |
+//! | E0010 | Invalid type annotation                              |
+//! | E0011 | Invalid class member                                 |
+//! | C0001 | Unexpected syntax tree node (internal compiler bug)  |
+//! | C0002 | Formatting would change the code (formatter bug)     |
+//! | L0001 | Unused local variable                                |
+//! | L0002 | Unused function argument                             |
+//! | L0003 | Local variable shadowing another one                 |
+//! | L0004 | Assignment to an undeclared global variable          |
+//! | L0005 | Unreachable code                                     |
+//! | L0006 | `match` without a `default` case                     |
+//! | L0007 | Empty `catch` block                                  |
+//! | L0008 | Duplicate table key                                  |
+//! | L0009 | Unknown rule in a `lint: allow` comment              |
+//! | T0001 | Mismatched types                                     |
+//! | T0002 | Wrong number of arguments                            |
+
+use crate::sourcemap::escape_json;
+use std::{
+	error::Error,
+	fmt,
+	ops::{Deref, DerefMut},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+/// How serious a [`Diagnostic`] is
+pub enum Severity {
+	/// Compilation cannot continue
+	Error,
+
+	/// Something is likely wrong but compilation can continue
+	Warning,
+
+	/// Additional information
+	Note,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Severity::Error => "error",
+			Severity::Warning => "warning",
+			Severity::Note => "note",
+		})
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A range of characters in a file, lines and columns start from 1
+/// The end column is exclusive
+pub struct Span {
+	/// The line where the span starts
+	pub line: usize,
+
+	/// The column where the span starts
+	pub column: usize,
+
+	/// The line where the span ends
+	pub end_line: usize,
+
+	/// The column where the span ends
+	pub end_column: usize,
+}
+
+impl Span {
+	/// Creates a new [`Span`] from its start and end position
+	pub const fn new(line: usize, column: usize, end_line: usize, end_column: usize) -> Self {
+		Self {
+			line,
+			column,
+			end_line,
+			end_column,
+		}
+	}
+
+	/// Creates a new [`Span`] covering a single character
+	pub const fn point(line: usize, column: usize) -> Self {
+		Self::new(line, column, line, column + 1)
+	}
+
+	/// Creates a new [`Span`] covering `len` characters on a single line
+	pub fn sized(line: usize, column: usize, len: usize) -> Self {
+		Self::new(line, column, line, column + len.max(1))
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+	feature = "serde",
+	serde(transparent, bound(deserialize = "'de: 'static"))
+)]
+/// A message produced while compiling, usually an error.
+/// Its [`DiagnosticData`] is boxed so that a `Result` with a [`Diagnostic`] as error stays small,
+/// the fields can be used directly through [`Deref`]
+pub struct Diagnostic(Box<DiagnosticData>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// The content of a [`Diagnostic`]
+pub struct DiagnosticData {
+	/// How serious the diagnostic is
+	pub severity: Severity,
+
+	/// The stable code of the diagnostic, see the [module documentation](self)
+	pub code: &'static str,
+
+	/// The name of the file the diagnostic refers to
+	pub file: String,
+
+	/// The location in the file, if the diagnostic refers to a specific part of it
+	pub span: Option<Span>,
+
+	/// The main message
+	pub message: String,
+
+	/// Additional notes
+	pub notes: Vec<String>,
+
+	/// Suggestions on how to fix the problem
+	pub help: Vec<String>,
+}
+
+impl Diagnostic {
+	/// Creates a new [`Diagnostic`] with no location or notes
+	pub fn new(
+		severity: Severity,
+		code: &'static str,
+		message: impl Into<String>,
+		file: impl Into<String>,
+	) -> Self {
+		Self(Box::new(DiagnosticData {
+			severity,
+			code,
+			file: file.into(),
+			span: None,
+			message: message.into(),
+			notes: Vec::new(),
+			help: Vec::new(),
+		}))
+	}
+
+	/// Creates a new error [`Diagnostic`] with no location or notes
+	pub fn error(code: &'static str, message: impl Into<String>, file: impl Into<String>) -> Self {
+		Self::new(Severity::Error, code, message, file)
+	}
+
+	/// Creates a new warning [`Diagnostic`] with no location or notes
+	pub fn warning(
+		code: &'static str,
+		message: impl Into<String>,
+		file: impl Into<String>,
+	) -> Self {
+		Self::new(Severity::Warning, code, message, file)
+	}
+
+	/// Sets the location of the diagnostic
+	pub fn with_span(mut self, span: Span) -> Self {
+		self.span = Some(span);
+		self
+	}
+
+	/// Sets the location of the diagnostic to a single character
+	pub fn at(self, line: usize, column: usize) -> Self {
+		self.with_span(Span::point(line, column))
+	}
+
+	/// Adds a note to the diagnostic
+	pub fn with_note(mut self, note: impl Into<String>) -> Self {
+		self.notes.push(note.into());
+		self
+	}
+
+	/// Adds a suggestion on how to fix the problem to the diagnostic
+	pub fn with_help(mut self, help: impl Into<String>) -> Self {
+		self.help.push(help.into());
+		self
+	}
+
+	/// Returns `true` if the diagnostic is an error
+	pub fn is_error(&self) -> bool {
+		self.severity == Severity::Error
+	}
+
+	/// Turns the diagnostic into a JSON object, with the same fields as the struct
+	/// and the severity written in lowercase
+	pub fn to_json(&self) -> String {
+		let span = match self.span {
+			Some(span) => format!(
+				"{{\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{}}}",
+				span.line, span.column, span.end_line, span.end_column
+			),
+			None => String::from("null"),
+		};
+		let list = |strings: &[String]| {
+			let strings: Vec<String> = strings.iter().map(|string| escape_json(string)).collect();
+			format!("[{}]", strings.join(","))
+		};
+		format!(
+			"{{\"severity\":\"{}\",\"code\":\"{}\",\"file\":{},\"span\":{},\"message\":{},\"notes\":{},\"help\":{}}}",
+			self.severity,
+			self.code,
+			escape_json(&self.file),
+			span,
+			escape_json(&self.message),
+			list(&self.notes),
+			list(&self.help)
+		)
+	}
+}
+
+impl Deref for Diagnostic {
+	type Target = DiagnosticData;
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl DerefMut for Diagnostic {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.file)?;
+		if let Some(span) = self.span {
+			write!(f, ":{}:{}", span.line, span.column)?;
+		}
+		write!(f, ": {}[{}]: {}", self.severity, self.code, self.message)?;
+		for note in &self.notes {
+			write!(f, "\n  = note: {note}")?;
+		}
+		for help in &self.help {
+			write!(f, "\n  = help: {help}")?;
+		}
+		Ok(())
+	}
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const CYAN: &str = "\x1b[1;36m";
+const BLUE: &str = "\x1b[1;34m";
+
+fn paint(text: &str, style: &str, color: bool) -> String {
+	if color {
+		format!("{style}{text}{RESET}")
+	} else {
+		text.to_owned()
+	}
+}
+
+// Columns count bytes, tabs are expanded so the carets line up with the printed line
+fn display_width(text: &str) -> usize {
+	text.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+fn byte_slice(line: &str, start: usize, end: usize) -> &str {
+	let mut start = start.min(line.len());
+	let mut end = end.clamp(start, line.len());
+	while !line.is_char_boundary(start) {
+		start -= 1;
+	}
+	while !line.is_char_boundary(end) {
+		end += 1;
+	}
+	&line[start..end]
+}
+
+impl Diagnostic {
+	/// Renders the diagnostic like rustc does, showing the line of `source` the diagnostic
+	/// points to with its span underlined, followed by the notes and help messages.
+	/// If `source` is `None` or the span is outside of it only the header is rendered.
+	/// When `color` is `false` no ANSI escape codes are used, which is useful for logs.
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::diagnostic::{Diagnostic, Span};
+	///
+	/// let diagnostic = Diagnostic::error("E0001", "Expected '<expr>', got ')'", "main.clue")
+	///     .with_span(Span::sized(1, 11, 1));
+	/// let rendered = diagnostic.render(Some("local x = )"), false);
+	/// assert_eq!(
+	///     rendered,
+	///     "error[E0001]: Expected '<expr>', got ')'\n --> main.clue:1:11\n  |\n1 | local x = )\n  |           ^\n"
+	/// );
+	/// ```
+	pub fn render(&self, source: Option<&str>, color: bool) -> String {
+		let style = match self.severity {
+			Severity::Error => RED,
+			Severity::Warning => YELLOW,
+			Severity::Note => GREEN,
+		};
+		let mut result = format!(
+			"{}{}\n",
+			paint(&format!("{}[{}]", self.severity, self.code), style, color),
+			paint(&format!(": {}", self.message), BOLD, color),
+		);
+		let snippet = self.span.and_then(|span| {
+			let line = source?.lines().nth(span.line.checked_sub(1)?)?;
+			Some((span, line.trim_end_matches('\r')))
+		});
+		let gutter = match snippet {
+			Some((span, _)) => " ".repeat(span.line.to_string().len()),
+			None => String::from(" "),
+		};
+		let location = match self.span {
+			Some(span) => format!("{}:{}:{}", self.file, span.line, span.column),
+			None => self.file.clone(),
+		};
+		result += &format!("{gutter}{} {location}\n", paint("-->", BLUE, color));
+		if let Some((span, line)) = snippet {
+			let start = span.column.saturating_sub(1);
+			let end = if span.end_line == span.line {
+				span.end_column.saturating_sub(1)
+			} else {
+				line.len()
+			};
+			let padding = display_width(byte_slice(line, 0, start));
+			let underline = display_width(byte_slice(line, start, end)).max(1);
+			let bar = paint("|", BLUE, color);
+			result += &format!("{gutter} {bar}\n");
+			result += &format!(
+				"{} {bar} {}\n",
+				paint(&span.line.to_string(), BLUE, color),
+				line.replace('\t', "    ")
+			);
+			result += &format!(
+				"{gutter} {bar} {}{}\n",
+				" ".repeat(padding),
+				paint(&"^".repeat(underline), style, color)
+			);
+		}
+		for note in &self.notes {
+			result += &format!("{gutter} {} {note}\n", paint("= note:", BOLD, color));
+		}
+		for help in &self.help {
+			result += &format!("{gutter} {} {help}\n", paint("= help:", CYAN, color));
+		}
+		result
+	}
+}
+
+impl Error for Diagnostic {}
+
+impl From<Diagnostic> for String {
+	fn from(diagnostic: Diagnostic) -> Self {
+		diagnostic.to_string()
+	}
+}
+
+impl From<Diagnostic> for Vec<Diagnostic> {
+	fn from(diagnostic: Diagnostic) -> Self {
+		vec![diagnostic]
+	}
+}
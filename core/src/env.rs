@@ -2,7 +2,7 @@
 //!
 //! It contains [`Options`] struct is the main struct used to store the compiler options,
 //! and is used by the [`Compiler`](crate::compiler::Compiler) to determine how to compile the code
-//! and also other helpful enums such as [`LuaVersion`], [`BitwiseMode`] and [`ContinueMode`]
+//! and also other helpful enums such as [`LuaVersion`], [`BitwiseMode`], [`ContinueMode`], [`TypesMode`] and [`LuaSTD`]
 
 use std::path::PathBuf;
 
@@ -38,22 +38,35 @@ pub enum ContinueMode {
 	MoonScript,
 }
 
-/*
-#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// How the type annotations are checked
 pub enum TypesMode {
+	#[default]
+	/// The annotations are only removed from the output
 	NONE,
+
+	/// Mismatched types are reported as warnings
 	WARN,
+
+	/// Mismatched types are reported as errors
 	STRICT,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// The standard library declared when checking types
 pub enum LuaSTD {
+	/// No standard library, every global is unknown
 	NONE,
+
+	#[default]
+	/// The LuaJIT (Lua 5.1) standard library, with the `bit` and `jit` modules
 	LUAJIT,
+
+	/// The Lua 5.4 standard library
 	LUA54,
-	//ADD MORE LATER
 }
-*/
 
 #[derive(Default, Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -146,8 +159,22 @@ pub struct Options {
 	/// Whether to print the symbol table
 	pub env_symbols: bool,
 
-	//pub env_types: TypesMode,
-	//pub env_std: LuaSTD,
+	/// Whether to use colors when rendering diagnostics
+	pub env_color: bool,
+
+	/// Whether to mark the compiled code with the lines it comes from to build a source map,
+	/// the markers must be removed with [`SourceMap::extract`](crate::sourcemap::SourceMap::extract)
+	pub env_sourcemap: bool,
+
+	/// How the type annotations are checked
+	pub env_types: TypesMode,
+
+	/// The standard library declared when checking types
+	pub env_std: LuaSTD,
+
+	/// Whether `@import` always loads the modules with `require`,
+	/// used when every file is compiled into its own Lua file instead of a bundle
+	pub env_require: bool,
 }
 
 impl Options {
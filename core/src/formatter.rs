@@ -0,0 +1,421 @@
+//! The formatter rewrites Clue code with a consistent layout.
+//!
+//! The [`ComplexToken`](crate::parser::ComplexToken) tree is already lowered towards Lua
+//! and the preprocessor removes comments and directives, so the layout comes from the [`LosslessCode`]
+//! while the tree decides whether the result is correct: the original and the formatted code
+//! must be lowered to the same tree, checked by compiling both of them without debug information.
+//! In the formatted code indentation is made of tabs, opening braces of code blocks stay on the line of their statement,
+//! `else`, `elseif` and `catch` follow the closing brace and operators are surrounded by spaces.
+//! Comments, strings and preprocessor directives are kept exactly as they were written,
+//! line breaks are kept (at most one blank line in a row) and the tokens of the code never change.
+
+use crate::{
+	compiler::Compiler,
+	diagnostic::Diagnostic,
+	env::Options,
+	lossless::{is_block_directive, LosslessCode, SyntaxKind, TriviaKind},
+	parser::parse_source,
+	scanner::TokenType::*,
+};
+
+/// The keywords that are followed by a code block
+const BLOCK_KEYWORDS: &[&str] = &[
+	"if",
+	"elseif",
+	"else",
+	"while",
+	"until",
+	"for",
+	"loop",
+	"fn",
+	"method",
+	"match",
+	"try",
+	"catch",
+	"enum",
+	"struct",
+	"constructor",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum PieceKind {
+	Word,
+	Keyword,
+	String,
+	Symbol,
+	Open,
+	Close,
+	LineComment,
+	BlockComment,
+	LineDirective,
+	BlockDirective,
+}
+
+#[derive(Debug)]
+struct Piece<'a> {
+	kind: PieceKind,
+	text: &'a str,
+	newlines: usize,
+}
+
+impl Piece<'_> {
+	fn is(&self, kind: PieceKind, text: &str) -> bool {
+		self.kind == kind && self.text == text
+	}
+
+	fn is_accessor(&self) -> bool {
+		self.kind == PieceKind::Symbol && matches!(self.text, "." | "::" | "?." | "?::")
+	}
+
+	fn is_value(&self) -> bool {
+		match self.kind {
+			PieceKind::Word | PieceKind::String | PieceKind::Close => true,
+			PieceKind::Keyword => matches!(self.text, "true" | "false" | "nil"),
+			PieceKind::Symbol => self.text == "...",
+			_ => false,
+		}
+	}
+}
+
+/// Turns the tokens and the trivia of the code into pieces, counting the line breaks between them
+fn split(code: &LosslessCode) -> Vec<Piece<'_>> {
+	let mut pieces = Vec::new();
+	let mut newlines = 0;
+	let tokens = code
+		.tokens
+		.iter()
+		.map(|token| (&token.leading, Some(token)));
+	for (leading, token) in tokens.chain([(&code.trailing, None)]) {
+		for trivia in leading {
+			let kind = match trivia.kind {
+				TriviaKind::Whitespace => {
+					newlines += trivia.text.matches('\n').count();
+					continue;
+				}
+				TriviaKind::LineComment => PieceKind::LineComment,
+				TriviaKind::BlockComment => PieceKind::BlockComment,
+				TriviaKind::Directive if is_block_directive(&trivia.text.as_bytes()[1..]) => {
+					PieceKind::BlockDirective
+				}
+				TriviaKind::Directive => PieceKind::LineDirective,
+			};
+			pieces.push(Piece {
+				kind,
+				text: trivia.text.trim_end(),
+				newlines,
+			});
+			newlines = 0;
+		}
+		let Some(token) = token else {
+			break;
+		};
+		let kind = match token.kind {
+			SyntaxKind::Token(STRING) => PieceKind::String,
+			SyntaxKind::Token(
+				ROUND_BRACKET_OPEN | SQUARE_BRACKET_OPEN | CURLY_BRACKET_OPEN | SAFE_CALL
+				| SAFE_SQUARE_BRACKET,
+			) => PieceKind::Open,
+			SyntaxKind::Token(
+				ROUND_BRACKET_CLOSED | SQUARE_BRACKET_CLOSED | CURLY_BRACKET_CLOSED,
+			) => PieceKind::Close,
+			SyntaxKind::Token(IDENTIFIER | NUMBER) | SyntaxKind::Variable => PieceKind::Word,
+			SyntaxKind::Token(kind) if kind >= IF && kind < EOF => PieceKind::Keyword,
+			_ => PieceKind::Symbol,
+		};
+		pieces.push(Piece {
+			kind,
+			text: &token.text,
+			newlines,
+		});
+		newlines = 0;
+	}
+	pieces
+}
+
+/// Checks if two symbols written without a space between them would become a different token
+fn would_merge(prev: &str, cur: &str) -> bool {
+	let joined = LosslessCode::new(&format!("{prev}{cur}"));
+	joined.tokens.first().map_or(true, |token| token.text != prev)
+}
+
+fn needs_space(prev: &Piece, cur: &Piece, prev_unary: bool, block: bool) -> bool {
+	use PieceKind::*;
+	let space = match (prev.kind, cur.kind) {
+		(_, LineComment | BlockComment | LineDirective | BlockDirective) => true,
+		(Open, Close) => false,
+		(Open, _) | (_, Close) => block,
+		(_, Symbol) if matches!(cur.text, "," | ";") => false,
+		(Symbol, _) if matches!(prev.text, "," | ";") => true,
+		_ if prev.is_accessor() || cur.is_accessor() => false,
+		(Symbol, Symbol) if prev.text == "-" && cur.text == "-" => true,
+		_ if prev_unary || prev.text == "$" => false,
+		(_, Open) => match cur.text {
+			"(" | "[" => {
+				!(prev.is_value() || prev.is(Keyword, "fn") || prev.is(Keyword, "constructor"))
+			}
+			"{" => true,
+			_ => false,
+		},
+		_ => true,
+	};
+	space
+		|| prev.kind == Symbol
+			&& matches!(cur.kind, Symbol | Open)
+			&& would_merge(prev.text, cur.text)
+}
+
+struct Bracket {
+	indent: usize,
+	block: bool,
+}
+
+/// Lays out the pieces of the code again
+fn print(pieces: &[Piece]) -> String {
+	let mut output = String::new();
+	let mut stack: Vec<Bracket> = Vec::new();
+	let mut pending_block: Option<usize> = None;
+	let mut line_indent = 0;
+	let mut prev: Option<(&Piece, bool)> = None;
+	// the `?` waiting for the `:` of their ternary and the depth of the type annotation being written
+	let mut ternaries = 0usize;
+	let mut annotation: Option<usize> = None;
+	for piece in pieces {
+		if annotation.is_some_and(|depth| {
+			piece.newlines > 0
+				|| piece.kind == PieceKind::Close && stack.len() <= depth
+				|| stack.len() == depth
+					&& (piece.is(PieceKind::Open, "{")
+						|| piece.kind == PieceKind::Symbol && matches!(piece.text, "=" | "," | ";"))
+		}) {
+			annotation = None;
+		}
+		let type_symbol = piece.kind == PieceKind::Symbol
+			&& match piece.text {
+				":" => ternaries == 0,
+				"?" => annotation.is_some(),
+				_ => false,
+			};
+		let block = match piece.kind {
+			PieceKind::Open => piece.text == "{" && pending_block == Some(stack.len()),
+			PieceKind::Close => stack.last().is_some_and(|bracket| bracket.block),
+			_ => {
+				prev.is_some_and(|(prev, _)| prev.kind == PieceKind::Open)
+					&& stack.last().is_some_and(|bracket| bracket.block)
+			}
+		};
+		let unary = match piece.text {
+			"!" | "#" | "~" => piece.kind == PieceKind::Symbol,
+			"-" => !prev
+				.is_some_and(|(prev, _)| prev.is_value() || prev.is(PieceKind::Keyword, "meta")),
+			_ => false,
+		};
+		match prev {
+			None => {}
+			Some((last, last_unary)) => {
+				let joins = !matches!(last.kind, PieceKind::LineComment | PieceKind::LineDirective)
+					&& (piece.kind == PieceKind::Open && block
+						|| last.is(PieceKind::Close, "}")
+							&& piece.kind == PieceKind::Keyword
+							&& matches!(piece.text, "elseif" | "else" | "catch"));
+				if piece.newlines > 0 && !joins {
+					output.push('\n');
+					if piece.newlines > 1
+						&& last.kind != PieceKind::Open
+						&& piece.kind != PieceKind::Close
+					{
+						output.push('\n');
+					}
+					line_indent = match piece.kind {
+						PieceKind::Close => stack.last().map_or(0, |bracket| bracket.indent),
+						_ => stack.last().map_or(0, |bracket| bracket.indent + 1),
+					};
+					output.extend(std::iter::repeat('\t').take(line_indent));
+				} else if !type_symbol && needs_space(last, piece, last_unary, block) {
+					output.push(' ');
+				}
+			}
+		}
+		output += piece.text;
+		match piece.kind {
+			PieceKind::Open => {
+				if block {
+					pending_block = None;
+				}
+				stack.push(Bracket {
+					indent: line_indent,
+					block,
+				});
+			}
+			PieceKind::Close => {
+				stack.pop();
+				if pending_block.is_some_and(|depth| depth > stack.len()) {
+					pending_block = None;
+				}
+			}
+			// an `until` right after a closing brace ends a `loop` instead of starting a new one
+			PieceKind::Keyword
+				if BLOCK_KEYWORDS.contains(&piece.text)
+					&& !(piece.text == "until"
+						&& prev.is_some_and(|(prev, _)| prev.is(PieceKind::Close, "}"))) =>
+			{
+				pending_block = Some(stack.len())
+			}
+			PieceKind::BlockDirective => pending_block = Some(stack.len()),
+			PieceKind::Symbol if piece.text == "=>" => pending_block = Some(stack.len()),
+			PieceKind::Symbol if piece.text == "?" && !type_symbol => ternaries += 1,
+			PieceKind::Symbol if piece.text == ":" => match type_symbol {
+				true => annotation = Some(stack.len()),
+				false => ternaries -= 1,
+			},
+			_ => {}
+		}
+		prev = Some((piece, unary));
+	}
+	if !output.is_empty() {
+		output.push('\n');
+	}
+	output
+}
+
+/// Formats the given Clue code
+/// It takes the code, the name of the file (used for the errors) and the options used to preprocess it
+///
+/// # Errors
+/// If the code cannot be compiled it is not formatted and every error found will be returned
+///
+/// # Examples
+/// ```
+/// use clue_core::{diagnostic::Diagnostic, env::Options, formatter::format_code};
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let filename = String::from("example.clue");
+///     let code = "local fn add(a,b)\n{\n  return a+b // sum\n}\n";
+///     let formatted = format_code(code, &filename, &Options::default())?;
+///     assert_eq!(formatted, "local fn add(a, b) {\n\treturn a + b // sum\n}\n");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn format_code(
+	code: &str,
+	filename: &String,
+	options: &Options,
+) -> Result<String, Vec<Diagnostic>> {
+	let options = Options {
+		env_debug: false,
+		..options.clone()
+	};
+	let expected = lower(code, filename, &options)?;
+	let formatted = print(&split(&LosslessCode::new(code)));
+	match lower(&formatted, filename, &options) {
+		Ok(found) if found == expected => Ok(formatted),
+		_ => Err(vec![Diagnostic::error(
+			"C0002",
+			"Formatting would change the meaning of the code",
+			filename.as_str(),
+		)]),
+	}
+}
+
+/// Parses the code and compiles its tree, to compare it with the tree of the formatted code
+fn lower(code: &str, filename: &String, options: &Options) -> Result<String, Vec<Diagnostic>> {
+	let ctokens = parse_source(code, filename, options)?;
+	Ok(Compiler::new(options, filename).compile_tokens(0, ctokens)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		preprocessor::{preprocess_code, preprocess_codes},
+		scanner::{scan_code, TokenType},
+	};
+	use std::fs;
+
+	fn tokens(code: &str) -> Vec<(TokenType, String)> {
+		let filename = String::from("test.clue");
+		let mut code = code.to_owned();
+		let (codes, variables, ..) = preprocess_code(
+			unsafe { code.as_bytes_mut() },
+			1,
+			false,
+			&filename,
+			&Options::default(),
+		)
+		.unwrap();
+		let codes = preprocess_codes(0, codes, &variables, &filename).unwrap();
+		scan_code(codes, &filename)
+			.unwrap()
+			.into_iter()
+			.map(|token| (token.kind, token.lexeme))
+			.collect()
+	}
+
+	fn format(code: &str) -> String {
+		format_code(code, &String::from("test.clue"), &Options::default()).unwrap()
+	}
+
+	#[test]
+	fn examples_round_trip() {
+		let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/../examples");
+		for entry in fs::read_dir(examples).unwrap() {
+			let code = fs::read_to_string(entry.unwrap().path()).unwrap();
+			let formatted = format(&code);
+			assert_eq!(tokens(&code), tokens(&formatted));
+			assert_eq!(format(&formatted), formatted);
+		}
+	}
+
+	#[test]
+	fn lowered_tree() {
+		let filename = String::from("test.clue");
+		let lower = |code| lower(code, &filename, &Options::default()).unwrap();
+		let expected = lower("local x = 1\nprint(x)\n");
+		assert_eq!(lower("local x=1\n\n\nprint( x ) // x\n"), expected);
+		assert_ne!(lower("local x = 1\nprint(-x)\n"), expected);
+	}
+
+	#[test]
+	fn layout() {
+		assert_eq!(
+			format(concat!(
+				"local t={1,2,[\"a\"]=-3}\n\n\n",
+				"if !t.a&&#t>0\n{ print(t [1]) }\n",
+				"else {\n  /* keep */ t.b=fn(x){return x/_2}\n}\n",
+				"try{ error(\"x\") }\ncatch e{}\n",
+			)),
+			concat!(
+				"local t = {1, 2, [\"a\"] = -3}\n\n",
+				"if !t.a && #t > 0 { print(t[1]) } else {\n",
+				"\t/* keep */ t.b = fn(x) { return x /_ 2 }\n}\n",
+				"try { error(\"x\") } catch e {}\n",
+			)
+		);
+	}
+
+	#[test]
+	fn type_annotations() {
+		assert_eq!(
+			format("local x : number ? = a?b:c\nlocal fn f(a:string,b:fn(number):string|nil) : any {}\n"),
+			concat!(
+				"local x: number? = a ? b : c\n",
+				"local fn f(a: string, b: fn(number): string | nil): any {}\n",
+			)
+		);
+	}
+
+	#[test]
+	fn directives() {
+		let code = concat!(
+			"@define NAME \"x\"   \n@ifdef CLUE_UNDEFINED{\n@error missing\n}\n",
+			"@macro m(a) {\n$a+1\n}\nprint($m!(  2 ),$NAME)\n",
+		);
+		assert_eq!(
+			format(code),
+			concat!(
+				"@define NAME \"x\"\n@ifdef CLUE_UNDEFINED {\n\t@error missing\n}\n",
+				"@macro m(a) {\n\t$a + 1\n}\nprint($m!(2), $NAME)\n",
+			)
+		);
+	}
+}
@@ -8,21 +8,30 @@ use std::{
 	fmt::Display,
 	fs,
 	path::{Path, PathBuf},
+	sync::{Mutex, MutexGuard, PoisonError},
 };
 
 use code::Code;
 use compiler::Compiler;
-use env::{BitwiseMode, ContinueMode, LuaVersion, Options};
-use parser::{parse_tokens, Expression};
+use diagnostic::Diagnostic;
+use env::{BitwiseMode, ContinueMode, LuaSTD, LuaVersion, Options, TypesMode};
+use parser::{parse_tokens_with_warnings, Expression};
 use preprocessor::{preprocess_code, preprocess_codes, read_file};
 use scanner::{scan_code, Token};
 
 pub mod code;
 pub mod compiler;
+pub mod diagnostic;
 pub mod env;
+pub mod formatter;
+pub mod lint;
+pub mod lossless;
 pub mod parser;
 pub mod preprocessor;
 pub mod scanner;
+pub mod sourcemap;
+pub mod symbols;
+pub mod types;
 
 #[macro_export]
 /// Check whether `tocheck` is `Ok` or `Err`
@@ -65,6 +74,7 @@ macro_rules! format_clue {
 /// It's recommended to use this API instead of the lower level APIs unless you need to
 pub struct Clue {
 	options: Options,
+	warnings: Mutex<Vec<Diagnostic>>,
 }
 
 impl Clue {
@@ -72,9 +82,31 @@ impl Clue {
 	pub fn new() -> Self {
 		Clue {
 			options: Options::default(),
+			warnings: Mutex::new(Vec::new()),
 		}
 	}
 
+	/// Returns the warnings found since the last call, like the ones of the type checker
+	/// when the `types` option is [`TypesMode::WARN`]
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{env::TypesMode, Clue};
+	///
+	/// let mut clue = Clue::new();
+	/// clue.types(TypesMode::WARN);
+	/// clue.compile_code("local x: number = \"one\"".to_owned()).unwrap();
+	/// assert_eq!(clue.take_warnings()[0].code, "T0001");
+	/// assert!(clue.take_warnings().is_empty());
+	/// ```
+	pub fn take_warnings(&self) -> Vec<Diagnostic> {
+		std::mem::take(&mut *self.lock_warnings())
+	}
+
+	fn lock_warnings(&self) -> MutexGuard<'_, Vec<Diagnostic>> {
+		self.warnings.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+
 	/// Sets the `tokens` option
 	/// If `tokens` is `true` then then the `tokens` option will be enabled
 	/// If `tokens` is `false` then then the `tokens` option will be disabled
@@ -145,6 +177,21 @@ impl Clue {
 	pub fn target_os(&mut self, os: String) {
 		self.options.env_targetos = os;
 	}
+
+	/// Sets the `types` option
+	/// The `types` option is used to enable type checking
+	/// The warnings of the type checker can be read with [`Clue::take_warnings`]
+	/// See [`TypesMode`] for the available modes
+	pub fn types(&mut self, mode: TypesMode) {
+		self.options.env_types = mode;
+	}
+
+	/// Sets the `std` option
+	/// The `std` option is used to choose the standard library known by the type checker
+	/// See [`LuaSTD`] for the available standard libraries
+	pub fn std(&mut self, std: LuaSTD) {
+		self.options.env_std = std;
+	}
 }
 
 impl Clue {
@@ -155,22 +202,22 @@ impl Clue {
 	/// If the code was successfully preprocessed, the [`Result`] will return a [`Code`] containing the preprocessed code
 	///
 	/// # Errors
-	/// If an error occurs while preprocessing the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while preprocessing the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///     let clue = Clue::new();
 	///     let code = clue.preprocess_code("print(\"Hello World!\")".to_owned())?;
 	///
 	///     Ok(())
 	/// }
-	pub fn preprocess_code(&self, code: String) -> Result<Code, String> {
+	pub fn preprocess_code(&self, code: String) -> Result<Code, Vec<Diagnostic>> {
 		let mut code = code;
 		let filename = String::from("(library)");
-		let (codes, variables, ..) = preprocess_code(
+		let (codes, variables, _, _, warnings) = preprocess_code(
 			// SAFETY: This is safe because the preprocessor will never output anything other than UTF-8
 			unsafe { code.as_bytes_mut() },
 			1,
@@ -178,7 +225,8 @@ impl Clue {
 			&filename,
 			&self.options,
 		)?;
-		preprocess_codes(0, codes, &variables, &filename)
+		self.lock_warnings().extend(warnings);
+		Ok(preprocess_codes(0, codes, &variables, &filename)?)
 	}
 
 	/// Preprocesses the given file
@@ -188,14 +236,14 @@ impl Clue {
 	/// If the code was successfully preprocessed, the [`Result`] will return a [`Code`] containing the preprocessed code
 	///
 	/// # Errors
-	/// If an error occurs while preprocessing the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while preprocessing the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///     let clue = Clue::new();
 	///     let code = clue.preprocess_file("../examples/fizzbuzz.clue")?;
 	///
@@ -205,15 +253,18 @@ impl Clue {
 	pub fn preprocess_file<P: AsRef<Path> + AsRef<OsStr> + Display>(
 		&self,
 		path: P,
-	) -> Result<Code, String> {
+	) -> Result<Code, Vec<Diagnostic>> {
 		let filepath = PathBuf::from(path.to_string());
 		let filename = filepath
 			.file_name()
-			.ok_or_else(|| format!("Invalid path: {}", path))?
+			.ok_or_else(|| {
+				Diagnostic::error("F0001", format!("Invalid path: {path}"), path.to_string())
+			})?
 			.to_string_lossy()
 			.into_owned();
-		let (codes, variables) = read_file(filepath, &filename, &self.options)?;
-		preprocess_codes(0, codes, &variables, &filename)
+		let (codes, variables, warnings) = read_file(filepath, &filename, &self.options)?;
+		self.lock_warnings().extend(warnings);
+		Ok(preprocess_codes(0, codes, &variables, &filename)?)
 	}
 }
 
@@ -228,17 +279,19 @@ impl Clue {
 	/// If the code was successfully scanned, the [`Result`] will return a [`Vec<Token>`] containing the scanned tokens
 	///
 	/// # Errors
-	/// If an error occurs while scanning the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while scanning the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	pub fn scan_preprocessed_file<P: AsRef<Path> + AsRef<OsStr> + Display>(
 		&self,
 		code: Code,
 		path: P,
-	) -> Result<Vec<Token>, String> {
+	) -> Result<Vec<Token>, Vec<Diagnostic>> {
 		let filepath: &Path = path.as_ref();
 		let filename = filepath
 			.file_name()
-			.ok_or_else(|| format!("Invalid path: {}", path))?
+			.ok_or_else(|| {
+				Diagnostic::error("F0001", format!("Invalid path: {path}"), path.to_string())
+			})?
 			.to_string_lossy()
 			.into_owned();
 		scan_code(code, &filename)
@@ -252,20 +305,20 @@ impl Clue {
 	/// If the code was successfully scanned, the [`Result`] will return a [`Vec<Token>`] containing the scanned tokens
 	///
 	/// # Errors
-	/// If an error occurs while scanning the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while scanning the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///   let clue = Clue::new();
 	///   let code = clue.preprocess_code("print(\"Hello World!\")".to_owned())?;
 	///   let tokens = clue.scan_preprocessed(code)?;
 	///
 	///   Ok(())
 	/// }
-	pub fn scan_preprocessed(&self, code: Code) -> Result<Vec<Token>, String> {
+	pub fn scan_preprocessed(&self, code: Code) -> Result<Vec<Token>, Vec<Diagnostic>> {
 		scan_code(code, &String::from("(library)"))
 	}
 
@@ -276,19 +329,19 @@ impl Clue {
 	/// If the code was successfully scanned, the [`Result`] will return a [`Vec<Token>`] containing the scanned tokens
 	///
 	/// # Errors
-	/// If an error occurs while scanning the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while scanning the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let tokens = clue.scan_code("print(\"Hello World!\")".to_owned())?;
 	///
 	///   Ok(())
 	/// }
-	pub fn scan_code(&self, code: String) -> Result<Vec<Token>, String> {
+	pub fn scan_code(&self, code: String) -> Result<Vec<Token>, Vec<Diagnostic>> {
 		let code = self.preprocess_code(code)?;
 		self.scan_preprocessed(code)
 	}
@@ -300,13 +353,13 @@ impl Clue {
 	/// If the code was successfully scanned, the [`Result`] will return a [`Vec<Token>`] containing the scanned tokens
 	///
 	/// # Errors
-	/// If an error occurs while scanning the file, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while scanning the file, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///   let clue = Clue::new();
 	///   let tokens = clue.scan_file("../examples/fizzbuzz.clue")?;
 	///
@@ -315,7 +368,7 @@ impl Clue {
 	pub fn scan_file<P: AsRef<Path> + AsRef<OsStr> + Display>(
 		&self,
 		filename: P,
-	) -> Result<Vec<Token>, String> {
+	) -> Result<Vec<Token>, Vec<Diagnostic>> {
 		let code = self.preprocess_file(&filename)?;
 		self.scan_preprocessed_file(code, &filename)
 	}
@@ -329,20 +382,20 @@ impl Clue {
 	/// If the code was successfully parsed, the [`Result`] will return a `(Expression, String)` containing the parsed expression and the static variables
 	///
 	/// # Errors
-	/// If an error occurs while parsing the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while parsing the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///  let clue = Clue::new();
 	///  let code = clue.preprocess_code("print(\"Hello World!\")".to_owned())?;
 	///  let (expression, statics) = clue.parse_preprocessed(code)?;
 	///
 	///  Ok(())
 	/// }
-	pub fn parse_preprocessed(&self, code: Code) -> Result<(Expression, String), String> {
+	pub fn parse_preprocessed(&self, code: Code) -> Result<(Expression, String), Vec<Diagnostic>> {
 		let tokens = self.scan_preprocessed(code)?;
 		self.parse_tokens(tokens)
 	}
@@ -354,21 +407,35 @@ impl Clue {
 	/// If the code was successfully parsed, the [`Result`] will return a `(Expression, String)` containing the parsed expression and the static variables
 	///
 	/// # Errors
-	/// If an error occurs while parsing the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while parsing the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let tokens = clue.scan_code("print(\"Hello World!\")".to_owned())?;
 	///    let (expression, statics) = clue.parse_tokens(tokens)?;
 	///
 	///    Ok(())
 	/// }
-	pub fn parse_tokens(&self, tokens: Vec<Token>) -> Result<(Expression, String), String> {
-		parse_tokens(tokens, &String::from("(library)"), &self.options)
+	pub fn parse_tokens(
+		&self,
+		tokens: Vec<Token>,
+	) -> Result<(Expression, String), Vec<Diagnostic>> {
+		self.parse_named_tokens(tokens, &String::from("(library)"))
+	}
+
+	fn parse_named_tokens(
+		&self,
+		tokens: Vec<Token>,
+		filename: &String,
+	) -> Result<(Expression, String), Vec<Diagnostic>> {
+		let (expression, statics, warnings) =
+			parse_tokens_with_warnings(tokens, filename, &self.options)?;
+		self.lock_warnings().extend(warnings);
+		Ok((expression, statics))
 	}
 
 	/// Parses the given code
@@ -378,19 +445,19 @@ impl Clue {
 	/// If the code was successfully parsed, the [`Result`] will return a `(Expression, String)` containing the parsed expression and the static variables
 	///
 	/// # Errors
-	/// If an error occurs while parsing the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while parsing the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///   let clue = Clue::new();
 	///   let (expression, statics) = clue.parse_code("print(\"Hello World!\")".to_owned())?;
 	///
 	///   Ok(())
 	/// }
-	pub fn parse_code(&self, code: String) -> Result<(Expression, String), String> {
+	pub fn parse_code(&self, code: String) -> Result<(Expression, String), Vec<Diagnostic>> {
 		let tokens = self.scan_code(code)?;
 		self.parse_tokens(tokens)
 	}
@@ -402,13 +469,13 @@ impl Clue {
 	/// If the code was successfully parsed, the [`Result`] will return a `(Expression, String)` containing the parsed expression and the static variables
 	///
 	/// # Errors
-	/// If an error occurs while parsing the file, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while parsing the file, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///   let clue = Clue::new();
 	///   let (expression, statics) = clue.parse_file("../examples/fizzbuzz.clue")?;
 	///
@@ -417,16 +484,18 @@ impl Clue {
 	pub fn parse_file<P: AsRef<Path> + AsRef<OsStr> + Display>(
 		&self,
 		path: P,
-	) -> Result<(Expression, String), String> {
+	) -> Result<(Expression, String), Vec<Diagnostic>> {
 		let filepath: &Path = path.as_ref();
 		let filename = filepath
 			.file_name()
-			.ok_or_else(|| format!("Invalid path: {}", path))?
+			.ok_or_else(|| {
+				Diagnostic::error("F0001", format!("Invalid path: {path}"), path.to_string())
+			})?
 			.to_string_lossy()
 			.into_owned();
 		let tokens = self.scan_file(&path)?;
 
-		parse_tokens(tokens, &filename, &self.options)
+		self.parse_named_tokens(tokens, &filename)
 	}
 }
 
@@ -438,20 +507,20 @@ impl Clue {
 	/// If the code was successfully compiled, the [`Result`] will return a [`String`] containing the compiled code
 	///
 	/// # Errors
-	/// If an error occurs while compiling the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while compiling the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rustrust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let tokens = clue.scan_code("print(\"Hello World!\")".to_owned())?;
 	///    let code = clue.compile_tokens(tokens)?;
 	///
 	///    Ok(())
 	/// }
-	pub fn compile_tokens(&self, tokens: Vec<Token>) -> Result<String, String> {
+	pub fn compile_tokens(&self, tokens: Vec<Token>) -> Result<String, Vec<Diagnostic>> {
 		let (ctokens, statics) = self.parse_tokens(tokens)?;
 		let filename = String::from("(library)");
 		let compiler = Compiler::new(&self.options, &filename);
@@ -465,20 +534,20 @@ impl Clue {
 	/// If the code was successfully compiled, the [`Result`] will return a [`String`] containing the compiled code
 	///
 	/// # Errors
-	/// If an error occurs while compiling the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while compiling the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///     let clue = Clue::new();
 	///     let code = clue.preprocess_code("print(\"Hello World!\")".to_owned())?;
 	///     let compiled = clue.compile_preprocessed(code)?;
 	///
 	///     Ok(())
 	/// }
-	pub fn compile_preprocessed(&self, code: Code) -> Result<String, String> {
+	pub fn compile_preprocessed(&self, code: Code) -> Result<String, Vec<Diagnostic>> {
 		let tokens = self.scan_preprocessed(code)?;
 		self.compile_tokens(tokens)
 	}
@@ -490,20 +559,23 @@ impl Clue {
 	/// If the code was successfully compiled, the [`Result`] will return a [`String`] containing the compiled code
 	///
 	/// # Errors
-	/// If an error occurs while compiling the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while compiling the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let parse_result = clue.parse_code("print(\"Hello World!\")".to_owned())?;
 	///    let code = clue.compile_ast(parse_result)?;
 	///
 	///    Ok(())
 	/// }
-	pub fn compile_ast(&self, (ctokens, statics): (Expression, String)) -> Result<String, String> {
+	pub fn compile_ast(
+		&self,
+		(ctokens, statics): (Expression, String),
+	) -> Result<String, Vec<Diagnostic>> {
 		let filename = String::from("(library)");
 		let compiler = Compiler::new(&self.options, &filename);
 		Ok(statics + &compiler.compile_tokens(0, ctokens)?)
@@ -516,19 +588,19 @@ impl Clue {
 	/// If the code was successfully compiled, the [`Result`] will return a [`String`] containing the compiled code
 	///
 	/// # Errors
-	/// If an error occurs while compiling the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while compiling the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let code = clue.compile_code("print(\"Hello World!\")".to_owned())?;
 	///
 	///    Ok(())
 	/// }
-	pub fn compile_code(&self, code: String) -> Result<String, String> {
+	pub fn compile_code(&self, code: String) -> Result<String, Vec<Diagnostic>> {
 		let tokens = self.scan_code(code)?;
 		self.compile_tokens(tokens)
 	}
@@ -540,13 +612,13 @@ impl Clue {
 	/// If the code was successfully compiled, the [`Result`] will return a [`String`] containing the compiled code
 	///
 	/// # Errors
-	/// If an error occurs while compiling the code, an [`Err`] containing a [`String`] with the error message will be returned
+	/// If an error occurs while compiling the code, an [`Err`] containing the [`Diagnostic`]s of every error found will be returned
 	///
 	/// # Example
 	/// ```rust
-	/// use clue_core::Clue;
+	/// use clue_core::{diagnostic::Diagnostic, Clue};
 	///
-	/// fn main() -> Result<(), String> {
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
 	///    let clue = Clue::new();
 	///    let code = clue.compile_file("../examples/fizzbuzz.clue")?;
 	///
@@ -555,11 +627,12 @@ impl Clue {
 	pub fn compile_file<P: AsRef<Path> + AsRef<OsStr> + Display>(
 		&self,
 		path: P,
-	) -> Result<String, String> {
+	) -> Result<String, Vec<Diagnostic>> {
 		let tokens = self.scan_file(&path)?;
 		let result = self.compile_tokens(tokens)?;
 		if self.options.env_output {
-			fs::write(path, &result).map_err(|e| e.to_string())?;
+			fs::write(&path, &result)
+				.map_err(|e| Diagnostic::error("F0001", e.to_string(), path.to_string()))?;
 		}
 		Ok(result)
 	}
@@ -578,3 +651,24 @@ impl Default for Clue {
 		Clue::new()
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::thread;
+
+	#[test]
+	fn shared_between_threads() {
+		let mut clue = Clue::new();
+		clue.types(TypesMode::WARN);
+		thread::scope(|scope| {
+			for _ in 0..4 {
+				scope.spawn(|| {
+					clue.compile_code("local x: number = \"one\"".to_owned())
+						.unwrap()
+				});
+			}
+		});
+		assert_eq!(clue.take_warnings().len(), 4);
+	}
+}
@@ -0,0 +1,1194 @@
+//! The linter looks for common mistakes in Clue code that compiles but probably does not do what was meant.
+//!
+//! [`lint_code`] parses the code and walks its [`ComplexToken`] tree,
+//! returning a warning [`Diagnostic`] for every problem found by the enabled [`Rule`]s.
+//! Rules can be turned off for a whole project with [`LintRules`] or for a single line
+//! with a `// lint: allow(rule, ...)` comment, written at the end of the line or alone on the line before it.
+
+use crate::{
+	diagnostic::{Diagnostic, Span},
+	env::Options,
+	lossless::{LosslessCode, LosslessToken, SyntaxKind, Trivia, TriviaKind},
+	parser::{parse_source, CodeBlock, ComplexToken, ComplexToken::*, Expression, FunctionArgs},
+	scanner::TokenType,
+};
+use ahash::{AHashMap, AHashSet};
+use clap::ValueEnum;
+use std::{collections::hash_map::Entry, fmt, iter::Peekable, slice, str::FromStr};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, ValueEnum)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+/// A mistake the linter can look for
+pub enum Rule {
+	/// A local variable that is never read
+	UnusedLocal,
+
+	/// A function argument that is never read
+	UnusedArgument,
+
+	/// A local variable with the same name of another local variable that is still visible
+	ShadowedLocal,
+
+	/// An assignment to a global variable that was never declared with `global`
+	UndeclaredGlobal,
+
+	/// Code after `return`, `break` or `continue` that can never run
+	UnreachableCode,
+
+	/// A `match` with no `default` case
+	MatchWithoutDefault,
+
+	/// A `catch` block with no code, which silently ignores the error
+	EmptyCatch,
+
+	/// The same key written twice in a table
+	DuplicateKey,
+}
+
+impl Rule {
+	/// Every rule, in the order of their codes
+	pub const ALL: [Rule; 8] = [
+		Rule::UnusedLocal,
+		Rule::UnusedArgument,
+		Rule::ShadowedLocal,
+		Rule::UndeclaredGlobal,
+		Rule::UnreachableCode,
+		Rule::MatchWithoutDefault,
+		Rule::EmptyCatch,
+		Rule::DuplicateKey,
+	];
+
+	/// Returns the code of the diagnostics reported by the rule
+	pub const fn code(self) -> &'static str {
+		match self {
+			Rule::UnusedLocal => "L0001",
+			Rule::UnusedArgument => "L0002",
+			Rule::ShadowedLocal => "L0003",
+			Rule::UndeclaredGlobal => "L0004",
+			Rule::UnreachableCode => "L0005",
+			Rule::MatchWithoutDefault => "L0006",
+			Rule::EmptyCatch => "L0007",
+			Rule::DuplicateKey => "L0008",
+		}
+	}
+
+	/// Returns the name used for the rule in `lint: allow` comments and configuration files
+	pub const fn name(self) -> &'static str {
+		match self {
+			Rule::UnusedLocal => "unused-local",
+			Rule::UnusedArgument => "unused-argument",
+			Rule::ShadowedLocal => "shadowed-local",
+			Rule::UndeclaredGlobal => "undeclared-global",
+			Rule::UnreachableCode => "unreachable-code",
+			Rule::MatchWithoutDefault => "match-without-default",
+			Rule::EmptyCatch => "empty-catch",
+			Rule::DuplicateKey => "duplicate-key",
+		}
+	}
+}
+
+impl fmt::Display for Rule {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+impl FromStr for Rule {
+	type Err = String;
+
+	fn from_str(name: &str) -> Result<Self, Self::Err> {
+		Rule::ALL
+			.into_iter()
+			.find(|rule| rule.name() == name)
+			.ok_or_else(|| format!("Unknown lint rule '{name}'"))
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The [`Rule`]s used by the linter, every rule is enabled by default
+pub struct LintRules {
+	enabled: [bool; Rule::ALL.len()],
+}
+
+impl Default for LintRules {
+	fn default() -> Self {
+		Self {
+			enabled: [true; Rule::ALL.len()],
+		}
+	}
+}
+
+impl LintRules {
+	/// Enables or disables a rule
+	pub fn set(&mut self, rule: Rule, enabled: bool) {
+		self.enabled[rule as usize] = enabled;
+	}
+
+	/// Returns `true` if the rule is enabled
+	pub fn is_enabled(&self, rule: Rule) -> bool {
+		self.enabled[rule as usize]
+	}
+
+	/// Enables or disables the rules listed in a configuration file like `.cluelint`
+	/// Every line is either empty, a comment starting with `#` or `rule = on` (or `off`, `true`, `false`)
+	///
+	/// # Errors
+	/// If a line is not valid or names an unknown rule an error is returned
+	///
+	/// # Examples
+	/// ```
+	/// use clue_core::lint::{LintRules, Rule};
+	///
+	/// let mut rules = LintRules::default();
+	/// rules.parse_config("# not for tests\nunused-argument = off\n", ".cluelint").unwrap();
+	/// assert!(!rules.is_enabled(Rule::UnusedArgument));
+	/// assert!(rules.is_enabled(Rule::UnusedLocal));
+	/// ```
+	pub fn parse_config(&mut self, config: &str, filename: &str) -> Result<(), Diagnostic> {
+		for (i, line) in config.lines().enumerate() {
+			let line = line.split('#').next().unwrap_or_default().trim();
+			if line.is_empty() {
+				continue;
+			}
+			let error = |message: String| {
+				Diagnostic::error("F0003", message, filename)
+					.at(i + 1, 1)
+					.with_help("expected a line like `unused-local = off`")
+			};
+			let Some((name, value)) = line.split_once('=') else {
+				return Err(error(format!("Invalid line '{line}'")));
+			};
+			let rule = name.trim().parse().map_err(error)?;
+			let enabled = match value.trim() {
+				"on" | "true" => true,
+				"off" | "false" => false,
+				value => return Err(error(format!("Invalid value '{value}' for '{rule}'"))),
+			};
+			self.set(rule, enabled);
+		}
+		Ok(())
+	}
+}
+
+struct Variable {
+	name: String,
+	span: Span,
+	argument: bool,
+	used: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+/// What a name is used for where it is written
+enum Site {
+	/// A local variable, function argument, loop variable or enum value being declared
+	Declaration,
+
+	/// A variable or a table key followed by `=` or an operator like `+=`
+	Assignment,
+}
+
+/// The columns and texts of the names written on a line, in the order of the code
+type Sites<'a> = AHashMap<(Site, usize), Vec<(usize, &'a str)>>;
+
+struct Linter<'a> {
+	filename: &'a String,
+	rules: &'a LintRules,
+	positions: Vec<(usize, usize, &'a str)>,
+	sites: Sites<'a>,
+	allowed: AHashMap<usize, Vec<Rule>>,
+	scopes: Vec<Vec<Variable>>,
+	globals: AHashSet<String>,
+	assigned: Vec<(String, Span)>,
+	diagnostics: Vec<Diagnostic>,
+}
+
+/// Names starting with `_` are never reported as unused or shadowed,
+/// this also skips the internal variables created by the parser
+fn is_ignored(name: &str) -> bool {
+	name.starts_with('_')
+}
+
+/// Checks if the code before a name ends with `.` or `:`, which makes the name a field
+fn is_accessor(before: &str) -> bool {
+	let before = before.trim_end();
+	(before.ends_with('.') && !before.ends_with(".."))
+		|| (before.ends_with(':') && !before.ends_with("::"))
+}
+
+type Tokens<'a> = Peekable<slice::Iter<'a, LosslessToken>>;
+
+/// Takes the next token if it is of the given kind
+fn next_if<'a>(tokens: &mut Tokens<'a>, kind: TokenType) -> Option<&'a LosslessToken> {
+	tokens.next_if(|token| token.kind == SyntaxKind::Token(kind))
+}
+
+/// Skips a type annotation, like `number?`, `string | nil` or `fn(number): string`
+fn skip_type(tokens: &mut Tokens) {
+	loop {
+		if next_if(tokens, TokenType::FN).is_some() {
+			if next_if(tokens, TokenType::ROUND_BRACKET_OPEN).is_some() {
+				let mut depth = 1usize;
+				while depth > 0 {
+					match tokens.next().map(|token| token.kind) {
+						Some(SyntaxKind::Token(TokenType::ROUND_BRACKET_OPEN)) => depth += 1,
+						Some(SyntaxKind::Token(TokenType::ROUND_BRACKET_CLOSED)) => depth -= 1,
+						Some(_) => {}
+						None => return,
+					}
+				}
+			}
+			// the return type takes the rest of the union
+			if next_if(tokens, TokenType::COLON).is_some() {
+				return skip_type(tokens);
+			}
+		} else {
+			tokens.next();
+		}
+		next_if(tokens, TokenType::QUESTION_MARK);
+		if next_if(tokens, TokenType::BIT_OR).is_none() {
+			break;
+		}
+	}
+}
+
+/// Takes the names of a list like `a, b: number, c`, stopping at the first token that does not fit
+fn name_list<'a>(tokens: &mut Tokens<'a>, names: &mut Vec<&'a LosslessToken>) {
+	while let Some(name) = next_if(tokens, TokenType::IDENTIFIER) {
+		names.push(name);
+		if next_if(tokens, TokenType::COLON).is_some() {
+			skip_type(tokens);
+		}
+		if next_if(tokens, TokenType::COMMA).is_none() {
+			break;
+		}
+	}
+}
+
+/// Takes the names declared inside the brackets that open with the next token,
+/// like the arguments of a function or the values of an enum, skipping their types and default values
+fn bracket_names<'a>(tokens: &mut Tokens<'a>, names: &mut Vec<&'a LosslessToken>) {
+	use TokenType::*;
+	if next_if(tokens, ROUND_BRACKET_OPEN)
+		.or_else(|| next_if(tokens, CURLY_BRACKET_OPEN))
+		.is_none()
+	{
+		return;
+	}
+	let mut depth = 1usize;
+	let mut expecting = true;
+	while let Some(token) = tokens.next() {
+		let SyntaxKind::Token(kind) = token.kind else {
+			expecting = false;
+			continue;
+		};
+		match kind {
+			ROUND_BRACKET_OPEN | SQUARE_BRACKET_OPEN | CURLY_BRACKET_OPEN | SAFE_CALL
+			| SAFE_SQUARE_BRACKET => depth += 1,
+			ROUND_BRACKET_CLOSED | SQUARE_BRACKET_CLOSED | CURLY_BRACKET_CLOSED => {
+				depth -= 1;
+				if depth == 0 {
+					return;
+				}
+			}
+			COMMA => {
+				expecting = depth == 1;
+				continue;
+			}
+			COLON if depth == 1 => skip_type(tokens),
+			IDENTIFIER if expecting => names.push(token),
+			_ => {}
+		}
+		expecting = false;
+	}
+}
+
+/// Checks if the token assigns a value to the name before it
+fn is_assignment(token: &LosslessToken) -> bool {
+	use TokenType::*;
+	matches!(
+		token.kind,
+		SyntaxKind::Token(
+			DEFINE
+				| DEFINE_AND | DEFINE_OR
+				| INCREASE | DECREASE
+				| MULTIPLY | DIVIDE
+				| DEFINE_COALESCE
+				| EXPONENTIATE
+				| CONCATENATE
+				| MODULATE
+		)
+	)
+}
+
+/// Finds where the names are declared and assigned in the code.
+/// The tree has no columns, so the warnings about a name use these to point at the right one
+fn find_sites(code: &LosslessCode) -> Sites<'_> {
+	use TokenType::*;
+	let mut declared = Vec::new();
+	let mut tokens = code.tokens.iter().peekable();
+	while let Some(token) = tokens.next() {
+		let SyntaxKind::Token(kind) = token.kind else {
+			continue;
+		};
+		let mut rest = tokens.clone();
+		match kind {
+			LOCAL => {
+				if next_if(&mut rest, FN)
+					.or_else(|| next_if(&mut rest, STRUCT))
+					.is_some()
+				{
+					declared.extend(next_if(&mut rest, IDENTIFIER));
+				} else {
+					name_list(&mut rest, &mut declared);
+				}
+			}
+			FOR => name_list(&mut rest, &mut declared),
+			CATCH => declared.extend(next_if(&mut rest, IDENTIFIER)),
+			FN | METHOD => {
+				// the name of the function, like `a.b::c`
+				while rest
+					.next_if(|token| {
+						matches!(
+							token.kind,
+							SyntaxKind::Token(IDENTIFIER | DOT | DOUBLE_COLON)
+						)
+					})
+					.is_some()
+				{}
+				bracket_names(&mut rest, &mut declared);
+			}
+			ENUM => bracket_names(&mut rest, &mut declared),
+			_ => {}
+		}
+	}
+	let mut sites = Sites::new();
+	for token in &declared {
+		sites
+			.entry((Site::Declaration, token.line))
+			.or_default()
+			.push((token.column, token.text.as_str()));
+	}
+	for (i, token) in code.tokens.iter().enumerate() {
+		if !matches!(token.kind, SyntaxKind::Token(IDENTIFIER | STRING | NUMBER))
+			|| declared.iter().any(|name| std::ptr::eq(*name, token))
+		{
+			continue;
+		}
+		let before = i.checked_sub(1).map(|i| code.tokens[i].kind);
+		let after = &code.tokens[i + 1..];
+		let assigned = match before {
+			Some(SyntaxKind::Token(DOT | SAFE_DOT | DOUBLE_COLON | SAFE_DOUBLE_COLON | COLON)) => {
+				false
+			}
+			// a key like `["name"]` or `[1]`
+			Some(SyntaxKind::Token(SQUARE_BRACKET_OPEN)) => {
+				after.first().map(|token| token.kind)
+					== Some(SyntaxKind::Token(SQUARE_BRACKET_CLOSED))
+					&& after.get(1).is_some_and(is_assignment)
+			}
+			_ => after.first().is_some_and(is_assignment),
+		};
+		if assigned {
+			sites
+				.entry((Site::Assignment, token.line))
+				.or_default()
+				.push((token.column, token.text.as_str()));
+		}
+	}
+	for line in sites.values_mut() {
+		line.sort_unstable();
+		line.dedup();
+	}
+	sites
+}
+
+/// Returns the rules listed in a `lint: allow(...)` comment
+fn pragma(trivia: &Trivia) -> Option<&str> {
+	let text = match trivia.kind {
+		TriviaKind::LineComment => trivia.text.strip_prefix("//")?,
+		TriviaKind::BlockComment => trivia.text.strip_prefix("/*")?.strip_suffix("*/")?,
+		_ => return None,
+	};
+	text.trim()
+		.strip_prefix("lint:")?
+		.trim_start()
+		.strip_prefix("allow(")?
+		.strip_suffix(')')
+}
+
+/// Returns the text of an expression made of a single symbol, like a name
+fn single_symbol(expr: &Expression) -> Option<&String> {
+	match expr.front() {
+		Some(SYMBOL(text)) if expr.len() == 1 => Some(text),
+		_ => None,
+	}
+}
+
+/// Returns the name of a table key, normalized so that `x`, `["x"]` and `['x']` are the same key,
+/// together with how it is shown in the warning
+fn key_name(key: &Expression) -> Option<(String, String)> {
+	if let Some(name) = single_symbol(key) {
+		return Some((format!("s:{name}"), name.clone()));
+	}
+	let literal = match key.get(1) {
+		Some(EXPR(expr)) if key.len() == 3 => single_symbol(expr)?.trim(),
+		_ => return None,
+	};
+	let normalized = if let Some(string) = literal
+		.strip_prefix('"')
+		.and_then(|string| string.strip_suffix('"'))
+		.or_else(|| literal.strip_prefix('\'')?.strip_suffix('\''))
+	{
+		if string.contains('\\') {
+			return None;
+		}
+		format!("s:{string}")
+	} else if let Ok(number) = literal.parse::<f64>() {
+		format!("n:{number}")
+	} else if matches!(literal, "true" | "false") {
+		format!("b:{literal}")
+	} else {
+		return None;
+	};
+	Some((normalized, literal.to_owned()))
+}
+
+impl<'a> Linter<'a> {
+	fn new(filename: &'a String, rules: &'a LintRules, code: &'a LosslessCode) -> Self {
+		let mut linter = Self {
+			filename,
+			rules,
+			positions: code
+				.tokens
+				.iter()
+				.map(|token| (token.line, token.column, token.text.as_str()))
+				.collect(),
+			sites: find_sites(code),
+			allowed: AHashMap::new(),
+			scopes: Vec::new(),
+			globals: AHashSet::new(),
+			assigned: Vec::new(),
+			diagnostics: Vec::new(),
+		};
+		linter.read_pragmas(code);
+		linter.read_statics(code);
+		linter
+	}
+
+	fn read_pragmas(&mut self, code: &LosslessCode) {
+		let mut last_line = None;
+		let tokens = code
+			.tokens
+			.iter()
+			.map(|token| (&token.leading, Some(token)));
+		for (leading, token) in tokens.chain([(&code.trailing, None)]) {
+			for trivia in leading {
+				let Some(rules) = pragma(trivia) else {
+					continue;
+				};
+				// a comment alone on its line refers to the next line of code
+				let line = match token {
+					_ if last_line == Some(trivia.line) => trivia.line,
+					Some(token) => token.line,
+					None => continue,
+				};
+				for name in rules.split(',').map(str::trim) {
+					match name.parse() {
+						Ok(rule) => self.allowed.entry(line).or_default().push(rule),
+						Err(message) => self.diagnostics.push(
+							Diagnostic::warning("L0009", message, self.filename).with_span(
+								Span::sized(trivia.line, trivia.column, trivia.text.len()),
+							),
+						),
+					}
+				}
+			}
+			if let Some(token) = token {
+				last_line = Some(token.line + token.text.matches('\n').count());
+			}
+		}
+	}
+
+	/// Statics and externs are compiled away by the parser, so their names are read from the code,
+	/// `extern fn` declares the global that contains the function
+	fn read_statics(&mut self, code: &LosslessCode) {
+		let mut tokens = code.tokens.iter().peekable();
+		while let Some(token) = tokens.next() {
+			let is_extern = match token.kind {
+				SyntaxKind::Token(TokenType::STATIC) => false,
+				SyntaxKind::Token(TokenType::EXTERN) => true,
+				_ => continue,
+			};
+			if next_if(&mut tokens, TokenType::FN).is_some() && is_extern {
+				if let Some(name) = next_if(&mut tokens, TokenType::IDENTIFIER) {
+					self.globals.insert(name.text.clone());
+				}
+				continue;
+			}
+			while let Some(name) = next_if(&mut tokens, TokenType::IDENTIFIER) {
+				self.globals.insert(name.text.clone());
+				if is_extern && next_if(&mut tokens, TokenType::COLON).is_some() {
+					skip_type(&mut tokens);
+				}
+				if next_if(&mut tokens, TokenType::COMMA).is_none() {
+					break;
+				}
+			}
+		}
+	}
+
+	/// Finds where `text` is written on the given line, or the first token of the line if it is not found
+	fn span(&self, line: usize, text: Option<&str>) -> Span {
+		let start = self.positions.partition_point(|(l, ..)| *l < line);
+		let end = self.positions.partition_point(|(l, ..)| *l <= line);
+		let on_line = &self.positions[start..end];
+		on_line
+			.iter()
+			.find(|(.., token)| Some(*token) == text)
+			.or_else(|| on_line.first())
+			.map_or(Span::point(line, 1), |(line, column, token)| {
+				Span::sized(*line, *column, token.len())
+			})
+	}
+
+	/// Returns where the next name of the given kind is written on the line, following the order of the code,
+	/// or where `name` is first written on the line if it is not found
+	fn claim(&mut self, site: Site, line: usize, name: &str) -> Span {
+		let claimed = self.sites.get_mut(&(site, line)).and_then(|sites| {
+			let i = sites.iter().position(|(_, text)| *text == name)?;
+			Some(sites.remove(i))
+		});
+		match claimed {
+			Some((column, text)) => Span::sized(line, column, text.len()),
+			None => self.span(line, Some(name)),
+		}
+	}
+
+	fn report(&mut self, rule: Rule, span: Span, message: String) {
+		if !self.rules.is_enabled(rule)
+			|| self
+				.allowed
+				.get(&span.line)
+				.is_some_and(|allowed| allowed.contains(&rule))
+		{
+			return;
+		}
+		let diagnostic = Diagnostic::warning(rule.code(), message, self.filename)
+			.with_span(span)
+			.with_help(format!(
+				"add `// lint: allow({rule})` to allow it on this line"
+			));
+		self.diagnostics.push(diagnostic);
+	}
+
+	fn lookup(&mut self, name: &str) -> Option<&mut Variable> {
+		self.scopes
+			.iter_mut()
+			.rev()
+			.flat_map(|scope| scope.iter_mut().rev())
+			.find(|variable| variable.name == name)
+	}
+
+	fn declare(&mut self, name: &str, span: Span, argument: bool) {
+		if name == "..." {
+			return;
+		}
+		if !is_ignored(name) {
+			if let Some(previous) = self.lookup(name).map(|variable| variable.span.line) {
+				self.report(
+					Rule::ShadowedLocal,
+					span,
+					format!("'{name}' shadows the local variable declared on line {previous}"),
+				);
+			}
+		}
+		if let Some(scope) = self.scopes.last_mut() {
+			scope.push(Variable {
+				name: name.to_owned(),
+				span,
+				argument,
+				used: false,
+			});
+		}
+	}
+
+	fn reference(&mut self, name: &str) {
+		if let Some(variable) = self.lookup(name) {
+			variable.used = true;
+		}
+	}
+
+	/// Marks the variables read by a piece of Lua code as used
+	fn reference_text(&mut self, text: &str) {
+		if text.starts_with("[[") || text.starts_with("[=") {
+			return;
+		}
+		let bytes = text.as_bytes();
+		let mut i = 0;
+		while i < bytes.len() {
+			let start = i;
+			match bytes[i] {
+				b'"' | b'\'' | b'`' => {
+					i += 1;
+					while i < bytes.len() && bytes[i] != bytes[start] {
+						i += if bytes[i] == b'\\' { 2 } else { 1 };
+					}
+					i += 1;
+				}
+				c if c.is_ascii_alphanumeric() || c == b'_' => {
+					while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_')
+					{
+						i += 1;
+					}
+					if !c.is_ascii_digit() && !is_accessor(&text[..start]) {
+						self.reference(&text[start..i]);
+					}
+				}
+				_ => i += 1,
+			}
+		}
+	}
+
+	fn push_scope(&mut self) {
+		self.scopes.push(Vec::new());
+	}
+
+	fn pop_scope(&mut self) {
+		let Some(scope) = self.scopes.pop() else {
+			return;
+		};
+		for variable in scope {
+			if variable.used || is_ignored(&variable.name) {
+				continue;
+			}
+			let (rule, kind) = if variable.argument {
+				(Rule::UnusedArgument, "argument")
+			} else {
+				(Rule::UnusedLocal, "local variable")
+			};
+			self.report(
+				rule,
+				variable.span,
+				format!("Unused {kind} '{}'", variable.name),
+			);
+		}
+	}
+
+	fn block(&mut self, block: &CodeBlock, variables: &[(&str, Span)]) {
+		self.push_scope();
+		for (name, span) in variables {
+			self.declare(name, *span, false);
+		}
+		self.statements(&block.code);
+		self.pop_scope();
+	}
+
+	fn function(&mut self, args: &FunctionArgs, code: &CodeBlock) {
+		self.push_scope();
+		for (name, default) in args {
+			let line = default.as_ref().map_or(code.start, |(_, line)| *line);
+			let span = self.claim(Site::Declaration, line, name);
+			if let Some((default, _)) = default {
+				self.expression(default);
+			}
+			self.declare(name, span, true);
+		}
+		self.statements(&code.code);
+		self.pop_scope();
+	}
+
+	fn statements(&mut self, code: &Expression) {
+		let mut ended = None;
+		for ctoken in code {
+			if let Some(keyword) = ended {
+				if let Some(line) = statement_line(ctoken) {
+					let span = self.span(line, None);
+					self.report(
+						Rule::UnreachableCode,
+						span,
+						format!("Unreachable code after `{keyword}`"),
+					);
+					ended = None;
+				}
+			}
+			self.visit(ctoken);
+			match ctoken {
+				RETURN_EXPR(_) => ended = Some("return"),
+				BREAK_LOOP => ended = Some("break"),
+				CONTINUE_LOOP => ended = Some("continue"),
+				_ => {}
+			}
+		}
+	}
+
+	fn expression(&mut self, expr: &Expression) {
+		let mut field = false;
+		for ctoken in expr {
+			match ctoken {
+				SYMBOL(text) => {
+					if !field {
+						self.reference_text(text);
+					}
+					field = is_accessor(text);
+				}
+				_ => {
+					self.visit(ctoken);
+					field = false;
+				}
+			}
+		}
+	}
+
+	/// `target` is where the name is written if it is a single name
+	fn assign(&mut self, name: &Expression, kind: TokenType, target: Option<Span>) {
+		match (single_symbol(name), target) {
+			(Some(name), Some(span)) => {
+				if kind != TokenType::DEFINE {
+					self.reference(name);
+				}
+				if self.lookup(name).is_none() {
+					self.assigned.push((name.clone(), span));
+				}
+			}
+			_ => self.expression(name),
+		}
+	}
+
+	fn table(
+		&mut self,
+		values: &[(Option<Expression>, Expression, usize)],
+		metas: &[(String, Expression, usize)],
+	) {
+		let mut keys: AHashMap<String, usize> = AHashMap::new();
+		let mut index = 0;
+		let mut entries = Vec::with_capacity(values.len() + metas.len());
+		for (key, value, line) in values {
+			match key {
+				Some(key) => {
+					self.expression(key);
+					let key = key_name(key);
+					// the keys are found before the values, which can contain other tables
+					let span = match &key {
+						Some((_, shown)) => self.claim(Site::Assignment, *line, shown),
+						None => self.span(*line, None),
+					};
+					entries.push((key, span));
+				}
+				None => {
+					index += 1;
+					let shown = index.to_string();
+					let span = self.span(*line, Some(&shown));
+					entries.push((Some((format!("n:{}", index as f64), shown)), span));
+				}
+			}
+			self.expression(value);
+		}
+		for (name, value, line) in metas {
+			let shown = format!("meta {}", name.trim_start_matches('_'));
+			let span = self.span(*line, Some("meta"));
+			entries.push((Some((format!("m:{name}"), shown)), span));
+			self.expression(value);
+		}
+		for (key, span) in entries {
+			let Some((key, shown)) = key else {
+				continue;
+			};
+			match keys.entry(key) {
+				Entry::Occupied(first) => {
+					let first = *first.get();
+					self.report(
+						Rule::DuplicateKey,
+						span,
+						format!(
+							"Duplicate key {shown} in table, it was already set on line {first}"
+						),
+					);
+				}
+				Entry::Vacant(entry) => {
+					entry.insert(span.line);
+				}
+			}
+		}
+	}
+
+	fn visit(&mut self, ctoken: &ComplexToken) {
+		match ctoken {
+			VARIABLE {
+				local,
+				names,
+				values,
+				line,
+			} => {
+				// the names are found before the values, which can declare variables too
+				let spans: Vec<Span> = names
+					.iter()
+					.filter(|_| *local)
+					.map(|name| self.claim(Site::Declaration, *line, name))
+					.collect();
+				for value in values {
+					self.expression(value);
+				}
+				if *local {
+					for (name, span) in names.iter().zip(spans) {
+						self.declare(name, span, false);
+					}
+				} else {
+					self.globals.extend(names.iter().cloned());
+				}
+			}
+			ALTER {
+				kind,
+				names,
+				values,
+				line,
+			} => {
+				let targets: Vec<Option<Span>> = names
+					.iter()
+					.map(|name| {
+						single_symbol(name).map(|name| self.claim(Site::Assignment, *line, name))
+					})
+					.collect();
+				for value in values {
+					self.expression(value);
+				}
+				for (name, target) in names.iter().zip(targets) {
+					self.assign(name, *kind, target);
+				}
+			}
+			TABLE { values, metas, .. } => self.table(values, metas),
+			FUNCTION {
+				local,
+				name,
+				args,
+				code,
+			} => {
+				match single_symbol(name) {
+					Some(name) if *local => {
+						let span = self.claim(Site::Declaration, code.start, name);
+						self.declare(name, span, false);
+					}
+					Some(name) => {
+						self.globals.insert(name.clone());
+					}
+					_ => self.expression(name),
+				}
+				self.function(args, code);
+			}
+			LAMBDA { args, code } => self.function(args, code),
+			IF_STATEMENT {
+				condition,
+				code,
+				next,
+			} => {
+				self.expression(condition);
+				self.block(code, &[]);
+				if let Some(next) = next {
+					self.visit(next);
+				}
+			}
+			MATCH_BLOCK {
+				name,
+				value,
+				branches,
+				line,
+			} => {
+				self.expression(value);
+				let span = self.span(*line, Some("match"));
+				self.declare(name, span, false);
+				let has_default = branches
+					.last()
+					.is_some_and(|(conditions, _, extra_if, code)| {
+						conditions.is_empty()
+							&& extra_if.is_none() && !is_implicit_default(code, *line)
+					});
+				if !has_default {
+					self.report(
+						Rule::MatchWithoutDefault,
+						span,
+						String::from("This match has no default case"),
+					);
+				}
+				for (conditions, internal, extra_if, code) in branches {
+					for condition in conditions {
+						self.expression(condition);
+					}
+					self.statements(internal);
+					if let Some(extra_if) = extra_if {
+						self.expression(extra_if);
+					}
+					self.block(code, &[]);
+				}
+			}
+			WHILE_LOOP {
+				condition, code, ..
+			}
+			| LOOP_UNTIL {
+				condition, code, ..
+			} => {
+				self.expression(condition);
+				self.block(code, &[]);
+			}
+			FOR_LOOP {
+				iterator,
+				start,
+				end,
+				alter,
+				code,
+				line,
+			} => {
+				let span = self.claim(Site::Declaration, *line, iterator);
+				self.expression(start);
+				self.expression(end);
+				self.expression(alter);
+				self.block(code, &[(iterator, span)]);
+			}
+			FOR_FUNC_LOOP {
+				iterators,
+				expr,
+				code,
+				line,
+			} => {
+				let iterators: Vec<(&str, Span)> = iterators
+					.iter()
+					.map(|name| (name.as_str(), self.claim(Site::Declaration, *line, name)))
+					.collect();
+				self.expression(expr);
+				self.block(code, &iterators);
+			}
+			TRY_CATCH {
+				totry,
+				catch,
+				error,
+			} => {
+				self.block(totry, &[]);
+				if let Some(catch) = catch {
+					if catch.code.is_empty() {
+						let span = self.span(catch.start, Some("catch"));
+						self.report(
+							Rule::EmptyCatch,
+							span,
+							String::from("This catch block is empty, the error is ignored"),
+						);
+					}
+					match error {
+						Some(error) => {
+							let span = self.claim(Site::Declaration, catch.start, error);
+							self.block(catch, &[(error, span)]);
+						}
+						None => self.block(catch, &[]),
+					}
+				}
+			}
+			IDENT { expr, .. } | EXPR(expr) => self.expression(expr),
+			SYMBOL(text) => self.reference_text(text),
+			CALL(args) => {
+				for arg in args {
+					self.expression(arg);
+				}
+			}
+			DO_BLOCK(code) => self.block(code, &[]),
+			RETURN_EXPR(Some(exprs)) => {
+				for expr in exprs {
+					self.expression(expr);
+				}
+			}
+			RETURN_EXPR(None) | CONTINUE_LOOP | BREAK_LOOP => {}
+		}
+	}
+
+	fn finish(mut self) -> Vec<Diagnostic> {
+		self.pop_scope();
+		for (name, span) in std::mem::take(&mut self.assigned) {
+			if !self.globals.contains(&name) {
+				self.report(
+					Rule::UndeclaredGlobal,
+					span,
+					format!("Assignment to the undeclared global variable '{name}'"),
+				);
+			}
+		}
+		self.diagnostics
+			.sort_by_key(|diagnostic| diagnostic.span.map(|span| (span.line, span.column)));
+		self.diagnostics
+	}
+}
+
+/// Returns the line of a statement written by the user, skipping the `::continue::` label
+/// and the assignments added by the parser to implement `continue`
+fn statement_line(ctoken: &ComplexToken) -> Option<usize> {
+	match ctoken {
+		ALTER { names, line, .. } => {
+			let internal = names
+				.iter()
+				.all(|name| single_symbol(name).is_some_and(|name| name.starts_with("_internal")));
+			(!internal).then_some(*line)
+		}
+		VARIABLE { line, .. }
+		| MATCH_BLOCK { line, .. }
+		| WHILE_LOOP { line, .. }
+		| LOOP_UNTIL { line, .. }
+		| FOR_LOOP { line, .. }
+		| FOR_FUNC_LOOP { line, .. }
+		| IDENT { line, .. } => Some(*line),
+		FUNCTION { code, .. }
+		| LAMBDA { code, .. }
+		| IF_STATEMENT { code, .. }
+		| DO_BLOCK(code)
+		| TRY_CATCH { totry: code, .. } => Some(code.start),
+		_ => None,
+	}
+}
+
+/// Checks if the branch is the `default` case the parser adds to a `match` used as a value,
+/// which sets the result to `nil`
+fn is_implicit_default(code: &CodeBlock, line: usize) -> bool {
+	match code.code.front() {
+		Some(ALTER { values, .. }) if code.code.len() == 1 => {
+			code.start == line
+				&& code.end == line
+				&& values.len() == 1
+				&& single_symbol(&values[0]).is_some_and(|value| value == "nil")
+		}
+		_ => false,
+	}
+}
+
+/// Looks for common mistakes in the given Clue code
+/// It takes the code, the name of the file (used for the warnings), the options used to parse it
+/// and the rules to check, then returns a warning for every problem found sorted by position
+///
+/// # Errors
+/// If the code cannot be compiled it is not linted and every error found will be returned
+///
+/// # Examples
+/// ```
+/// use clue_core::{
+///     diagnostic::Diagnostic,
+///     env::Options,
+///     lint::{lint_code, LintRules},
+/// };
+///
+/// fn main() -> Result<(), Vec<Diagnostic>> {
+///     let filename = String::from("example.clue");
+///     let code = "local fn add(a, b) {\n\treturn a + 1\n}\nprint(add(1, 2))\n";
+///     let warnings = lint_code(code, &filename, &Options::default(), &LintRules::default())?;
+///     assert_eq!(warnings.len(), 1);
+///     assert_eq!(warnings[0].message, "Unused argument 'b'");
+///
+///     Ok(())
+/// }
+/// ```
+pub fn lint_code(
+	code: &str,
+	filename: &String,
+	options: &Options,
+	rules: &LintRules,
+) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+	let ast = parse_source(code, filename, options)?;
+	let lossless = LosslessCode::new(code);
+	let mut linter = Linter::new(filename, rules, &lossless);
+	linter.push_scope();
+	linter.statements(&ast);
+	Ok(linter.finish())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn lint(code: &str) -> Vec<(&'static str, usize)> {
+		lint_code(
+			code,
+			&String::from("test.clue"),
+			&Options::default(),
+			&LintRules::default(),
+		)
+		.unwrap()
+		.into_iter()
+		.map(|warning| (warning.code, warning.span.unwrap().line))
+		.collect()
+	}
+
+	#[test]
+	fn rules() {
+		assert_eq!(
+			lint(concat!(
+				"local x = 1\n",
+				"local fn f(a, _b) {\n\tlocal x = a\n\twhile x { break\n\tprint(x) }\n}\n",
+				"total = f(2)\n",
+				"local t = {a = 1, [\"a\"] = 2, 3, [1] = 4}\n",
+				"try { print(t) } catch {}\n",
+				"match t { 1 => { print(x) } }\n",
+			)),
+			[
+				("L0003", 3),
+				("L0005", 5),
+				("L0004", 7),
+				("L0008", 8),
+				("L0008", 8),
+				("L0007", 9),
+				("L0006", 10),
+			]
+		);
+	}
+
+	#[test]
+	fn columns() {
+		let columns = |code| -> Vec<(&'static str, usize)> {
+			lint_code(
+				code,
+				&String::from("test.clue"),
+				&Options::default(),
+				&LintRules::default(),
+			)
+			.unwrap()
+			.into_iter()
+			.map(|warning| (warning.code, warning.span.unwrap().column))
+			.collect()
+		};
+		assert_eq!(
+			columns("print({a = 1, a = {a = 2}, [\"a\"] = 3})"),
+			[("L0008", 15), ("L0008", 29)]
+		);
+		assert_eq!(
+			columns("local fn f(x) { local x = x }"),
+			[("L0001", 10), ("L0003", 23), ("L0001", 23)]
+		);
+		assert_eq!(
+			columns("local y = fn(y) { return y }\nprint(total) total = 1"),
+			[("L0001", 7), ("L0004", 14)]
+		);
+	}
+
+	#[test]
+	fn used_variables() {
+		assert!(lint(concat!(
+			"global count = 0\nlocal t = {}\n",
+			"for i = 1, 10 { count += i }\n",
+			"for _, v of t { t.total = v?.n ?? 0 }\n",
+			"local y = match count { 0 => 1, default => 2 }\n",
+			"if local z = t.x { print(z, y) }\n",
+			"extern score: number?, hook: fn(string): boolean | nil, other\n",
+			"extern fn love.draw(dt: number)\n",
+			"score, hook, love, other = 1, print, {}, nil\n",
+		))
+		.is_empty());
+	}
+
+	#[test]
+	fn pragmas() {
+		assert_eq!(
+			lint(concat!(
+				"local a = 1 // lint: allow(unused-local)\n",
+				"// lint: allow(unused-local, undeclared-global)\n",
+				"local b = 2\n",
+				"local c = 3 /* lint: allow(unused-local, typo) */\n",
+			)),
+			[("L0009", 4)]
+		);
+	}
+}
@@ -0,0 +1,351 @@
+//! This module contains a lossless view of Clue source code, used by tools that need to rewrite it.
+//!
+//! Unlike the preprocessor and the scanner, which throw away comments, directives and whitespace,
+//! [`LosslessCode`] keeps every byte of the original file: each token carries the trivia written before it,
+//! so the code can always be turned back into the exact original text with [`ToString`].
+
+use crate::scanner::{symbol_kind, word_kind, TokenType};
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// The kind of some [`Trivia`]
+pub enum TriviaKind {
+	/// Spaces, tabs and new lines
+	Whitespace,
+
+	/// A comment that starts with `//` and ends with the line
+	LineComment,
+
+	/// A comment between `/*` and `*/`
+	BlockComment,
+
+	/// A preprocessor directive, like `@define NAME value`.
+	/// The directives that open a block, like `@ifos linux {`, stop before their `{`
+	/// and the code inside the block is made of normal tokens.
+	Directive,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A part of the code that does not change what it does
+pub struct Trivia {
+	/// The kind of trivia.
+	pub kind: TriviaKind,
+
+	/// The original text.
+	pub text: String,
+
+	/// The line where the trivia starts.
+	pub line: usize,
+
+	/// The column where the trivia starts.
+	pub column: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// The kind of a [`LosslessToken`]
+pub enum SyntaxKind {
+	/// A token the scanner would read, with the type it would give it
+	Token(TokenType),
+
+	/// A preprocessor variable or macro call, like `$NAME`, `$macro!` or `$1`
+	Variable,
+
+	/// A character that is not valid Clue code
+	Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A token of the original code, together with the trivia written before it
+pub struct LosslessToken {
+	/// The kind of token.
+	pub kind: SyntaxKind,
+
+	/// The original text, strings keep their quotes and escapes.
+	pub text: String,
+
+	/// The line where the token starts.
+	pub line: usize,
+
+	/// The column where the token starts.
+	pub column: usize,
+
+	/// The whitespace, comments and directives between the previous token and this one.
+	pub leading: Vec<Trivia>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Every token and trivia of some Clue code
+pub struct LosslessCode {
+	/// The tokens of the code.
+	pub tokens: Vec<LosslessToken>,
+
+	/// The trivia after the last token.
+	pub trailing: Vec<Trivia>,
+}
+
+fn skip_string(code: &[u8], mut i: usize) -> usize {
+	let end = code[i];
+	i += 1;
+	while i < code.len() {
+		match code[i] {
+			b'\\' => i += 1,
+			c if c == end => return i + 1,
+			_ => {}
+		}
+		i += 1;
+	}
+	code.len()
+}
+
+fn skip_word(code: &[u8], mut i: usize) -> usize {
+	while i < code.len() && (code[i].is_ascii_alphanumeric() || code[i] == b'_') {
+		i += 1;
+	}
+	i
+}
+
+fn skip_number(code: &[u8], mut i: usize) -> usize {
+	let hex = code[i] == b'0' && matches!(code.get(i + 1), Some(b'x' | b'X'));
+	while i < code.len() {
+		match code[i] {
+			c if c.is_ascii_alphanumeric() || c == b'_' => i += 1,
+			b'.' if code.get(i + 1).is_some_and(u8::is_ascii_digit) => i += 1,
+			b'-' if !hex
+				&& matches!(code[i - 1], b'e' | b'E')
+				&& code.get(i + 1).is_some_and(u8::is_ascii_digit) =>
+			{
+				i += 1
+			}
+			_ => break,
+		}
+	}
+	i
+}
+
+fn skip_directive(code: &[u8], mut i: usize) -> usize {
+	let name_end = skip_word(code, i + 1);
+	let block = is_block_directive(&code[i + 1..]);
+	while i < code.len() {
+		match code[i] {
+			b'\n' if !block => break,
+			b'{' if block => break,
+			b'\'' | b'"' | b'`' => {
+				i = skip_string(code, i);
+				continue;
+			}
+			_ => {}
+		}
+		i += 1;
+	}
+	while i > name_end && code[i - 1].is_ascii_whitespace() {
+		i -= 1;
+	}
+	i
+}
+
+fn skip_symbol(code: &str, i: usize) -> (usize, SyntaxKind) {
+	for len in (1..=3).rev() {
+		if let Some(kind) = code.get(i..i + len).and_then(symbol_kind) {
+			return (i + len, SyntaxKind::Token(kind));
+		}
+	}
+	let len = code[i..].chars().next().map_or(1, char::len_utf8);
+	(i + len, SyntaxKind::Unknown)
+}
+
+/// Checks if the directive, written without its `@`, is followed by a block of code
+pub(crate) fn is_block_directive(directive: &[u8]) -> bool {
+	directive.starts_with(b"macro")
+		|| directive.starts_with(b"if")
+		|| directive.starts_with(b"else")
+}
+
+impl LosslessCode {
+	/// Splits the given code into tokens and trivia
+	/// This never fails: invalid characters become [`SyntaxKind::Unknown`] tokens
+	/// and unterminated strings or comments continue until the end of the code
+	///
+	/// # Examples
+	/// ```
+	/// use clue_core::{lossless::{LosslessCode, SyntaxKind, TriviaKind}, scanner::TokenType};
+	///
+	/// let code = "local x = 1 // one\n@define TWO 2\nprint(x, $TWO)\n";
+	/// let lossless = LosslessCode::new(code);
+	/// assert_eq!(lossless.tokens[0].kind, SyntaxKind::Token(TokenType::LOCAL));
+	/// assert_eq!(lossless.tokens[4].leading[1].kind, TriviaKind::LineComment);
+	/// assert_eq!(lossless.tokens[8].kind, SyntaxKind::Variable);
+	/// assert_eq!(lossless.to_string(), code);
+	/// ```
+	pub fn new(code: &str) -> Self {
+		let bytes = code.as_bytes();
+		let mut tokens: Vec<LosslessToken> = Vec::new();
+		let mut trivia = Vec::new();
+		let mut line = 1;
+		let mut line_start = 0;
+		let mut i = 0;
+		while i < bytes.len() {
+			let start = i;
+			let next = bytes.get(i + 1).copied();
+			let kind = match bytes[i] {
+				c if c.is_ascii_whitespace() => {
+					while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+						i += 1;
+					}
+					Err(TriviaKind::Whitespace)
+				}
+				b'/' if next == Some(b'/') => {
+					while i < bytes.len() && bytes[i] != b'\n' {
+						i += 1;
+					}
+					Err(TriviaKind::LineComment)
+				}
+				b'/' if next == Some(b'*') => {
+					i = match code[i + 2..].find("*/") {
+						Some(end) => i + end + 4,
+						None => bytes.len(),
+					};
+					Err(TriviaKind::BlockComment)
+				}
+				b'@' => {
+					i = skip_directive(bytes, i);
+					Err(TriviaKind::Directive)
+				}
+				b'\'' | b'"' | b'`' => {
+					i = skip_string(bytes, i);
+					Ok(SyntaxKind::Token(TokenType::STRING))
+				}
+				b'$' => {
+					i = skip_word(bytes, i + 1);
+					if i > start + 1
+						&& bytes.get(i) == Some(&b'!')
+						&& bytes.get(i + 1) != Some(&b'=')
+					{
+						i += 1;
+					}
+					Ok(SyntaxKind::Variable)
+				}
+				b'0'..=b'9' => {
+					i = skip_number(bytes, i);
+					Ok(SyntaxKind::Token(TokenType::NUMBER))
+				}
+				c if c.is_ascii_alphabetic() || c == b'_' => {
+					i = skip_word(bytes, i);
+					let last = match tokens.last() {
+						Some(LosslessToken {
+							kind: SyntaxKind::Token(kind),
+							..
+						}) => *kind,
+						_ => TokenType::EOF,
+					};
+					Ok(SyntaxKind::Token(word_kind(&code[start..i], last)))
+				}
+				_ => {
+					let kind;
+					(i, kind) = skip_symbol(code, i);
+					Ok(kind)
+				}
+			};
+			let text = code[start..i].to_owned();
+			let column = start - line_start + 1;
+			let token_line = line;
+			for (offset, _) in text.match_indices('\n') {
+				line += 1;
+				line_start = start + offset + 1;
+			}
+			match kind {
+				Ok(kind) => tokens.push(LosslessToken {
+					kind,
+					text,
+					line: token_line,
+					column,
+					leading: std::mem::take(&mut trivia),
+				}),
+				Err(kind) => trivia.push(Trivia {
+					kind,
+					text,
+					line: token_line,
+					column,
+				}),
+			}
+		}
+		LosslessCode {
+			tokens,
+			trailing: trivia,
+		}
+	}
+}
+
+impl fmt::Display for LosslessCode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for token in &self.tokens {
+			for trivia in &token.leading {
+				f.write_str(&trivia.text)?;
+			}
+			f.write_str(&token.text)?;
+		}
+		for trivia in &self.trailing {
+			f.write_str(&trivia.text)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		let code = concat!(
+			"@macro twice(x) {\r\n\t$x * 2\r\n}\r\n",
+			"local s = `raw\nstring` /* unterminated",
+		);
+		let lossless = LosslessCode::new(code);
+		assert_eq!(lossless.to_string(), code);
+		assert_eq!(lossless.tokens[0].text, "{");
+		assert_eq!(lossless.tokens[0].leading[0].text, "@macro twice(x)");
+		assert_eq!(lossless.trailing[1].kind, TriviaKind::BlockComment);
+		let string = &lossless.tokens[8];
+		assert_eq!((string.line, string.column), (4, 11));
+		assert_eq!(lossless.trailing[1].line, 5);
+	}
+
+	#[test]
+	fn kinds() {
+		let kinds: Vec<SyntaxKind> = LosslessCode::new("a?::b(...)/_t.end ?: ü")
+			.tokens
+			.into_iter()
+			.map(|token| token.kind)
+			.collect();
+		use TokenType::*;
+		assert_eq!(
+			kinds,
+			[
+				IDENTIFIER,
+				SAFE_DOUBLE_COLON,
+				IDENTIFIER,
+				ROUND_BRACKET_OPEN,
+				THREEDOTS,
+				ROUND_BRACKET_CLOSED,
+				FLOOR_DIVISION,
+				IDENTIFIER,
+				DOT,
+				IDENTIFIER,
+				QUESTION_MARK,
+				COLON,
+			]
+			.map(SyntaxKind::Token)
+			.into_iter()
+			.chain([SyntaxKind::Unknown])
+			.collect::<Vec<SyntaxKind>>()
+		);
+	}
+}
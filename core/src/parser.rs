@@ -8,10 +8,15 @@
 
 use self::ComplexToken::*;
 use crate::compiler::Compiler;
-use crate::env::{BitwiseMode, ContinueMode, LuaVersion, Options};
+use crate::diagnostic::{Diagnostic, Severity, Span};
+use crate::env::{BitwiseMode, ContinueMode, LuaVersion, Options, TypesMode};
 use crate::scanner::{BorrowedToken, TokenType::*};
 use crate::scanner::{Token, TokenType};
-use crate::{check, format_clue};
+use crate::format_clue;
+use crate::preprocessor::{preprocess_code, preprocess_codes};
+use crate::scanner::scan_code;
+use crate::types::{parse_type, std_declarations, FunctionType, LuaType};
+use ahash::{AHashMap, AHashSet};
 use std::cell::Cell;
 use std::vec;
 use std::{cmp, collections::VecDeque};
@@ -32,8 +37,27 @@ pub type Expression = VecDeque<ComplexToken>;
 /// used in function signatures.
 pub type FunctionArgs = Vec<(String, Option<(Expression, usize)>)>;
 
-//pub type LocalsList = Option<AHashMap<String, LuaType>>;
-//pub type ArgsAndTypes = (FunctionArgs, Option<Vec<(String, LuaType)>>);
+/// The types of the names visible in a block of code, `None` if the types are not checked.
+/// The flag tells if the type was written by the user or only inferred from the first value.
+type LocalsList = Option<AHashMap<String, (LuaType, bool)>>;
+
+/// Function arguments together with the types they have inside the function.
+type ArgsAndTypes = (FunctionArgs, Vec<LuaType>);
+
+/// A class declared with `struct`, see [`ParserInfo::build_class`]
+struct Class {
+	name: String,
+	local: bool,
+	parent: Option<String>,
+	fields: FunctionArgs,
+	types: Vec<LuaType>,
+	metas: Vec<(Option<Expression>, Expression, usize)>,
+	constructor: Option<(ArgsAndTypes, CodeBlock)>,
+	methods: Expression,
+}
+
+/// A name being declared, with its type annotation and position.
+type TypedName = (String, Option<LuaType>, usize, usize);
 
 /// An optional end token, which is used to check if the end token is present.
 /// It is a tuple of the token type and the token lexeme.
@@ -268,14 +292,47 @@ pub struct CodeBlock {
 	pub end: usize,
 }
 
-/*
-#[derive(Clone, PartialEq, Eq, Debug)]
-pub enum LuaType {
-	ANY,
-	NIL,
-	NUMBER,
+/// Returns the path of an expression made only of names and dots, like `math.floor`
+fn expression_path<'a>(expr: impl Iterator<Item = &'a ComplexToken>) -> Option<String> {
+	let mut path = String::new();
+	for (i, ctoken) in expr.enumerate() {
+		match ctoken {
+			SYMBOL(lexeme) if i % 2 == 1 && lexeme == "." => path.push('.'),
+			SYMBOL(lexeme)
+				if i % 2 == 0
+					&& lexeme.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') =>
+			{
+				path += lexeme
+			}
+			IDENT { expr, .. } if i == 0 => path += &expression_path(expr.iter())?,
+			_ => return None,
+		}
+	}
+	(!path.is_empty() && !path.ends_with('.')).then_some(path)
 }
-*/
+
+/// Returns the type of a literal value
+fn literal_type(lexeme: &str) -> Option<LuaType> {
+	match lexeme {
+		"nil" => Some(LuaType::NIL),
+		"true" | "false" => Some(LuaType::BOOLEAN),
+		_ if lexeme
+			.strip_prefix('.')
+			.unwrap_or(lexeme)
+			.starts_with(|c: char| c.is_ascii_digit()) =>
+		{
+			Some(LuaType::NUMBER)
+		}
+		_ if lexeme.starts_with(['"', '\'', '`'])
+			|| lexeme.starts_with("[[")
+			|| lexeme.starts_with("[=") =>
+		{
+			Some(LuaType::STRING)
+		}
+		_ => None,
+	}
+}
+
 struct ParserInfo<'a> {
 	options: &'a Options,
 	current: usize,
@@ -287,12 +344,20 @@ struct ParserInfo<'a> {
 	internal_stack: Vec<Cell<Expression>>,
 	statics: String,
 	compiler: Compiler<'a>,
-	//locals: LocalsList,
+	errors: Vec<Diagnostic>,
+	warnings: Vec<Diagnostic>,
+	locals: LocalsList,
+	returns: Option<LuaType>,
+	/// The names of the structs that are not classes, which cannot be used as a parent
+	structs: AHashSet<String>,
 }
 
 impl<'a> ParserInfo<'a> {
 	fn new(
-		tokens: Vec<Token>, /* , locals: LocalsList */
+		tokens: Vec<Token>,
+		locals: LocalsList,
+		returns: Option<LuaType>,
+		structs: AHashSet<String>,
 		filename: &'a String,
 		options: &'a Options,
 	) -> ParserInfo<'a> {
@@ -306,33 +371,46 @@ impl<'a> ParserInfo<'a> {
 			internal_stack: Vec::new(),
 			statics: String::new(),
 			compiler: Compiler::new(options, filename),
+			errors: Vec::new(),
+			warnings: Vec::new(),
 			options,
-			// locals,
+			locals,
+			returns,
+			structs,
 		}
 	}
 
-	/*
-		fn warning(&self, msg: impl Into<String>, line: usize) {
-			println!(
-				"Warning in \"{}\" at line {}!\nWarning: \"{}\"",
-				self.filename,
-				line,
-				msg.into()
-			);
+	/// Reports a type mismatch, as an error in strict mode and as a warning otherwise
+	fn type_mismatch(&mut self, code: &'static str, msg: String, line: usize, column: usize) {
+		let severity = match self.options.env_types {
+			TypesMode::STRICT => Severity::Error,
+			_ => Severity::Warning,
+		};
+		let diagnostic =
+			Diagnostic::new(severity, code, msg, self.filename.as_str()).at(line, column);
+		match severity {
+			Severity::Error => self.errors.push(diagnostic),
+			_ => self.warnings.push(diagnostic),
 		}
-	*/
+	}
 
-	fn error(&mut self, msg: impl Into<String>, line: usize, column: usize) -> String {
-		eprintln!("Error in {}:{line}:{column}!", self.filename);
-		msg.into()
+	fn error(
+		&mut self,
+		code: &'static str,
+		msg: impl Into<String>,
+		line: usize,
+		column: usize,
+	) -> Diagnostic {
+		Diagnostic::error(code, msg, self.filename.as_str()).at(line, column)
 	}
 
-	fn expected(&mut self, expected: &str, got: &str, line: usize, column: usize) -> String {
-		self.error(
+	fn expected(&mut self, expected: &str, got: &str, line: usize, column: usize) -> Diagnostic {
+		Diagnostic::error(
+			"E0001",
 			format_clue!("Expected '", expected, "', got '", got, "'"),
-			line,
-			column,
+			self.filename.as_str(),
 		)
+		.with_span(Span::sized(line, column, got.chars().count()))
 	}
 
 	fn expected_before(
@@ -341,16 +419,47 @@ impl<'a> ParserInfo<'a> {
 		before: &str,
 		line: usize,
 		column: usize,
-	) -> String {
-		self.error(
+	) -> Diagnostic {
+		Diagnostic::error(
+			"E0002",
 			format_clue!("Expected '", expected, "' before '", before, "'"),
-			line,
-			column,
+			self.filename.as_str(),
 		)
+		.with_span(Span::sized(line, column, before.chars().count()))
 	}
 
-	fn unexpected(&mut self, str: &str, line: usize, column: usize) -> String {
-		self.error(format_clue!("Unexpected token '", str, "'"), line, column)
+	fn unexpected(&mut self, str: &str, line: usize, column: usize) -> Diagnostic {
+		Diagnostic::error(
+			"E0003",
+			format_clue!("Unexpected token '", str, "'"),
+			self.filename.as_str(),
+		)
+		.with_span(Span::sized(line, column, str.chars().count()))
+	}
+
+	fn recover(&mut self) {
+		self.internal_stack.clear();
+		let mut cscope = 0usize;
+		while !self.ended() {
+			let t = self.peek(0);
+			match t.kind() {
+				CURLY_BRACKET_OPEN => cscope += 1,
+				CURLY_BRACKET_CLOSED => cscope = cscope.saturating_sub(1),
+				SEMICOLON if cscope == 0 => {
+					self.current += 1;
+					break;
+				}
+				LOCAL | GLOBAL | STATIC | METHOD | IF | MATCH | WHILE | UNTIL | LOOP | FOR
+				| CONTINUE | BREAK | RETURN | TRY | ENUM | STRUCT | EXTERN
+					if cscope == 0 =>
+				{
+					break
+				}
+				FN if cscope == 0 && self.peek(1).kind() == IDENTIFIER => break,
+				_ => {}
+			}
+			self.current += 1;
+		}
 	}
 
 	const fn ended(&self) -> bool {
@@ -401,7 +510,7 @@ impl<'a> ParserInfo<'a> {
 		&mut self,
 		expected: TokenType,
 		error: &str,
-	) -> Result<BorrowedToken, String> {
+	) -> Result<BorrowedToken, Diagnostic> {
 		let t = self.advance();
 		if t.kind() != expected {
 			return Err(self.expected(error, &t.lexeme(), t.line(), t.column()));
@@ -409,7 +518,7 @@ impl<'a> ParserInfo<'a> {
 		Ok(t)
 	}
 
-	fn assert_compare(&mut self, expected: TokenType, error: &str) -> Result<(), String> {
+	fn assert_compare(&mut self, expected: TokenType, error: &str) -> Result<(), Diagnostic> {
 		if !self.compare(expected) {
 			let t = self.peek(0);
 			return Err(self.expected(error, &t.lexeme(), t.line(), t.column()));
@@ -422,7 +531,7 @@ impl<'a> ParserInfo<'a> {
 		tocheck: &BorrowedToken,
 		end: OptionalEnd,
 		iftrue: T,
-	) -> Result<T, String> {
+	) -> Result<T, Diagnostic> {
 		if let Some((kind, lexeme)) = end {
 			if tocheck.kind() != kind {
 				return Err(self.expected(
@@ -436,27 +545,185 @@ impl<'a> ParserInfo<'a> {
 		Ok(iftrue)
 	}
 
-	fn assert(&mut self, expected: TokenType, error: &str) -> Result<(), String> {
+	fn assert(&mut self, expected: TokenType, error: &str) -> Result<(), Diagnostic> {
 		if !self.advance_if(expected) {
 			let t = self.peek(0);
 			return Err(self.expected(error, &t.lexeme(), t.line(), t.column()));
 		}
 		Ok(())
 	}
-	/*
-		fn assert_variable(&mut self, mut variable: Iter<ComplexToken>) -> Result<LuaType, String> {
-			let mut scope: &LocalsList = &self.locals;
-			let mut luatype = LuaType::NIL;
-			while let Some(locals) = scope {
-				if let Some(t) = variable.next() {
-					// TODO
-				} else {
-					break;
+	fn build_type(&mut self) -> Result<Option<LuaType>, Diagnostic> {
+		if !self.advance_if(COLON) {
+			return Ok(None);
+		}
+		parse_type(&self.tokens, &mut self.current, self.filename).map(Some)
+	}
+
+	fn add_variable(&mut self, name: String, luatype: LuaType) {
+		if let Some(locals) = &mut self.locals {
+			locals.insert(name, (luatype, true));
+		}
+	}
+
+	/// Adds a name whose type was inferred, assigning it other values later widens its type
+	fn add_inferred_variable(&mut self, name: String, luatype: LuaType) {
+		if let Some(locals) = &mut self.locals {
+			locals.insert(name, (luatype, false));
+		}
+	}
+
+	/// Runs `f` with some more names declared, used for the code blocks that declare their own variables
+	fn with_locals<T>(
+		&mut self,
+		names: impl IntoIterator<Item = (String, LuaType)>,
+		f: impl FnOnce(&mut Self) -> T,
+	) -> T {
+		let Some(locals) = self.locals.clone() else {
+			return f(self);
+		};
+		for (name, luatype) in names {
+			self.add_variable(name, luatype);
+		}
+		let result = f(self);
+		self.locals = Some(locals);
+		result
+	}
+
+	/// Finds the type of a name or of a path like `math.floor`
+	fn lookup_type(&self, path: &str) -> Option<LuaType> {
+		let locals = self.locals.as_ref()?;
+		if let Some((luatype, _)) = locals.get(path) {
+			return Some(luatype.clone());
+		}
+		match path.split_once('.') {
+			Some((root, _)) if locals.contains_key(root) => None,
+			_ => std_declarations(self.options.env_std).get(path).cloned(),
+		}
+	}
+
+	fn infer_identifier_type(&self, expr: &Expression) -> LuaType {
+		let luatype = match expr.back() {
+			Some(CALL(_)) => match expression_path(expr.range(..expr.len() - 1))
+				.and_then(|path| self.lookup_type(&path))
+			{
+				Some(LuaType::FUNCTION(Some(signature))) => Some(signature.returns),
+				_ => None,
+			},
+			_ => expression_path(expr.iter()).and_then(|path| self.lookup_type(&path)),
+		};
+		luatype.unwrap_or(LuaType::ANY)
+	}
+
+	/// Infers the type of an expression from its literals, operators and the annotated names it uses
+	fn infer_type(&self, expr: &Expression) -> LuaType {
+		if self.locals.is_none() {
+			return LuaType::ANY;
+		}
+		if expr.len() == 1 {
+			return match &expr[0] {
+				SYMBOL(lexeme) => literal_type(lexeme)
+					.or_else(|| self.lookup_type(lexeme))
+					.unwrap_or(LuaType::ANY),
+				TABLE { .. } => LuaType::TABLE,
+				LAMBDA { .. } => LuaType::FUNCTION(None),
+				EXPR(expr) => self.infer_type(expr),
+				IDENT { expr, .. } => self.infer_identifier_type(expr),
+				_ => LuaType::ANY,
+			};
+		}
+		// the operator with the lowest precedence decides the type of the result
+		let mut result = (0, LuaType::ANY);
+		for ctoken in expr {
+			let SYMBOL(operator) = ctoken else {
+				continue;
+			};
+			let operator = match operator.trim() {
+				"and" | "or" => return LuaType::ANY,
+				"==" | "~=" | "<" | ">" | "<=" | ">=" => (3, LuaType::BOOLEAN),
+				".." => (2, LuaType::STRING),
+				"not" => (1, LuaType::BOOLEAN),
+				"+" | "-" | "*" | "/" | "%" | "^" | "#" | "&" | "|" | "~" | "<<" | ">>" => {
+					(1, LuaType::NUMBER)
 				}
+				_ => continue,
+			};
+			if operator.0 > result.0 {
+				result = operator;
 			}
-			Ok(luatype)
 		}
-	*/
+		result.1
+	}
+
+	fn check_type(
+		&mut self,
+		expected: &LuaType,
+		value: &Expression,
+		line: usize,
+		column: usize,
+		message: impl FnOnce(&LuaType) -> String,
+	) {
+		let found = self.infer_type(value);
+		if !expected.accepts(&found) {
+			self.type_mismatch("T0001", message(&found), line, column);
+		}
+	}
+
+	fn check_call(
+		&mut self,
+		name: &str,
+		signature: &FunctionType,
+		args: &[Expression],
+		line: usize,
+		column: usize,
+	) {
+		for (i, arg) in args.iter().enumerate() {
+			let Some(expected) = signature.args.get(i) else {
+				if !signature.variadic {
+					self.type_mismatch(
+						"T0002",
+						format!(
+							"Too many arguments for '{name}': expected {}, got {}",
+							signature.args.len(),
+							args.len()
+						),
+						line,
+						column,
+					);
+				}
+				break;
+			};
+			self.check_type(expected, arg, line, column, |found| {
+				format!(
+					"Argument {} of '{name}' should be '{expected}', got '{found}'",
+					i + 1
+				)
+			});
+		}
+		// a call or `...` as the last argument can give any amount of values
+		let expands = args.last().is_some_and(|arg| match arg.back() {
+			Some(IDENT { expr, .. }) => matches!(expr.back(), Some(CALL(_))),
+			Some(SYMBOL(lexeme)) => lexeme == "...",
+			_ => false,
+		});
+		if expands {
+			return;
+		}
+		let missing = signature.args.iter().enumerate().skip(args.len());
+		if let Some((i, expected)) = missing
+			.into_iter()
+			.find(|(_, expected)| !expected.accepts(&LuaType::NIL))
+		{
+			self.type_mismatch(
+				"T0002",
+				format!(
+					"Argument {} of '{name}' is missing, it should be '{expected}'",
+					i + 1
+				),
+				line,
+				column,
+			);
+		}
+	}
 
 	fn get_next_internal_var(&mut self) -> String {
 		let var = format_clue!("_internal", self.internal_var_id.to_string());
@@ -464,7 +731,7 @@ impl<'a> ParserInfo<'a> {
 		var
 	}
 
-	fn build_call(&mut self) -> Result<Vec<Expression>, String> {
+	fn build_call(&mut self) -> Result<Vec<Expression>, Diagnostic> {
 		let args: Vec<Expression> = if self.advance_if(ROUND_BRACKET_CLOSED) {
 			Vec::new()
 		} else {
@@ -473,7 +740,7 @@ impl<'a> ParserInfo<'a> {
 		Ok(args)
 	}
 
-	fn find_expressions(&mut self, end: OptionalEnd) -> Result<Vec<Expression>, String> {
+	fn find_expressions(&mut self, end: OptionalEnd) -> Result<Vec<Expression>, Diagnostic> {
 		let mut exprs: Vec<Expression> = Vec::new();
 		loop {
 			let expr = self.build_expression(None)?;
@@ -485,7 +752,57 @@ impl<'a> ParserInfo<'a> {
 		}
 	}
 
-	fn build_table(&mut self) -> Result<ComplexToken, String> {
+	/// Reads the name of a metamethod after `meta` and returns the name Lua gives to it
+	fn build_meta_name(&mut self) -> Result<&'static str, Diagnostic> {
+		let name_token = self.advance();
+		Ok(match name_token.lexeme().as_ref() {
+			"index" => "__index",
+			"newindex" => "__newindex",
+			"usedindex" => {
+				if matches!(self.options.env_target, Some(LuaVersion::BLUA)) {
+					"__usedindex"
+				} else {
+					return Err(self.error(
+						"E0005",
+						"The 'usedindex' metamethod can only be used with --target=blua",
+						name_token.line(),
+						name_token.column(),
+					));
+				}
+			}
+			"mode" => "__mode",
+			"call" => "__call",
+			"metatable" => "__metatable",
+			"tostring" => "__tostring",
+			"len" => "__len",
+			"pairs" => "__pairs",
+			"ipairs" => "__ipairs",
+			"gc" => "__gc",
+			"name" => "__name",
+			"close" => "__close",
+			"unm" | "unary" => "__unm",
+			"add" | "+" => "__add",
+			"sub" | "-" => "__sub",
+			"mul" | "*" => "__mul",
+			"div" | "/" => "__div",
+			"mod" | "%" => "__mod",
+			"pow" | "^" => "__pow",
+			"concat" | ".." => "__concat",
+			"eq" | "equal" | "==" => "__eq",
+			"lt" | "less_than" | "<" => "__lt",
+			"le" | "less_than_equal" | "<=" => "__le",
+			_ => {
+				return Err(self.expected(
+					"<meta name>",
+					&name_token.lexeme(),
+					name_token.line(),
+					name_token.column(),
+				));
+			}
+		})
+	}
+
+	fn build_table(&mut self) -> Result<ComplexToken, Diagnostic> {
 		let mut values: Vec<(Option<Expression>, Expression, usize)> = Vec::new();
 		let mut metas: Vec<(String, Expression, usize)> = Vec::new();
 		let mut metatable: Option<String> = None;
@@ -569,6 +886,7 @@ impl<'a> ParserInfo<'a> {
 					if self.advance_if(WITH) {
 						if !metas.is_empty() {
 							return Err(self.error(
+								"E0005",
 								"An external metatable cannot be used if the table already set its own metamethods",
 								pn.line(),
 								pn.column()
@@ -581,56 +899,13 @@ impl<'a> ParserInfo<'a> {
 
 					if metatable.is_some() {
 						return Err(self.error(
+								"E0005",
 								"Metamethods cannot be set if the table already uses an external metatable",
 								pn.line(),
 								pn.column()
 							));
 					}
-					let name_token = self.advance();
-					name = Err(String::from(match name_token.lexeme().as_ref() {
-						"index" => "__index",
-						"newindex" => "__newindex",
-						"usedindex" => {
-							if matches!(self.options.env_target, Some(LuaVersion::BLUA)) {
-								"__usedindex"
-							} else {
-								return Err(self.error(
-								"The 'usedindex' metamethod can only be used with --target=blua",
-								name_token.line(),
-								name_token.column()
-							));
-							}
-						}
-						"mode" => "__mode",
-						"call" => "__call",
-						"metatable" => "__metatable",
-						"tostring" => "__tostring",
-						"len" => "__len",
-						"pairs" => "__pairs",
-						"ipairs" => "__ipairs",
-						"gc" => "__gc",
-						"name" => "__name",
-						"close" => "__close",
-						"unm" | "unary" => "__unm",
-						"add" | "+" => "__add",
-						"sub" | "-" => "__sub",
-						"mul" | "*" => "__mul",
-						"div" | "/" => "__div",
-						"mod" | "%" => "__mod",
-						"pow" | "^" => "__pow",
-						"concat" | ".." => "__concat",
-						"eq" | "equal" | "==" => "__eq",
-						"lt" | "less_than" | "<" => "__lt",
-						"le" | "less_than_equal" | "<=" => "__le",
-						_ => {
-							return Err(self.expected(
-								"<meta name>",
-								&name_token.lexeme(),
-								name_token.line(),
-								name_token.column(),
-							));
-						}
-					}))
+					name = Err(String::from(self.build_meta_name()?));
 				}
 				_ => return Err(self.expected("<name>", &pn.lexeme(), pn.line(), pn.column())),
 			}
@@ -686,7 +961,7 @@ impl<'a> ParserInfo<'a> {
 		t: &BorrowedToken,
 		notable: &mut bool,
 		checkback: Option<&Expression>,
-	) -> Result<(), String> {
+	) -> Result<(), Diagnostic> {
 		if match self.peek(0).kind() {
 			NUMBER | IDENTIFIER | STRING | TRUE | FALSE | MINUS | BIT_NOT | NIL | NOT | HASHTAG
 			| ROUND_BRACKET_OPEN | THREEDOTS | MATCH => false,
@@ -697,6 +972,7 @@ impl<'a> ParserInfo<'a> {
 			_ => true,
 		} {
 			return Err(self.error(
+				"E0004",
 				format!("Operator '{}' has invalid right hand token", t.lexeme()),
 				t.line(),
 				t.column(),
@@ -705,6 +981,7 @@ impl<'a> ParserInfo<'a> {
 		if let Some(expr) = checkback {
 			if expr.is_empty() {
 				return Err(self.error(
+					"E0004",
 					format!("Operator '{}' lacks a left hand token", t.lexeme()),
 					t.line(),
 					t.column(),
@@ -718,6 +995,7 @@ impl<'a> ParserInfo<'a> {
 					| THREEDOTS | CURLY_BRACKET_CLOSED
 			) {
 				return Err(self.error(
+					"E0004",
 					format!("Operator '{}' has invalid left hand token", t.lexeme()),
 					t.line(),
 					t.column(),
@@ -734,7 +1012,7 @@ impl<'a> ParserInfo<'a> {
 		fname: impl Into<String>,
 		end: OptionalEnd,
 		notable: &mut bool,
-	) -> Result<(), String> {
+	) -> Result<(), Diagnostic> {
 		self.check_operator(t, notable, Some(expr))?;
 		let mut arg1 = Expression::with_capacity(expr.len());
 		arg1.append(expr);
@@ -752,7 +1030,7 @@ impl<'a> ParserInfo<'a> {
 		fname: &str,
 		end: OptionalEnd,
 		notable: &mut bool,
-	) -> Result<bool, String> {
+	) -> Result<bool, Diagnostic> {
 		self.check_operator(t, notable, Some(expr))?;
 		Ok(if let Some(bit) = &self.options.env_jitbit {
 			self.build_function_op(t, expr, format!("{bit}.{fname}"), end, notable)?;
@@ -768,11 +1046,12 @@ impl<'a> ParserInfo<'a> {
 		t: &BorrowedToken,
 		expr: &mut Expression,
 		lexeme: &str,
-	) -> Result<(), String> {
+	) -> Result<(), Diagnostic> {
 		if !self.compare(IDENTIFIER)
 			|| matches!(self.look_back(0).kind(), IDENTIFIER | SQUARE_BRACKET_CLOSED)
 		{
 			return Err(self.error(
+				"E0008",
 				format!("'{}' should be used only when indexing", t.lexeme()),
 				self.peek(0).line(),
 				self.peek(0).column(),
@@ -794,23 +1073,23 @@ impl<'a> ParserInfo<'a> {
 	}
 
 	fn get_prev_expr(&mut self) -> &mut Expression {
-		return match self.internal_stack.last_mut() {
+		match self.internal_stack.last_mut() {
 			Some(last) => last.get_mut(),
 			None => &mut self.expr
-		};
+		}
 	}
 
 	fn use_internal_stack<T>(
 		&mut self,
-		f: impl FnOnce(&mut Self) -> Result<T, String>
-	) -> Result<(T, Expression), String> {
+		f: impl FnOnce(&mut Self) -> Result<T, Diagnostic>,
+	) -> Result<(T, Expression), Diagnostic> {
 		self.internal_stack.push(Cell::new(Expression::new()));
 		let result = f(self)?;
 		let code = self.internal_stack.pop().unwrap().into_inner();
 		Ok((result, code))
 	}
 
-	fn build_expression(&mut self, end: OptionalEnd) -> Result<Expression, String> {
+	fn build_expression(&mut self, end: OptionalEnd) -> Result<Expression, Diagnostic> {
 		let mut expr = Expression::with_capacity(16);
 		let notable = &mut true;
 		let start = self.current;
@@ -1075,14 +1354,15 @@ impl<'a> ParserInfo<'a> {
 					}
 				}
 				FN => {
-					let /*(*/args/*, types)*/ = if self.advance_if(ROUND_BRACKET_OPEN)
+					let args = if self.advance_if(ROUND_BRACKET_OPEN)
 						&& !self.advance_if(ROUND_BRACKET_CLOSED)
 					{
 						self.build_function_args()?
 					} else {
-						/*(*/FunctionArgs::new()//, None)
+						(FunctionArgs::new(), Vec::new())
 					};
-					let code = self.build_function_block(/*types*/)?;
+					let (_, returns) = self.build_function_signature(&args)?;
+					let (args, code) = self.build_function_block(args, returns)?;
 					expr.push_back(LAMBDA { args, code });
 					if self.check_val() {
 						break t;
@@ -1101,11 +1381,11 @@ impl<'a> ParserInfo<'a> {
 		self.assert_end(&self.look_back(0), end, expr)
 	}
 
-	fn build_name(&mut self) -> Result<Expression, String> {
+	fn build_name(&mut self) -> Result<Expression, Diagnostic> {
 		Ok(vec_deque![self.build_identifier()?])
 	}
 
-	fn build_identifier(&mut self) -> Result<ComplexToken, String> {
+	fn build_identifier(&mut self) -> Result<ComplexToken, Diagnostic> {
 		let line = self.look_back(0).line();
 		let mut expr = Expression::with_capacity(8);
 		let safe_indexing = self.build_identifier_internal(&mut expr)?;
@@ -1141,7 +1421,7 @@ impl<'a> ParserInfo<'a> {
 		true
 	}
 
-	fn build_identifier_internal(&mut self, expr: &mut Expression) -> Result<bool, String> {
+	fn build_identifier_internal(&mut self, expr: &mut Expression) -> Result<bool, Diagnostic> {
 		let mut safe_indexing = false;
 		self.current -= 1;
 		loop {
@@ -1226,7 +1506,17 @@ impl<'a> ParserInfo<'a> {
 				}
 				ROUND_BRACKET_OPEN | SAFE_CALL => {
 					safe_indexing |= self.build_safe_index(ROUND_BRACKET_OPEN, t.kind(), expr);
-					expr.push_back(CALL(self.build_call()?));
+					let (line, column) = (t.line(), t.column());
+					let path = expression_path(expr.iter());
+					let signature = match path.as_ref().and_then(|path| self.lookup_type(path)) {
+						Some(LuaType::FUNCTION(Some(signature))) => path.zip(Some(signature)),
+						_ => None,
+					};
+					let args = self.build_call()?;
+					if let Some((path, signature)) = signature {
+						self.check_call(&path, &signature, &args, line, column);
+					}
+					expr.push_back(CALL(args));
 					if self.check_val() {
 						break;
 					}
@@ -1237,7 +1527,7 @@ impl<'a> ParserInfo<'a> {
 		Ok(safe_indexing)
 	}
 
-	fn get_code_block_start(&mut self) -> Result<usize, String> {
+	fn get_code_block_start(&mut self) -> Result<usize, Diagnostic> {
 		let t = self.advance();
 		if t.kind() != CURLY_BRACKET_OPEN {
 			self.current -= 2;
@@ -1252,22 +1542,33 @@ impl<'a> ParserInfo<'a> {
 		}
 	}
 
-	fn parse_code_block(
-		&mut self,
-		mut tokens: Vec<Token>,
-		//locals: LocalsList,
-	) -> Result<Expression, String> {
+	fn parse_code_block(&mut self, mut tokens: Vec<Token>) -> Result<Expression, Diagnostic> {
 		if tokens.is_empty() {
 			Ok(Expression::new())
 		} else {
 			tokens.push(self.tokens.last().unwrap().clone());
-			let (ctokens, statics) = parse_tokens(tokens, self.filename, self.options)?;
-			self.statics += &statics;
-			Ok(ctokens)
+			match parse_block(
+				tokens,
+				self.locals.clone(),
+				self.returns.clone(),
+				self.structs.clone(),
+				self.filename,
+				self.options,
+			) {
+				Ok((ctokens, statics, warnings)) => {
+					self.statics += &statics;
+					self.warnings.extend(warnings);
+					Ok(ctokens)
+				}
+				Err(errors) => {
+					self.errors.extend(errors);
+					Ok(Expression::new())
+				}
+			}
 		}
 	}
 
-	fn build_code_block(&mut self /* , locals: LocalsList */) -> Result<CodeBlock, String> {
+	fn build_code_block(&mut self) -> Result<CodeBlock, Diagnostic> {
 		let start = self.get_code_block_start()?;
 		let mut tokens: Vec<Token> = Vec::new();
 		let mut cscope = 1u8;
@@ -1288,29 +1589,47 @@ impl<'a> ParserInfo<'a> {
 			}
 			tokens.push(t.into_owned());
 		}
-		let code = self.parse_code_block(tokens /* , locals */)?;
+		let code = self.parse_code_block(tokens)?;
 		Ok(CodeBlock { start, code, end })
 	}
 
 	fn build_function_block(
 		&mut self,
-		//args: Option<Vec<(String, LuaType)>>,
-	) -> Result<CodeBlock, String> {
-		/*
-		if let Some(args) = args {
-			self.build_code_block({
-				let mut locals = self.locals.clone().unwrap();
-				for (name, luatype) in args {
-					locals.insert(name, luatype);
-				}
-				Some(locals)
-			})
-		} else {*/
-		self.build_code_block(/*self.locals.clone()*/)
-		//}
+		(args, types): ArgsAndTypes,
+		returns: Option<LuaType>,
+	) -> Result<(FunctionArgs, CodeBlock), Diagnostic> {
+		let names = args.iter().map(|(name, _)| name.clone()).zip(types);
+		let code = self.with_locals(names, |i| {
+			let returns = std::mem::replace(&mut i.returns, returns);
+			let code = i.build_code_block();
+			i.returns = returns;
+			code
+		})?;
+		Ok((args, code))
+	}
+
+	/// Builds the signature of a function from its arguments and its optional return type
+	fn build_function_signature(
+		&mut self,
+		(args, types): &ArgsAndTypes,
+	) -> Result<(FunctionType, Option<LuaType>), Diagnostic> {
+		let returns = self.build_type()?;
+		let mut signature = FunctionType {
+			args: Vec::with_capacity(args.len()),
+			variadic: false,
+			returns: returns.clone().unwrap_or(LuaType::ANY),
+		};
+		for ((name, default), luatype) in args.iter().zip(types) {
+			match (name.as_str(), default) {
+				("...", _) => signature.variadic = true,
+				(_, Some(_)) => signature.args.push(luatype.clone().optional()),
+				(_, None) => signature.args.push(luatype.clone()),
+			}
+		}
+		Ok((signature, returns))
 	}
 
-	fn build_loop_block(&mut self) -> Result<CodeBlock, String> {
+	fn build_loop_block(&mut self) -> Result<CodeBlock, Diagnostic> {
 		let mut hascontinue: Option<String> = None;
 		let mut is_in_other_loop = false;
 		let start = self.get_code_block_start()?;
@@ -1355,7 +1674,7 @@ impl<'a> ParserInfo<'a> {
 			}
 			tokens.push(t.into_owned());
 		}
-		let mut code = self.parse_code_block(tokens /* , self.locals.clone() */)?;
+		let mut code = self.parse_code_block(tokens)?;
 		if let Some(name) = hascontinue {
 			use ContinueMode::*;
 			match self.options.env_continue {
@@ -1396,7 +1715,7 @@ impl<'a> ParserInfo<'a> {
 		Ok(CodeBlock { start, code, end })
 	}
 
-	fn build_identifier_list(&mut self) -> Result<Vec<String>, String> {
+	fn build_identifier_list(&mut self) -> Result<Vec<String>, Diagnostic> {
 		let mut idents: Vec<String> = Vec::new();
 		loop {
 			let t = self.assert_advance(IDENTIFIER, "<name>")?;
@@ -1407,13 +1726,21 @@ impl<'a> ParserInfo<'a> {
 		}
 	}
 
-	fn build_function_args(&mut self) -> Result</* ArgsAndTypes */ FunctionArgs, String> {
+	fn build_typed_identifier_list(&mut self) -> Result<Vec<TypedName>, Diagnostic> {
+		let mut idents = Vec::new();
+		loop {
+			let t = self.assert_advance(IDENTIFIER, "<name>")?;
+			let luatype = self.build_type()?;
+			idents.push((t.lexeme(), luatype, t.line(), t.column()));
+			if !self.advance_if(COMMA) {
+				break Ok(idents);
+			}
+		}
+	}
+
+	fn build_function_args(&mut self) -> Result<ArgsAndTypes, Diagnostic> {
 		let mut args = FunctionArgs::new();
-		/*let mut types: Option<Vec<(String, LuaType)>> = if self.locals.is_some() {
-			Some(Vec::new())
-		} else {
-			None
-		};*/
+		let mut types = Vec::new();
 		while {
 			let name = {
 				let t = self.advance();
@@ -1426,49 +1753,52 @@ impl<'a> ParserInfo<'a> {
 					_ => return Err(self.expected("<name>", &t.lexeme(), t.line(), t.column())),
 				}
 			};
-			/*if let Some(types) = &mut types {
-				types.push((
-					name.lexeme(),
-					if name.kind() == THREEDOTS {
-						LuaType::ANY
-					} else {
-						self.build_type()?
-					},
-				))
-			}*/
+			let (line, column) = (name.line(), name.column());
+			let name = name.lexeme();
+			let luatype = if name == "..." {
+				None
+			} else {
+				self.build_type()?
+			};
 			let t = self.advance();
-			match t.kind() {
+			let more = match t.kind() {
 				COMMA => {
-					args.push((name.lexeme(), None));
+					args.push((name, None));
 					true
 				}
 				DEFINE => {
 					let default = self.build_expression(None)?;
-					args.push((name.lexeme(), Some((default, name.line()))));
-					let notended = self.peek(0).kind() != CURLY_BRACKET_OPEN;
-					if notended {
-						match self.look_back(0).kind() {
-							COMMA => {}
-							ROUND_BRACKET_CLOSED => self.current -= 1,
-							_ => {
-								let t = self.peek(0);
-								return Err(self.expected(")", &t.lexeme(), t.line(), t.column()));
-							}
+					if let Some(luatype) = &luatype {
+						self.check_type(luatype, &default, line, column, |found| {
+							format!("The default value of '{name}' should be '{luatype}', got '{found}'")
+						});
+					}
+					args.push((name, Some((default, line))));
+					match self.look_back(0).kind() {
+						COMMA => true,
+						ROUND_BRACKET_CLOSED => false,
+						_ => {
+							let t = self.peek(0);
+							return Err(self.expected(")", &t.lexeme(), t.line(), t.column()));
 						}
 					}
-					notended
 				}
 				ROUND_BRACKET_CLOSED => {
-					args.push((name.lexeme(), None));
+					args.push((name, None));
 					false
 				}
 				_ => return Err(self.expected(")", &t.lexeme(), t.line(), t.column())),
-			}
+			};
+			types.push(luatype.unwrap_or(LuaType::ANY));
+			more
 		} {}
-		Ok(/* (args, types) */ args)
+		Ok((args, types))
 	}
 
-	fn build_elseif_chain(&mut self, condition: Option<Expression>) -> Result<ComplexToken, String> {
+	fn build_elseif_chain(
+		&mut self,
+		condition: Option<Expression>,
+	) -> Result<ComplexToken, Diagnostic> {
 		let condition = match condition {
 			Some(condition) => condition,
 			None => {
@@ -1498,7 +1828,7 @@ impl<'a> ParserInfo<'a> {
 				self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?
 			}
 		};
-		let code = self.build_code_block(/*self.locals.clone()*/)?;
+		let code = self.build_code_block()?;
 		Ok(IF_STATEMENT {
 			condition,
 			code,
@@ -1506,9 +1836,7 @@ impl<'a> ParserInfo<'a> {
 				let t = self.advance();
 				match t.kind() {
 					ELSEIF => Some(Box::new(self.build_elseif_chain(None)?)),
-					ELSE => Some(Box::new(DO_BLOCK(
-						self.build_code_block(/*self.locals.clone()*/)?,
-					))),
+					ELSE => Some(Box::new(DO_BLOCK(self.build_code_block()?))),
 					_ => {
 						self.current -= 1;
 						None
@@ -1518,7 +1846,7 @@ impl<'a> ParserInfo<'a> {
 		})
 	}
 
-	fn build_enums(&mut self, local: bool) -> Result<Expression, String> {
+	fn build_enums(&mut self, local: bool) -> Result<Expression, Diagnostic> {
 		self.current += 1;
 		self.assert(CURLY_BRACKET_OPEN, "{")?;
 		let mut enums = Expression::new();
@@ -1548,13 +1876,21 @@ impl<'a> ParserInfo<'a> {
 					}
 					if t.kind() != NUMBER {
 						return Err(self.error(
+							"E0006",
 							"Enums values should be a non-float number ranging from -32768 to 32767.",
 							t.line(),
 							t.column()
 						));
 					}
 					lexeme += &t.lexeme();
-					n = check!(lexeme.parse());
+					n = lexeme.parse().map_err(|_| {
+						self.error(
+							"E0006",
+							"Enums values should be a non-float number ranging from -32768 to 32767.",
+							t.line(),
+							t.column(),
+						)
+					})?;
 					self.advance_if(COMMA);
 					SYMBOL(n.to_string())
 				}
@@ -1567,65 +1903,457 @@ impl<'a> ParserInfo<'a> {
 				values: vec![vec_deque![value]],
 			});
 		}
-		/*if let Some(locals) = &mut self.locals {
-			for r#enum in &enums {
-				if let VARIABLE { names, .. } = r#enum {
-					locals.insert(names[0].clone(), LuaType::NUMBER);
-				}
+		for r#enum in &enums {
+			if let VARIABLE { names, .. } = r#enum {
+				self.add_variable(names[0].clone(), LuaType::NUMBER);
 			}
-		}*/
+		}
 		Ok(enums)
 	}
 
-	fn build_function(&mut self, local: bool) -> Result<ComplexToken, String> {
+	/// Builds a struct, made of its constructor and the metatable of its values (if it has one).
+	/// The constructor takes the fields in the order they are declared
+	/// and uses the default value of the ones that are `nil`.
+	/// The metatable is declared first but built after the constructor, so metamethods can use it.
+	/// A struct with a parent, a `constructor` or methods is a class instead, see [`Self::build_class`]
+	fn build_struct(&mut self, local: bool) -> Result<Expression, Diagnostic> {
 		self.current += 1;
 		let t = self.assert_advance(IDENTIFIER, "<name>")?;
-		let name = vec_deque![SYMBOL(t.lexeme())];
-		self.assert(ROUND_BRACKET_OPEN, "(")?;
-		let /*(*/args/*, types)*/ = if !self.advance_if(ROUND_BRACKET_CLOSED) {
-			self.build_function_args()?
+		let (name, line) = (t.lexeme(), t.line());
+		let parent = if self.advance_if(COLON) {
+			let t = self.assert_advance(IDENTIFIER, "<name>")?;
+			let parent = t.lexeme();
+			if self.structs.contains(&parent) {
+				return Err(self.error(
+					"E0011",
+					format!("'{parent}' is a struct, not a class, so it cannot be the parent of '{name}'"),
+					t.line(),
+					t.column(),
+				)
+				.with_help(format!("give '{parent}' a constructor or a method to make it a class")));
+			}
+			Some(parent)
 		} else {
-			/*(*/FunctionArgs::new()//, None)
+			None
 		};
-		let code = self.build_function_block(/*types*/)?;
-		/*if self.locals.is_some() {
-			self.add_variable(t.lexeme(), LuaType::NIL);
-		}*/
-		Ok(FUNCTION {
+		self.assert(CURLY_BRACKET_OPEN, "{")?;
+		let mut fields = FunctionArgs::new();
+		let mut types = Vec::new();
+		let mut metas: Vec<(Option<Expression>, Expression, usize)> = Vec::new();
+		let mut metatable: Option<(String, usize, usize)> = None;
+		let mut constructor: Option<(ArgsAndTypes, CodeBlock)> = None;
+		let mut methods = Expression::new();
+		while !self.advance_if(CURLY_BRACKET_CLOSED) {
+			let t = self.advance();
+			match t.kind() {
+				IDENTIFIER => {
+					let field = t.lexeme();
+					let luatype = self.build_type()?;
+					let default = if self.advance_if(DEFINE) {
+						let default = self.build_expression(None)?;
+						self.current -= 1;
+						if let Some(luatype) = &luatype {
+							self.check_type(luatype, &default, t.line(), t.column(), |found| {
+								format!("The default value of '{field}' should be '{luatype}', got '{found}'")
+							});
+						}
+						Some((default, t.line()))
+					} else {
+						None
+					};
+					let luatype = luatype.unwrap_or(LuaType::ANY);
+					types.push(match default {
+						Some(_) => luatype.optional(),
+						None => luatype,
+					});
+					fields.push((field, default));
+				}
+				META if self.advance_if(WITH) => {
+					if !metas.is_empty() {
+						return Err(self.error(
+							"E0005",
+							"An external metatable cannot be used if the struct already set its own metamethods",
+							t.line(),
+							t.column(),
+						));
+					}
+					let metatable_name = self.assert_advance(IDENTIFIER, "<name>")?.lexeme();
+					metatable = Some((metatable_name, t.line(), t.column()));
+				}
+				META => {
+					if metatable.is_some() {
+						return Err(self.error(
+							"E0005",
+							"Metamethods cannot be set if the struct already uses an external metatable",
+							t.line(),
+							t.column(),
+						));
+					}
+					let meta = vec_deque![SYMBOL(String::from(self.build_meta_name()?))];
+					self.assert(DEFINE, "=")?;
+					let value = self.build_expression(None)?;
+					self.current -= 1;
+					metas.push((Some(meta), value, t.line()));
+				}
+				METHOD | FN => {
+					let method = self.assert_advance(IDENTIFIER, "<name>")?.lexeme();
+					self.assert(ROUND_BRACKET_OPEN, "(")?;
+					let separator = if t.kind() == METHOD { ":" } else { "." };
+					let method = vec_deque![
+						SYMBOL(name.clone()),
+						SYMBOL(String::from(separator)),
+						SYMBOL(method)
+					];
+					methods.push_back(self.build_method(method)?);
+				}
+				CONSTRUCTOR => {
+					if constructor.is_some() {
+						return Err(self.error(
+							"E0011",
+							format!("'{name}' already has a constructor"),
+							t.line(),
+							t.column(),
+						));
+					}
+					self.assert(ROUND_BRACKET_OPEN, "(")?;
+					let args = self.build_args_after_bracket()?;
+					if self.peek(0).kind() == COLON {
+						let t = self.peek(0);
+						return Err(self.error(
+							"E0011",
+							"A constructor cannot declare its return type",
+							t.line(),
+							t.column(),
+						));
+					}
+					let types = args.1.clone();
+					let (args, code) = self.build_function_block(args, None)?;
+					constructor = Some(((args, types), code));
+				}
+				_ => return Err(self.expected("}", &t.lexeme(), t.line(), t.column())),
+			}
+			self.advance_if(COMMA);
+		}
+		let end = self.look_back(0).line();
+		if parent.is_some() || constructor.is_some() || !methods.is_empty() {
+			if let Some((_, line, column)) = metatable {
+				return Err(self.error(
+					"E0011",
+					"A class cannot use an external metatable",
+					line,
+					column,
+				)
+				.with_help("classes are the metatable of their objects, set their metamethods with 'meta'"));
+			}
+			let class = Class {
+				name,
+				local,
+				parent,
+				fields,
+				types,
+				metas,
+				constructor,
+				methods,
+			};
+			self.structs.remove(&class.name);
+			return Ok(self.build_class(class, line, end));
+		}
+		self.structs.insert(name.clone());
+		let mut metatable = metatable.map(|(name, ..)| name);
+		let mut result = Expression::with_capacity(3);
+		let metas = if metas.is_empty() {
+			None
+		} else {
+			let internal = self.get_next_internal_var();
+			result.push_back(VARIABLE {
+				local: true,
+				names: vec![internal.clone()],
+				values: Vec::new(),
+				line,
+			});
+			metatable = Some(internal.clone());
+			Some(ALTER {
+				kind: DEFINE,
+				names: vec_deque![vec_deque![SYMBOL(internal)]],
+				values: vec![vec_deque![TABLE {
+					values: metas,
+					metas: Vec::new(),
+					metatable: None,
+				}]],
+				line,
+			})
+		};
+		let values = fields
+			.iter()
+			.map(|(field, _)| {
+				let field = vec_deque![SYMBOL(field.clone())];
+				(Some(field.clone()), field, line)
+			})
+			.collect();
+		let signature = FunctionType {
+			args: types,
+			variadic: false,
+			returns: LuaType::TABLE,
+		};
+		self.add_variable(name.clone(), LuaType::FUNCTION(Some(Box::new(signature))));
+		result.push_back(FUNCTION {
+			local,
+			name: vec_deque![SYMBOL(name)],
+			args: fields,
+			code: CodeBlock {
+				start: line,
+				code: vec_deque![RETURN_EXPR(Some(vec![vec_deque![TABLE {
+					values,
+					metas: Vec::new(),
+					metatable,
+				}]]))],
+				end,
+			},
+		});
+		result.extend(metas);
+		Ok(result)
+	}
+
+	/// Builds a class, a table that is the metatable of its objects and indexes itself.
+	/// Its objects are created with `Class.new(...)` (or `Class(...)`, like a struct),
+	/// which calls `self:init(...)` on a new object:
+	/// `init` is the `constructor` (after setting the fields with a default value),
+	/// calls the `init` of the parent if the class has one but no constructor
+	/// or else takes the fields in the order they are declared, like a struct.
+	/// The parent is used as the `__index` of the class, so its methods are inherited
+	fn build_class(&mut self, class: Class, line: usize, end: usize) -> Expression {
+		let Class {
+			name,
+			local,
+			parent,
+			fields,
+			types,
+			metas,
+			constructor,
+			methods,
+		} = class;
+		let index = |table: &str, key: &str| {
+			vec_deque![
+				SYMBOL(table.to_owned()),
+				SYMBOL(String::from(".")),
+				SYMBOL(key.to_owned())
+			]
+		};
+		let assign = |name: Expression, value: Expression, line: usize| ALTER {
+			kind: DEFINE,
+			names: vec_deque![name],
+			values: vec![value],
+			line,
+		};
+		let mut result = Expression::with_capacity(methods.len() + 4);
+		// the class is the first argument of `__call`, a local class is not visible yet in its own declaration
+		let call = LAMBDA {
+			args: vec![(name.clone(), None), (String::from("..."), None)],
+			code: CodeBlock {
+				start: line,
+				code: vec_deque![RETURN_EXPR(Some(vec![vec_deque![IDENT {
+					expr: vec_deque![
+						SYMBOL(name.clone()),
+						SYMBOL(String::from(".")),
+						SYMBOL(String::from("new")),
+						CALL(vec![vec_deque![SYMBOL(String::from("..."))]])
+					],
+					line,
+				}]]))],
+				end: line,
+			},
+		};
+		let mut class_metas: Vec<_> = parent
+			.iter()
+			.map(|parent| {
+				(
+					String::from("__index"),
+					vec_deque![SYMBOL(parent.clone())],
+					line,
+				)
+			})
+			.collect();
+		class_metas.push((String::from("__call"), vec_deque![call], line));
+		result.push_back(VARIABLE {
 			local,
+			names: vec![name.clone()],
+			values: vec![vec_deque![TABLE {
+				values: Vec::new(),
+				metas: class_metas,
+				metatable: None,
+			}]],
+			line,
+		});
+		result.push_back(assign(
+			index(&name, "__index"),
+			vec_deque![SYMBOL(name.clone())],
+			line,
+		));
+		for (meta, value, line) in metas {
+			let Some(SYMBOL(meta)) = meta.and_then(|mut meta| meta.pop_front()) else {
+				unreachable!()
+			};
+			result.push_back(assign(index(&name, &meta), value, line));
+		}
+		let defaults = fields.iter().filter_map(|(field, default)| {
+			let (default, line) = default.clone()?;
+			Some(assign(index("self", field), default, line))
+		});
+		let (args, signature, code) = match (constructor, &parent) {
+			(Some(((args, types), mut code)), _) => {
+				let signature = args
+					.iter()
+					.zip(types)
+					.filter(|((name, _), _)| name != "...")
+					.map(|((_, default), luatype)| match default {
+						Some(_) => luatype.optional(),
+						None => luatype,
+					})
+					.collect();
+				let variadic = args.iter().any(|(name, _)| name == "...");
+				for default in defaults.rev() {
+					code.code.push_front(default);
+				}
+				(args, Some((signature, variadic)), code)
+			}
+			(None, Some(parent)) => {
+				let mut code: Expression = vec_deque![IDENT {
+					expr: vec_deque![
+						SYMBOL(parent.clone()),
+						SYMBOL(String::from(".")),
+						SYMBOL(String::from("init")),
+						CALL(vec![
+							vec_deque![SYMBOL(String::from("self"))],
+							vec_deque![SYMBOL(String::from("..."))]
+						])
+					],
+					line,
+				}];
+				code.extend(defaults);
+				let code = CodeBlock {
+					start: line,
+					code,
+					end,
+				};
+				(vec![(String::from("..."), None)], None, code)
+			}
+			(None, None) => {
+				let code = fields
+					.iter()
+					.map(|(field, _)| {
+						assign(
+							index("self", field),
+							vec_deque![SYMBOL(field.clone())],
+							line,
+						)
+					})
+					.collect();
+				let code = CodeBlock {
+					start: line,
+					code,
+					end,
+				};
+				(fields, Some((types, false)), code)
+			}
+		};
+		let signature = match signature {
+			Some((args, variadic)) => LuaType::FUNCTION(Some(Box::new(FunctionType {
+				args,
+				variadic,
+				returns: LuaType::TABLE,
+			}))),
+			None => parent
+				.and_then(|parent| self.lookup_type(&format!("{parent}.new")))
+				.unwrap_or(LuaType::FUNCTION(None)),
+		};
+		self.add_variable(name.clone(), LuaType::TABLE);
+		self.add_variable(format!("{name}.new"), signature);
+		result.push_back(FUNCTION {
+			local: false,
+			name: vec_deque![
+				SYMBOL(name.clone()),
+				SYMBOL(String::from(":")),
+				SYMBOL(String::from("init"))
+			],
+			args,
+			code,
+		});
+		let object = vec_deque![SYMBOL(String::from("self"))];
+		result.push_back(FUNCTION {
+			local: false,
+			name: index(&name, "new"),
+			args: vec![(String::from("..."), None)],
+			code: CodeBlock {
+				start: line,
+				code: vec_deque![
+					VARIABLE {
+						local: true,
+						names: vec![String::from("self")],
+						values: vec![vec_deque![SYMBOL(format!("setmetatable({{}}, {name})"))]],
+						line,
+					},
+					IDENT {
+						expr: vec_deque![
+							SYMBOL(String::from("self")),
+							SYMBOL(String::from(":")),
+							SYMBOL(String::from("init")),
+							CALL(vec![vec_deque![SYMBOL(String::from("..."))]])
+						],
+						line,
+					},
+					RETURN_EXPR(Some(vec![object]))
+				],
+				end,
+			},
+		});
+		result.extend(methods);
+		result
+	}
+
+	/// Builds a method whose name was already read, starting after its `(`
+	fn build_method(&mut self, name: Expression) -> Result<ComplexToken, Diagnostic> {
+		let args = self.build_args_after_bracket()?;
+		let (signature, returns) = self.build_function_signature(&args)?;
+		if let Some(path) = expression_path(name.iter()) {
+			self.add_variable(path, LuaType::FUNCTION(Some(Box::new(signature))));
+		}
+		let (args, code) = self.build_function_block(args, returns)?;
+		Ok(FUNCTION {
+			local: false,
 			name,
 			args,
 			code,
 		})
 	}
-	/*
-		fn add_variable(&mut self, name: String, luatype: LuaType) {
-			if let Some(locals) = &mut self.locals {
-				locals.insert(name, luatype);
-			}
-		}
 
-		fn build_type(&mut self) -> Result<LuaType, String> {
-			if self.advance_if(COLON) {
-				Ok(LuaType::ANY) //PLACEHOLDER
-			} else {
-				Ok(LuaType::NIL)
-			}
+	fn build_args_after_bracket(&mut self) -> Result<ArgsAndTypes, Diagnostic> {
+		if !self.advance_if(ROUND_BRACKET_CLOSED) {
+			self.build_function_args()
+		} else {
+			Ok((FunctionArgs::new(), Vec::new()))
 		}
-	*/
-	/*fn build_variable(&mut self) -> Result</*(*/ String /*, LuaType)*/, String> {
-		let name = self.assert_advance(IDENTIFIER, "<name>")?.lexeme();
-		/*if self.locals.is_some() {
-			let luatype = self.build_type()?;
-			self.add_variable(name.to_string(), luatype.clone());
-			Ok((name, luatype))
-		} else {*/
-		Ok(/*(*/ name /*, LuaType::ANY)*/)
-		//}
-	}*/
+	}
 
+	fn build_function(&mut self, local: bool) -> Result<ComplexToken, Diagnostic> {
+		self.current += 1;
+		let t = self.assert_advance(IDENTIFIER, "<name>")?;
+		let name = vec_deque![SYMBOL(t.lexeme())];
+		self.assert(ROUND_BRACKET_OPEN, "(")?;
+		let args = self.build_args_after_bracket()?;
+		let (signature, returns) = self.build_function_signature(&args)?;
+		self.add_variable(t.lexeme(), LuaType::FUNCTION(Some(Box::new(signature))));
+		let (args, code) = self.build_function_block(args, returns)?;
+		Ok(FUNCTION {
+			local,
+			name,
+			args,
+			code,
+		})
+	}
 	#[allow(clippy::type_complexity)]
-	fn build_destructure_table(&mut self) -> Result<(Vec<String>, Vec<String>, Vec<String>), String> {
+	fn build_destructure_table(
+		&mut self,
+	) -> Result<(Vec<String>, Vec<String>, Vec<String>), Diagnostic> {
 		let mut names = Vec::new();
 		let mut key_names = Vec::new();
 		let name = self.get_next_internal_var();
@@ -1645,7 +2373,7 @@ impl<'a> ParserInfo<'a> {
 		key_names: &mut Vec<String>,
 		internal_names: &mut Vec<String>,
 		key_start: String,
-	) -> Result<(), String> {
+	) -> Result<(), Diagnostic> {
 		loop {
 			let t = self.assert_advance(IDENTIFIER, "<name>")?;
 			names.push(if self.advance_if(ARROW) {
@@ -1708,12 +2436,16 @@ impl<'a> ParserInfo<'a> {
 		local: bool,
 		line: usize,
 		destructure: bool,
-	) -> Result<ComplexToken, String> {
-		let (names, destructure) = if destructure {
+	) -> Result<ComplexToken, Diagnostic> {
+		let (typed_names, destructure) = if destructure {
 			let (names, key_names, internal_names) = self.build_destructure_table()?;
+			let names = names
+				.into_iter()
+				.map(|name| (name, None, line, 0))
+				.collect();
 			(names, Some((key_names, internal_names)))
 		} else {
-			(self.build_identifier_list()?, None)
+			(self.build_typed_identifier_list()?, None)
 		};
 		let check = self.advance().kind();
 		let mut values: Vec<Expression> = if check != DEFINE {
@@ -1723,11 +2455,10 @@ impl<'a> ParserInfo<'a> {
 			if local {
 				Vec::new()
 			} else {
-				/*if let Some(_locals) = &self.locals {
-					//println!("{:?}", locals);
-				} else {
-					self.warning("Defining external globals will not do anything if you don't have type checking enabled!", line)
-				}*/
+				// a global without a value only declares its type
+				for (name, luatype, ..) in typed_names {
+					self.add_variable(name, luatype.unwrap_or(LuaType::ANY));
+				}
 				self.current -= 1;
 				return Ok(SYMBOL(String::new()));
 			}
@@ -1735,6 +2466,31 @@ impl<'a> ParserInfo<'a> {
 			self.find_expressions(None)?
 		};
 		self.current -= 1;
+		let mut names = Vec::with_capacity(typed_names.len());
+		for (i, (name, luatype, line, column)) in typed_names.into_iter().enumerate() {
+			let value = values.get(i).filter(|_| destructure.is_none());
+			let luatype = match (luatype, value) {
+				(Some(luatype), Some(value)) => {
+					self.check_type(&luatype, value, line, column, |found| {
+						format!("'{name}' is declared as '{luatype}' but its value is '{found}'")
+					});
+					luatype
+				}
+				(Some(luatype), None) => luatype,
+				(None, Some(value)) => {
+					let luatype = match self.infer_type(value) {
+						LuaType::NIL => LuaType::ANY,
+						luatype => luatype,
+					};
+					self.add_inferred_variable(name.clone(), luatype);
+					names.push(name);
+					continue;
+				}
+				(None, None) => LuaType::ANY,
+			};
+			self.add_variable(name.clone(), luatype);
+			names.push(name);
+		}
 		if let Some((key_names, internal_names)) = destructure {
 			self.build_table_destructuring(internal_names, values, line);
 			values = Vec::new();
@@ -1750,7 +2506,7 @@ impl<'a> ParserInfo<'a> {
 		})
 	}
 
-	fn compile_static(&mut self, expr: Expression) -> Result<(), String> {
+	fn compile_static(&mut self, expr: Expression) -> Result<(), Diagnostic> {
 		let code = self.compiler.compile_tokens(0, expr)?;
 		self.statics += &(code + "\n");
 		Ok(())
@@ -1759,8 +2515,8 @@ impl<'a> ParserInfo<'a> {
 	fn build_match_block(
 		&mut self,
 		name: String,
-		func: &impl Fn(&mut ParserInfo<'a> /* , LocalsList */) -> Result<CodeBlock, String>,
-	) -> Result<ComplexToken, String> {
+		func: &impl Fn(&mut ParserInfo<'a>) -> Result<CodeBlock, Diagnostic>,
+	) -> Result<ComplexToken, Diagnostic> {
 		let line = self.peek(0).line();
 		let value = self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?;
 		let mut branches: Vec<MatchCase> = Vec::new();
@@ -1771,25 +2527,20 @@ impl<'a> ParserInfo<'a> {
 					ARROW => {
 						if branches.is_empty() {
 							return Err(self.error(
+								"E0007",
 								"The default case (with no extra if) of a match block must be the last case, not the first",
 								t.line(),
 								t.column()
 							));
 						}
-						branches.push((Vec::new(), Expression::new(), None, func(self /* , self.locals.clone() */)?));
+						branches.push((Vec::new(), Expression::new(), None, func(self)?));
 						self.assert(CURLY_BRACKET_CLOSED, "}")?;
 						false
 					}
 					IF => {
-						let (extra_if, internal_expr) = self.use_internal_stack(|i|
-							i.build_expression(Some((ARROW, "=>")))
-						)?;
-						branches.push((
-							Vec::new(),
-							internal_expr,
-							Some(extra_if),
-							func(self /* , self.locals.clone() */)?,
-						));
+						let (extra_if, internal_expr) =
+							self.use_internal_stack(|i| i.build_expression(Some((ARROW, "=>"))))?;
+						branches.push((Vec::new(), internal_expr, Some(extra_if), func(self)?));
 						!self.advance_if(CURLY_BRACKET_CLOSED)
 					}
 					_ => return Err(self.expected("=>", &t.lexeme(), t.line(), t.column())),
@@ -1819,12 +2570,7 @@ impl<'a> ParserInfo<'a> {
 				if !current.is_empty() {
 					conditions.push(current);
 				}
-				branches.push((
-					conditions,
-					internal_expr,
-					extra_if,
-					func(self /* , self.locals.clone() */)?
-				));
+				branches.push((conditions, internal_expr, extra_if, func(self)?));
 				!self.advance_if(CURLY_BRACKET_CLOSED)
 			}
 		} {}
@@ -1840,7 +2586,7 @@ impl<'a> ParserInfo<'a> {
 		&mut self,
 		code: Option<CodeBlock>,
 		end: OptionalEnd
-	) -> Result<(Expression, CodeBlock), String> {
+	) -> Result<(Expression, CodeBlock), Diagnostic> {
 		let start = self.peek(0).line();
 		let (condition, mut internal_code) = self.use_internal_stack(|i| i.build_expression(end))?;
 		let end = self.look_back(1).line();
@@ -1868,7 +2614,7 @@ impl<'a> ParserInfo<'a> {
 		mut expr: Expression,
 		target_expr: &mut Expression,
 		strict: bool,
-	) -> Result<(), String> {
+	) -> Result<(), Diagnostic> {
 		let line = self.peek(0).line();
 		self.current += 1;
 		let safe_indexing = self.build_identifier_internal(&mut expr)?;
@@ -1897,7 +2643,7 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_local_global(&mut self, t: &BorrowedToken) -> Result<(), String> {
+	fn parse_token_local_global(&mut self, t: &BorrowedToken) -> Result<(), Diagnostic> {
 		let local = t.kind() == LOCAL;
 		match self.peek(0).kind() {
 			FN => {
@@ -1908,6 +2654,10 @@ impl<'a> ParserInfo<'a> {
 				let enums = &mut self.build_enums(local)?;
 				self.expr.append(enums);
 			}
+			STRUCT => {
+				let r#struct = &mut self.build_struct(local)?;
+				self.expr.append(r#struct);
+			}
 			_ => {
 				let destructure = self.advance_if(CURLY_BRACKET_OPEN);
 				let vars = self.build_variables(local, t.line(), destructure)?;
@@ -1917,7 +2667,7 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_static(&mut self, t: &BorrowedToken) -> Result<(), String> {
+	fn parse_token_static(&mut self, t: &BorrowedToken) -> Result<(), Diagnostic> {
 		match self.peek(0).kind() {
 			FN => {
 				let function = vec_deque![self.build_function(true)?];
@@ -1927,6 +2677,10 @@ impl<'a> ParserInfo<'a> {
 				let enums = self.build_enums(true)?;
 				self.compile_static(enums)?;
 			}
+			STRUCT => {
+				let r#struct = self.build_struct(true)?;
+				self.compile_static(r#struct)?;
+			}
 			_ => {
 				let vars = vec_deque![self.build_variables(true, t.line(), false)?];
 				self.compile_static(vars)?;
@@ -1935,7 +2689,29 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_method(&mut self) -> Result<(), String> {
+	/// Declares globals that are given by the host environment, they are not compiled
+	/// but their types are used by the type checker and their names by the linter
+	fn parse_token_extern(&mut self) -> Result<(), Diagnostic> {
+		if self.advance_if(FN) {
+			let mut path = self.assert_advance(IDENTIFIER, "<name>")?.lexeme();
+			while self.advance_if(DOT) {
+				path.push('.');
+				path += &self.assert_advance(IDENTIFIER, "<name>")?.lexeme();
+			}
+			self.assert(ROUND_BRACKET_OPEN, "(")?;
+			let args = self.build_args_after_bracket()?;
+			let (signature, _) = self.build_function_signature(&args)?;
+			self.add_variable(path, LuaType::FUNCTION(Some(Box::new(signature))));
+		} else {
+			for (name, luatype, ..) in self.build_typed_identifier_list()? {
+				self.add_variable(name, luatype.unwrap_or(LuaType::ANY));
+			}
+		}
+		self.advance_if(SEMICOLON);
+		Ok(())
+	}
+
+	fn parse_token_method(&mut self) -> Result<(), Diagnostic> {
 		let name = {
 			let mut expr = Expression::with_capacity(4);
 			loop {
@@ -1962,23 +2738,12 @@ impl<'a> ParserInfo<'a> {
 			}
 			expr
 		};
-		let /*(*/args/*, types)*/ = if !self.advance_if(ROUND_BRACKET_CLOSED) {
-			self.build_function_args()?
-		} else {
-			/*(*/FunctionArgs::new()//, None)
-		};
-		let code = self.build_function_block(/*types*/)?;
-		//ADD FUNCTION FOR ADDING VALUES INSIDE TABLES MAYBE?
-		self.expr.push_back(FUNCTION {
-			local: false,
-			name,
-			args,
-			code,
-		});
+		let method = self.build_method(name)?;
+		self.expr.push_back(method);
 		Ok(())
 	}
 
-	fn parse_token_identifier(&mut self, t: &BorrowedToken) -> Result<(), String> {
+	fn parse_token_identifier(&mut self, t: &BorrowedToken) -> Result<(), Diagnostic> {
 		let start = self.current - 1;
 		let mut first_expr = Expression::with_capacity(8);
 		let safe_indexing = self.build_identifier_internal(&mut first_expr)?;
@@ -2018,6 +2783,7 @@ impl<'a> ParserInfo<'a> {
 			return Ok(());
 		} else if safe_indexing {
 			return Err(self.error(
+				"E0008",
 				"Safe indexing cannot be used when altering variables",
 				t.line(),
 				t.column(),
@@ -2037,6 +2803,9 @@ impl<'a> ParserInfo<'a> {
 			return Err(self.expected("=", &checkt.lexeme(), checkt.line(), checkt.column()));
 		}
 		let values = self.find_expressions(None)?;
+		if matches!(check, DEFINE | DEFINE_COALESCE) {
+			self.check_assignments(&names, &values, t.line(), t.column());
+		}
 		if check == DEFINE_COALESCE {
 			for value in values {
 				if let Some(name) = names.pop_front() {
@@ -2072,7 +2841,41 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_round_bracket_open(&mut self) -> Result<(), String> {
+	/// Checks the values assigned to names with a declared type and widens the inferred ones
+	fn check_assignments(
+		&mut self,
+		names: &VecDeque<Expression>,
+		values: &[Expression],
+		line: usize,
+		column: usize,
+	) {
+		if self.locals.is_none() {
+			return;
+		}
+		for (name, value) in names.iter().zip(values) {
+			let Some(path) = expression_path(name.iter()) else {
+				continue;
+			};
+			let found = self.infer_type(value);
+			let luatype = match self.locals.as_mut().unwrap().get_mut(&path) {
+				Some((luatype, false)) => {
+					*luatype = LuaType::union(vec![luatype.clone(), found]);
+					continue;
+				}
+				Some((luatype, true)) => luatype.clone(),
+				None => match self.lookup_type(&path) {
+					Some(luatype) => luatype,
+					None => continue,
+				},
+			};
+			if !luatype.accepts(&found) {
+				let message = format!("Cannot assign '{found}' to '{path}', which is '{luatype}'");
+				self.type_mismatch("T0001", message, line, column);
+			}
+		}
+	}
+
+	fn parse_token_round_bracket_open(&mut self) -> Result<(), Diagnostic> {
 		let expr = vec_deque![EXPR(self.build_expression(Some((ROUND_BRACKET_CLOSED, ")")))?)];
 		let mut target_expr = Expression::with_capacity(2);
 		self.build_round_brackets(expr, &mut target_expr, true)?;
@@ -2081,33 +2884,33 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_curly_bracket_open(&mut self) -> Result<(), String> {
+	fn parse_token_curly_bracket_open(&mut self) -> Result<(), Diagnostic> {
 		self.current -= 1;
-		let block = self.build_code_block(/*self.locals.clone()*/)?;
+		let block = self.build_code_block()?;
 		self.expr.push_back(DO_BLOCK(block));
 		Ok(())
 	}
 
-	fn parse_token_if(&mut self) -> Result<(), String> {
+	fn parse_token_if(&mut self) -> Result<(), Diagnostic> {
 		let ctoken = self.build_elseif_chain(None)?;
 		self.expr.push_back(ctoken);
 		Ok(())
 	}
 
-	fn parse_token_match(&mut self) -> Result<(), String> {
+	fn parse_token_match(&mut self) -> Result<(), Diagnostic> {
 		let name = self.get_next_internal_var();
 		let ctoken = self.build_match_block(name, &ParserInfo::build_code_block)?;
 		self.expr.push_back(ctoken);
 		Ok(())
 	}
 
-	fn parse_token_while(&mut self, line: usize) -> Result<(), String> {
+	fn parse_token_while(&mut self, line: usize) -> Result<(), Diagnostic> {
 		let (condition, code) = self.build_loop(None, Some((CURLY_BRACKET_OPEN, "{")))?;
 		self.expr.push_back(WHILE_LOOP { condition, code, line });
 		Ok(())
 	}
 
-	fn parse_token_until(&mut self, line: usize) -> Result<(), String> {
+	fn parse_token_until(&mut self, line: usize) -> Result<(), Diagnostic> {
 		let (mut condition, code) = self.build_loop(None, Some((CURLY_BRACKET_OPEN, "{")))?;
 		condition.push_front(SYMBOL(String::from("not (")));
 		condition.push_back(SYMBOL(String::from(")")));
@@ -2115,7 +2918,7 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_loop(&mut self, line: usize) -> Result<(), String> {
+	fn parse_token_loop(&mut self, line: usize) -> Result<(), Diagnostic> {
 		let code = self.build_loop_block()?;
 		let t = self.advance();
 		match t.kind() {
@@ -2139,7 +2942,7 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_for(&mut self, line: usize) -> Result<(), String> {
+	fn parse_token_for(&mut self, line: usize) -> Result<(), Diagnostic> {
 		if self.peek(1).kind() == DEFINE {
 			let iterator = self.assert_advance(IDENTIFIER, "<name>")?.lexeme();
 			self.current += 1;
@@ -2155,7 +2958,8 @@ impl<'a> ParserInfo<'a> {
 				COMMA => self.build_expression(Some((CURLY_BRACKET_OPEN, "{")))?,
 				_ => return Err(self.expected(",", &t.lexeme(), t.line(), t.column())),
 			};
-			let code = self.build_loop_block()?;
+			let names = [(iterator.clone(), LuaType::NUMBER)];
+			let code = self.with_locals(names, Self::build_loop_block)?;
 			self.expr.push_back(FOR_LOOP {
 				iterator,
 				start,
@@ -2190,7 +2994,8 @@ impl<'a> ParserInfo<'a> {
 					));
 				}
 			};
-			let code = self.build_loop_block()?;
+			let names = iterators.iter().map(|name| (name.clone(), LuaType::ANY));
+			let code = self.with_locals(names, Self::build_loop_block)?;
 			self.expr.push_back(FOR_FUNC_LOOP {
 				iterators,
 				expr,
@@ -2201,24 +3006,36 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_continue(&mut self) -> Result<(), String> {
+	fn parse_token_continue(&mut self) -> Result<(), Diagnostic> {
 		self.expr.push_back(CONTINUE_LOOP);
 		self.advance_if(SEMICOLON);
 		Ok(())
 	}
 
-	fn parse_token_break(&mut self) -> Result<(), String> {
+	fn parse_token_break(&mut self) -> Result<(), Diagnostic> {
 		self.expr.push_back(BREAK_LOOP);
 		self.advance_if(SEMICOLON);
 		Ok(())
 	}
 
-	fn parse_token_return(&mut self) -> Result<(), String> {
+	fn parse_token_return(&mut self) -> Result<(), Diagnostic> {
+		let t = self.look_back(0);
+		let (line, column) = (t.line(), t.column());
 		let exprs = if self.ended() || self.advance_if(SEMICOLON) {
 			None
 		} else {
 			Some(self.find_expressions(None)?)
 		};
+		if let Some(returns) = self.returns.clone() {
+			let nil = vec_deque![SYMBOL(String::from("nil"))];
+			let value = exprs
+				.as_ref()
+				.and_then(|exprs| exprs.first())
+				.unwrap_or(&nil);
+			self.check_type(&returns, value, line, column, |found| {
+				format!("This function should return '{returns}', got '{found}'")
+			});
+		}
 		self.expr.push_back(RETURN_EXPR(exprs));
 		if !self.ended() {
 			let t = self.look_back(0);
@@ -2227,8 +3044,8 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_try(&mut self) -> Result<(), String> {
-		let totry = self.build_code_block(/*self.locals.clone()*/)?;
+	fn parse_token_try(&mut self) -> Result<(), Diagnostic> {
+		let totry = self.build_code_block()?;
 		let error: Option<String>;
 		let catch = if self.advance_if(CATCH) {
 			let t = self.advance();
@@ -2238,7 +3055,8 @@ impl<'a> ParserInfo<'a> {
 				error = None;
 				self.current -= 1;
 			}
-			Some(self.build_code_block(/*self.locals.clone()*/)?)
+			let names = error.iter().map(|name| (name.clone(), LuaType::ANY));
+			Some(self.with_locals(names, Self::build_code_block)?)
 		} else {
 			error = None;
 			None
@@ -2251,8 +3069,9 @@ impl<'a> ParserInfo<'a> {
 		Ok(())
 	}
 
-	fn parse_token_fn_enum(&mut self, t: &BorrowedToken) -> Result<(), String> {
+	fn parse_token_fn_enum(&mut self, t: &BorrowedToken) -> Result<(), Diagnostic> {
 		Err(self.error(
+			"E0009",
 			format!(
 				"'{}' must have 'local', 'global' or 'static' beforehand",
 				t.lexeme()
@@ -2263,18 +3082,76 @@ impl<'a> ParserInfo<'a> {
 	}
 }
 
+fn parse_block(
+	tokens: Vec<Token>,
+	locals: LocalsList,
+	returns: Option<LuaType>,
+	structs: AHashSet<String>,
+	filename: &String,
+	options: &Options,
+) -> Result<(Expression, String, Vec<Diagnostic>), Vec<Diagnostic>> {
+	let mut i = ParserInfo::new(tokens, locals, returns, structs, filename, options);
+	while !i.ended() {
+		let t = i.advance();
+		if let Err(error) = match t.kind() {
+			LOCAL | GLOBAL => i.parse_token_local_global(&t),
+			STATIC => i.parse_token_static(&t),
+			METHOD => i.parse_token_method(),
+			EXTERN => i.parse_token_extern(),
+			IDENTIFIER => i.parse_token_identifier(&t),
+			ROUND_BRACKET_OPEN => i.parse_token_round_bracket_open(),
+			CURLY_BRACKET_OPEN => i.parse_token_curly_bracket_open(),
+			IF => i.parse_token_if(),
+			MATCH => i.parse_token_match(),
+			WHILE => i.parse_token_while(t.line()),
+			UNTIL => i.parse_token_until(t.line()),
+			LOOP => i.parse_token_loop(t.line()),
+			FOR => i.parse_token_for(t.line()),
+			CONTINUE => i.parse_token_continue(),
+			BREAK => i.parse_token_break(),
+			RETURN => i.parse_token_return(),
+			TRY => i.parse_token_try(),
+			FN | ENUM | STRUCT => i.parse_token_fn_enum(&t),
+			SEMICOLON => Ok(()),
+			EOF => break,
+			_ => Err(i.expected("<end>", &t.lexeme(), t.line(), t.column())),
+		} {
+			i.errors.push(error);
+			i.recover();
+		}
+	}
+
+	if !i.errors.is_empty() {
+		i.errors
+			.sort_by_key(|error| error.span.map(|span| (span.line, span.column)));
+		return Err(i.errors);
+	}
+
+	Ok((
+		i.expr,
+		if !i.statics.is_empty() && options.env_debug {
+			format!("--statics defined in \"{}\":\n{}\n", i.filename, i.statics)
+		} else {
+			i.statics
+		},
+		i.warnings,
+	))
+}
+
 /// Parses a list of tokens into an expression
 /// Takes a list of [`Token`]s, a filename, and [`Options`]
 /// Returns an expression and statics as a string
+/// The warnings of the type checker are discarded, use [`parse_tokens_with_warnings`] to get them
 ///
 /// # Errors
-/// Returns an [`Err`] containing the error message if an unexpected [`Token`] is found.
+/// Returns an [`Err`] containing every error found if an unexpected [`Token`] is found.
+/// After an error the parser skips to the next statement, so a single run reports all of them.
 ///
 /// # Examples
 /// ```
-/// use clue_core::{env::Options, parser::*, preprocessor::*, scanner::*};
+/// use clue_core::{diagnostic::Diagnostic, env::Options, parser::*, preprocessor::*, scanner::*};
 ///
-/// fn main() -> Result<(), String> {
+/// fn main() -> Result<(), Vec<Diagnostic>> {
 ///     let options = Options::default();
 ///     let filename = String::from("fizzbuzz.clue");
 ///     let mut code = include_str!("../../examples/fizzbuzz.clue").to_owned();
@@ -2295,45 +3172,196 @@ impl<'a> ParserInfo<'a> {
 /// ```
 pub fn parse_tokens(
 	tokens: Vec<Token>,
-	//locals: Option<AHashMap<String, LuaType>>,
 	filename: &String,
 	options: &Options,
-) -> Result<(Expression, String), String> {
-	let mut i = ParserInfo::new(tokens /* , locals */, filename, options);
-	while !i.ended() {
-		let t = i.advance();
-		match t.kind() {
-			LOCAL | GLOBAL => i.parse_token_local_global(&t)?,
-			STATIC => i.parse_token_static(&t)?,
-			METHOD => i.parse_token_method()?,
-			IDENTIFIER => i.parse_token_identifier(&t)?,
-			ROUND_BRACKET_OPEN => i.parse_token_round_bracket_open()?,
-			CURLY_BRACKET_OPEN => i.parse_token_curly_bracket_open()?,
-			IF => i.parse_token_if()?,
-			MATCH => i.parse_token_match()?,
-			WHILE => i.parse_token_while(t.line())?,
-			UNTIL => i.parse_token_until(t.line())?,
-			LOOP => i.parse_token_loop(t.line())?,
-			FOR => i.parse_token_for(t.line())?,
-			CONTINUE => i.parse_token_continue()?,
-			BREAK => i.parse_token_break()?,
-			RETURN => i.parse_token_return()?,
-			TRY => i.parse_token_try()?,
-			FN | ENUM => i.parse_token_fn_enum(&t)?,
-			SEMICOLON => {}
-			EOF => break,
-			_ => return Err(i.expected("<end>", &t.lexeme(), t.line(), t.column())),
-		}
-	}
+) -> Result<(Expression, String), Vec<Diagnostic>> {
+	let (expr, statics, _) = parse_tokens_with_warnings(tokens, filename, options)?;
+	Ok((expr, statics))
+}
+
+/// Parses a list of tokens like [`parse_tokens`], also returning the warnings of the type checker.
+/// Types are only checked when [`Options::env_types`] is not [`TypesMode::NONE`]:
+/// in [`TypesMode::WARN`] mismatched types are warnings, in [`TypesMode::STRICT`] they are errors.
+///
+/// # Errors
+/// Returns an [`Err`] containing every error found, like [`parse_tokens`]
+///
+/// # Examples
+/// ```
+/// use clue_core::{code::Code, env::{Options, TypesMode}, parser::*, scanner::*};
+///
+/// let options = Options {
+///     env_types: TypesMode::WARN,
+///     ..Options::default()
+/// };
+/// let filename = String::from("types.clue");
+/// let code = "local x: number = \"one\"";
+/// let tokens = scan_code(Code::from((code, 1, 1)), &filename).unwrap();
+/// let (_, _, warnings) = parse_tokens_with_warnings(tokens, &filename, &options).unwrap();
+/// assert_eq!(warnings[0].code, "T0001");
+/// ```
+pub fn parse_tokens_with_warnings(
+	tokens: Vec<Token>,
+	filename: &String,
+	options: &Options,
+) -> Result<(Expression, String, Vec<Diagnostic>), Vec<Diagnostic>> {
+	let locals = (options.env_types != TypesMode::NONE).then(AHashMap::default);
+	parse_block(tokens, locals, None, AHashSet::new(), filename, options)
+}
 
-	//println!("LOCALS = {:#?}", i.locals);
+/// Preprocesses, scans and parses the given code without changing it, for the tools that only read the AST
+pub(crate) fn parse_source(
+	code: &str,
+	filename: &String,
+	options: &Options,
+) -> Result<Expression, Vec<Diagnostic>> {
+	let mut code = code.to_owned();
+	let (codes, variables, ..) = preprocess_code(
+		// SAFETY: the preprocessor only ever replaces whole characters with ASCII spaces
+		unsafe { code.as_bytes_mut() },
+		1,
+		false,
+		filename,
+		options,
+	)?;
+	let codes = preprocess_codes(0, codes, &variables, filename)?;
+	Ok(parse_tokens(scan_code(codes, filename)?, filename, options)?.0)
+}
 
-	Ok((
-		i.expr,
-		if !i.statics.is_empty() && options.env_debug {
-			format!("--statics defined in \"{}\":\n{}\n", i.filename, i.statics)
-		} else {
-			i.statics
-		},
-	))
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{code::Code, Clue};
+
+	#[test]
+	fn collect_every_error() {
+		let errors = Clue::new()
+			.parse_code(String::from(
+				"local x = \nlocal y = 3\nif y {\n\tlocal z = )\n}\nlocal w = ]\nprint(x)",
+			))
+			.unwrap_err();
+		let lines: Vec<usize> = errors.iter().map(|e| e.span.unwrap().line).collect();
+		assert_eq!(lines, [2, 4, 6]);
+	}
+
+	#[test]
+	fn structs() {
+		let clue = Clue::new();
+		let code = clue
+			.compile_code(String::from(
+				"local struct P { x = 1, y, meta index = P }\nglobal struct Q { meta with M }",
+			))
+			.unwrap();
+		assert!(code.starts_with("local _internal0;\nlocal function P(x, y)"));
+		assert!(code.contains("}, _internal0);"));
+		assert!(code.contains("_internal0 = {\n\t__index = P\n};"));
+		assert!(code.contains("function Q()\n\treturn setmetatable({"));
+		assert!(code.trim_end().ends_with("}, M);\nend"));
+		let errors = clue
+			.parse_code(String::from(
+				"struct A {}\nlocal struct B { meta with M, meta index = B }",
+			))
+			.unwrap_err();
+		let codes: Vec<&str> = errors.iter().map(|error| error.code).collect();
+		assert_eq!(codes, ["E0009", "E0005"]);
+	}
+
+	#[test]
+	fn classes() {
+		let clue = Clue::new();
+		let code = clue
+			.compile_code(String::from(concat!(
+				"local struct A { x = 1\n method get() { return self.x } }\n",
+				"local struct B: A { constructor(x) { A.init(self, x) } }\n",
+				"local struct C: B { y = 2 }",
+			)))
+			.unwrap();
+		assert!(code.starts_with("local A = setmetatable({}, {\n\t__call = function(A, ...)\n\t\treturn A.new(...);\n\tend\n});\nA.__index = A;\nfunction A:init(x)"));
+		assert!(
+			code.contains("\tlocal self = setmetatable({}, A);\n\tself:init(...);\n\treturn self;")
+		);
+		assert!(code.contains("function A:get()"));
+		assert!(code.contains("local B = setmetatable({}, {\n\t__index = A, \n\t__call = function(B, ...)\n\t\treturn B.new(...);\n\tend\n});"));
+		assert!(code.contains("function C:init(...)\n\tB.init(self, ...);\n\tself.y = 2;\nend"));
+		let errors = clue
+			.parse_code(String::from(concat!(
+				"local struct D { constructor() {}, constructor() {} }\n",
+				"local struct E { meta with M\n fn f() {} }\n",
+				"local struct F { x = 0 }\n",
+				"local struct G: F { y = 1 }",
+			)))
+			.unwrap_err();
+		let errors: Vec<(&str, usize)> = errors
+			.iter()
+			.map(|error| (error.code, error.span.unwrap().line))
+			.collect();
+		assert_eq!(errors, [("E0011", 1), ("E0011", 2), ("E0011", 5)]);
+	}
+
+	#[test]
+	fn externs() {
+		let mut clue = Clue::new();
+		clue.types(TypesMode::STRICT);
+		let code = concat!(
+			"extern version: string\n",
+			"extern fn host.log(level: number, ...)\n",
+			"host.log(1, version)\n",
+			"host.log(version)\n",
+		);
+		let errors = clue.parse_code(String::from(code)).unwrap_err();
+		let errors: Vec<(&str, usize)> = errors
+			.iter()
+			.map(|e| (e.code, e.span.unwrap().line))
+			.collect();
+		assert_eq!(errors, [("T0001", 4)]);
+		let errors = clue
+			.parse_code(String::from("extern x, fn f()"))
+			.unwrap_err();
+		assert_eq!(errors[0].code, "E0001");
+		let code = clue
+			.compile_code(String::from(
+				"extern fn host.log(...)\nextern x\nhost.log(x)",
+			))
+			.unwrap();
+		assert_eq!(code.trim(), "host.log(x);");
+	}
+
+	#[test]
+	fn check_types() {
+		let code = concat!(
+			"local fn add(a: number, b: number = 0): number {\n\treturn a .. b\n}\n",
+			"local x: string = add(1)\n",
+			"add(\"1\", 2, 3)\n",
+			"math.floor(add())\n",
+			"local enum { ONE }\n",
+			"local y: number? = ONE + #x\n",
+		);
+		let mut clue = Clue::new();
+		clue.types(TypesMode::STRICT);
+		let errors = clue.parse_code(String::from(code)).unwrap_err();
+		let errors: Vec<(&str, usize)> = errors
+			.iter()
+			.map(|e| (e.code, e.span.unwrap().line))
+			.collect();
+		assert_eq!(
+			errors,
+			[
+				("T0001", 2),
+				("T0001", 4),
+				("T0001", 5),
+				("T0002", 5),
+				("T0002", 6)
+			]
+		);
+
+		let filename = String::from("test.clue");
+		let options = Options {
+			env_types: TypesMode::WARN,
+			..Options::default()
+		};
+		let tokens = scan_code(Code::from((code, 1, 1)), &filename).unwrap();
+		let (_, _, warnings) = parse_tokens_with_warnings(tokens, &filename, &options).unwrap();
+		assert_eq!(warnings.len(), 5);
+		assert!(Clue::new().parse_code(String::from(code)).is_ok());
+	}
 }
@@ -4,8 +4,8 @@
 //! It exposes three functions: [`preprocess_code`], [`preprocess_codes`] and [`preprocess_variables`]
 
 use crate::{
-	check,
 	code::{Code, CodeChar},
+	diagnostic::Diagnostic,
 	env::Options,
 	format_clue,
 };
@@ -18,13 +18,9 @@ use std::{
 	iter::{Peekable, Rev},
 	path::PathBuf,
 	str::{self, Split},
-	u8::{self, MAX},
 };
 use utf8_decode::decode;
 
-#[cfg(feature = "lsp")]
-use serde_json::json;
-
 macro_rules! pp_if {
 	($code:ident, $ifname:ident, $prev:ident) => {{
 		let check = $code.$ifname(b'{')?;
@@ -65,13 +61,19 @@ pub enum PPVar {
 	VarArgs(PPCode),
 }
 
-fn error(msg: impl Into<String>, line: usize, column: usize, filename: &String) -> String {
-	eprintln!("Error in {filename}:{line}:{column}!");
-	msg.into()
+fn error(
+	code: &'static str,
+	msg: impl Into<String>,
+	line: usize,
+	column: usize,
+	filename: &str,
+) -> Diagnostic {
+	Diagnostic::error(code, msg, filename).at(line, column)
 }
 
-fn expected(expected: &str, got: &str, line: usize, column: usize, filename: &String) -> String {
+fn expected(expected: &str, got: &str, line: usize, column: usize, filename: &str) -> Diagnostic {
 	error(
+		"P0002",
 		format_clue!("Expected '", expected, "', got '", got, "'"),
 		line,
 		column,
@@ -84,9 +86,10 @@ fn expected_before(
 	before: &str,
 	line: usize,
 	column: usize,
-	filename: &String,
-) -> String {
+	filename: &str,
+) -> Diagnostic {
 	error(
+		"P0003",
 		format_clue!("Expected '", expected, "' before '", before, "'"),
 		line,
 		column,
@@ -116,6 +119,7 @@ struct CodeFile<'a> {
 	last_if: bool,
 	cscope: u8,
 	ends: Vec<u8>,
+	warnings: Vec<Diagnostic>,
 }
 
 impl<'a> CodeFile<'a> {
@@ -139,19 +143,24 @@ impl<'a> CodeFile<'a> {
 			last_if: true,
 			cscope,
 			ends: Vec::new(),
+			warnings: Vec::new(),
 		}
 	}
 
-	fn is_ascii(&mut self, c: Option<CodeChar>) -> Result<Option<CodeChar>, String> {
+	fn is_ascii(&mut self, c: Option<CodeChar>) -> Result<Option<CodeChar>, Diagnostic> {
 		match c {
 			None => Ok(None),
 			Some(c) if c.0.is_ascii() => Ok(Some(c)),
 			Some((_, line, column)) => {
-				let c = check!(decode(
-					&mut self.code[self.read - 1..cmp::min(self.read + 3, self.code.len())].iter().copied()
+				let c = decode(
+					&mut self.code[self.read - 1..cmp::min(self.read + 3, self.code.len())]
+						.iter()
+						.copied(),
 				)
-				.unwrap());
+				.unwrap()
+				.map_err(|e| error("P0001", e.to_string(), line, column, self.filename))?;
 				Err(error(
+					"P0001",
 					format!("Invalid character '{c}'"),
 					line,
 					column,
@@ -230,7 +239,7 @@ impl<'a> CodeFile<'a> {
 		}
 	}
 
-	fn read_char(&mut self) -> Result<Option<CodeChar>, String> {
+	fn read_char(&mut self) -> Result<Option<CodeChar>, Diagnostic> {
 		let c = self.read_char_unchecked();
 		self.is_ascii(c)
 	}
@@ -242,45 +251,41 @@ impl<'a> CodeFile<'a> {
 		self.peeked
 	}
 
-	fn peek_char(&mut self) -> Result<Option<CodeChar>, String> {
+	fn peek_char(&mut self) -> Result<Option<CodeChar>, Diagnostic> {
 		let c = self.peek_char_unchecked();
 		self.is_ascii(c)
 	}
 
-	fn assert_char(&mut self, wanted_c: u8) -> Result<(), String> {
+	fn assert_char(&mut self, wanted_c: u8) -> Result<(), Diagnostic> {
 		match self.read_char()? {
-			None => {
-				return Err(expected_before(
-					&String::from_utf8_lossy(&[wanted_c]),
-					"<end>",
-					self.line,
-					self.column,
-					self.filename,
-				))
-			}
-			Some((c, line, column)) if c != wanted_c => {
-				return Err(expected(
-					&String::from_utf8_lossy(&[wanted_c]),
-					&String::from_utf8_lossy(&[c]),
-					line,
-					column,
-					self.filename,
-				))
-			}
+			None => Err(expected_before(
+				&String::from_utf8_lossy(&[wanted_c]),
+				"<end>",
+				self.line,
+				self.column,
+				self.filename,
+			)),
+			Some((c, line, column)) if c != wanted_c => Err(expected(
+				&String::from_utf8_lossy(&[wanted_c]),
+				&String::from_utf8_lossy(&[c]),
+				line,
+				column,
+				self.filename,
+			)),
 			_ => Ok(()),
 		}
 	}
 
-	fn assert_reach(&mut self, wanted_c: u8) -> Result<(), String> {
+	fn assert_reach(&mut self, wanted_c: u8) -> Result<(), Diagnostic> {
 		self.skip_whitespace();
 		self.assert_char(wanted_c)
 	}
 
 	fn read(
 		&mut self,
-		mut get: impl FnMut(&mut Self) -> Result<Option<CodeChar>, String>,
+		mut get: impl FnMut(&mut Self) -> Result<Option<CodeChar>, Diagnostic>,
 		mut check: impl FnMut(&mut Self, CodeChar) -> bool,
-	) -> Result<Code, String> {
+	) -> Result<Code, Diagnostic> {
 		let mut code = Code::new();
 		while let Some(c) = get(self)? {
 			if check(self, c) {
@@ -300,7 +305,7 @@ impl<'a> CodeFile<'a> {
 		.to_string()
 	}
 
-	fn read_identifier(&mut self) -> Result<Code, String> {
+	fn read_identifier(&mut self) -> Result<Code, Diagnostic> {
 		self.read(Self::peek_char, |code, (c, ..)| {
 			if c.is_ascii_alphanumeric() || c == b'_' {
 				code.read_char_unchecked().unwrap();
@@ -311,14 +316,20 @@ impl<'a> CodeFile<'a> {
 		})
 	}
 
-	fn read_string(&mut self, c: CodeChar) -> Result<Code, String> {
+	fn read_string(&mut self, c: CodeChar) -> Result<Code, Diagnostic> {
 		self.comment = CommentState::String;
 		let mut skip_next = false;
 		self.read(
 			|code| {
 				let stringc = code.read_char_unchecked();
 				if stringc.is_none() {
-					Err(error("Unterminated string", c.1, c.2, self.filename))
+					Err(error(
+						"P0004",
+						"Unterminated string",
+						c.1,
+						c.2,
+						self.filename,
+					))
 				} else {
 					Ok(stringc)
 				}
@@ -345,8 +356,8 @@ impl<'a> CodeFile<'a> {
 	fn read_until_with(
 		&mut self,
 		end: u8,
-		f: impl FnMut(&mut Self) -> Result<Option<CodeChar>, String>,
-	) -> Result<Option<Code>, String> {
+		f: impl FnMut(&mut Self) -> Result<Option<CodeChar>, Diagnostic>,
+	) -> Result<Option<Code>, Diagnostic> {
 		let mut reached = false;
 		let result = self.read(f, |_, (c, ..)| {
 			if c == end {
@@ -359,7 +370,7 @@ impl<'a> CodeFile<'a> {
 		Ok(reached.then_some(result))
 	}
 
-	fn read_until(&mut self, end: u8) -> Result<Code, String> {
+	fn read_until(&mut self, end: u8) -> Result<Code, Diagnostic> {
 		self.read_until_with(end, Self::read_char)?.ok_or_else(|| {
 			expected_before(
 				&(end as char).to_string(),
@@ -371,7 +382,7 @@ impl<'a> CodeFile<'a> {
 		})
 	}
 
-	fn read_macro_args(&mut self) -> Result<Code, String> {
+	fn read_macro_args(&mut self) -> Result<Code, Diagnostic> {
 		let mut args = Code::new();
 		args.push(self.read_char_unchecked().unwrap());
 		while let Some(c) = self.peek_char()? {
@@ -398,18 +409,19 @@ impl<'a> CodeFile<'a> {
 		))
 	}
 
-	fn read_macro_block(&mut self) -> Result<(PPCode, PPVars), String> {
+	fn read_macro_block(&mut self) -> Result<(PPCode, PPVars), Diagnostic> {
 		let line = self.line;
 		let len = self.code.len();
 		let block = &mut self.code[self.read..len];
-		let (block, ppvars, line, read) =
+		let (block, ppvars, line, read, warnings) =
 			preprocess_code(block, line, true, self.filename, &Options::default())?;
+		self.warnings.extend(warnings);
 		self.line = line;
 		self.read += read;
 		Ok((block, ppvars))
 	}
 
-	fn skip_block(&mut self) -> Result<(), String> {
+	fn skip_block(&mut self) -> Result<(), Diagnostic> {
 		while let Some(c) = self.read_char()? {
 			match c.0 {
 				b'{' => self.skip_block()?,
@@ -429,7 +441,7 @@ impl<'a> CodeFile<'a> {
 		))
 	}
 
-	fn keep_block(&mut self, to_keep: bool) -> Result<(), String> {
+	fn keep_block(&mut self, to_keep: bool) -> Result<(), Diagnostic> {
 		self.last_if = to_keep;
 		if to_keep {
 			self.ends.push(self.cscope);
@@ -440,12 +452,12 @@ impl<'a> CodeFile<'a> {
 		}
 	}
 
-	fn ifos(&mut self, end: u8) -> Result<bool, String> {
+	fn ifos(&mut self, end: u8) -> Result<bool, Diagnostic> {
 		let checked_os = self.read_until(end)?.trim();
 		Ok(checked_os == self.options.env_targetos)
 	}
 
-	fn iflua(&mut self, end: u8) -> Result<bool, String> {
+	fn iflua(&mut self, end: u8) -> Result<bool, Diagnostic> {
 		use crate::env::LuaVersion::*;
 		let checked_lua_version = self.read_until(end)?.trim();
 		let Some(target) = self.options.env_target else {
@@ -461,16 +473,16 @@ impl<'a> CodeFile<'a> {
 		)
 	}
 
-	fn ifdef(&mut self, end: u8) -> Result<bool, String> {
+	fn ifdef(&mut self, end: u8) -> Result<bool, Diagnostic> {
 		let to_check = self.read_until(end)?.trim();
 		Ok(env::var_os(to_check.to_string()).is_some())
 	}
 
-	fn ifndef(&mut self, end: u8) -> Result<bool, String> {
+	fn ifndef(&mut self, end: u8) -> Result<bool, Diagnostic> {
 		self.ifdef(end).map(|ok| !ok)
 	}
 
-	fn ifcmp(&mut self, end: u8) -> Result<bool, String> {
+	fn ifcmp(&mut self, end: u8) -> Result<bool, Diagnostic> {
 		let Some(to_compare1) = env::var_os(self.read_identifier()?.to_string()) else {
 			self.read_until(end)?;
 			return Ok(false)
@@ -504,7 +516,7 @@ impl<'a> CodeFile<'a> {
 		})
 	}
 
-	fn bool_op(&mut self, b: bool) -> Result<bool, String> {
+	fn bool_op(&mut self, b: bool) -> Result<bool, Diagnostic> {
 		let mut result = !b;
 		loop {
 			if self.r#if()? == b {
@@ -520,7 +532,7 @@ impl<'a> CodeFile<'a> {
 		}
 	}
 
-	fn r#if(&mut self) -> Result<bool, String> {
+	fn r#if(&mut self) -> Result<bool, Diagnostic> {
 		let check = {
 			let function = self.read_identifier()?.to_string();
 			self.assert_char(b'(')?;
@@ -549,6 +561,7 @@ impl<'a> CodeFile<'a> {
 				}
 				_ => {
 					return Err(error(
+						"P0005",
 						format!("Unknown function '{function}'"),
 						self.line,
 						self.column,
@@ -561,10 +574,15 @@ impl<'a> CodeFile<'a> {
 		Ok(check)
 	}
 
-	fn get_version_number(&self, version: &mut Split<char>, default : &str) -> Result<u8, String> {
+	fn get_version_number(
+		&self,
+		version: &mut Split<char>,
+		default: &str,
+	) -> Result<u8, Diagnostic> {
 		let num = match version.next() {
 			None => {
 				return Err(error(
+					"P0006",
 					"Incomplete version (must be 'X.Y.Z')",
 					self.line,
 					self.column,
@@ -577,6 +595,7 @@ impl<'a> CodeFile<'a> {
 		match num.parse::<u8>() {
 			Ok(num) => Ok(num),
 			Err(_) => Err(error(
+				"P0006",
 				"Invalid version (must be 'X.Y.Z')",
 				self.line,
 				self.column,
@@ -586,7 +605,7 @@ impl<'a> CodeFile<'a> {
 	}
 }
 
-/// Reads a file and gives back the a list of preprocessed code blocks and the variables
+/// Reads a file and gives back the a list of preprocessed code blocks, the variables and the warnings
 ///
 /// # Errors
 /// If the file cannot be read or the code cannot be preprocessed it will return an [`Err`] with the error message
@@ -597,7 +616,7 @@ impl<'a> CodeFile<'a> {
 ///
 /// fn main() -> Result<(), String> {
 ///     let options = Options::default();
-///     let (code, vars) = read_file(
+///     let (code, vars, warnings) = read_file(
 ///         "../examples/macro.clue",
 ///         &String::from("macro.clue"),
 ///         &options,
@@ -610,12 +629,15 @@ pub fn read_file(
 	path: impl Into<PathBuf>,
 	filename: &String,
 	options: &Options,
-) -> Result<(PPCode, PPVars), String> {
-	let result = preprocess_code(&mut check!(fs::read(path.into())), 1, false, filename, options)?;
-	Ok((result.0, result.1))
+) -> Result<(PPCode, PPVars, Vec<Diagnostic>), Diagnostic> {
+	let mut code = fs::read(path.into())
+		.map_err(|e| Diagnostic::error("F0001", e.to_string(), filename.as_str()))?;
+	let (codes, variables, _, _, warnings) = preprocess_code(&mut code, 1, false, filename, options)?;
+	Ok((codes, variables, warnings))
 }
 
-/// Preprocesses code and gives back the a list of preprocessed code blocks and the variable
+/// Preprocesses code and gives back the a list of preprocessed code blocks, the variables,
+/// the line and byte it stopped at and the warnings
 ///
 /// # Errors
 /// If the code cannot be preprocessed it will return an [`Err`] with the error message
@@ -639,7 +661,7 @@ pub fn preprocess_code(
 	is_block: bool,
 	filename: &String,
 	options: &Options,
-) -> Result<(PPCode, PPVars, usize, usize), String> {
+) -> Result<(PPCode, PPVars, usize, usize, Vec<Diagnostic>), Diagnostic> {
 	let mut output_dir: Option<PathBuf> = None;
 	let mut finalcode = VecDeque::new();
 	let mut currentcode = Code::with_capacity(code.len());
@@ -681,7 +703,7 @@ pub fn preprocess_code(
 						code.keep_block(!code.last_if)?;
 					}
 					"import" => {
-						if output_dir.is_none() {
+						if output_dir.is_none() && !options.env_require {
 							output_dir = Some(match options.env_outputname.as_ref() {
 								Some(output_dir) => output_dir
 									.parent()
@@ -689,10 +711,15 @@ pub fn preprocess_code(
 										|| output_dir.to_path_buf(),
 										|output_dir| output_dir.to_path_buf()
 									),
-								None => check!(env::current_dir())
+								None => env::current_dir().map_err(|e| {
+									Diagnostic::error(
+										"F0001",
+										e.to_string(),
+										code.filename.as_str(),
+									)
+								})?,
 							})
 						}
-						let output_dir = output_dir.as_ref().unwrap();
 						let str_start = code.read_char_unchecked();
 						let module = match str_start {
 							Some((b'\'' | b'"' | b'`', ..)) => {
@@ -704,16 +731,21 @@ pub fn preprocess_code(
 						}.to_string();
 						let name = code.read_line();
 						let name = name.trim();
-						let mut dirs = module.split('.');
-						let mut module_path = output_dir.join(dirs.next().unwrap());
-						for dir in dirs {
-							module_path.push(dir);
-						}
-						module_path.set_extension("lua");
-						let function = if module_path.exists() {
-							"require"
-						} else {
-							"import"
+						let function = match &output_dir {
+							Some(output_dir) => {
+								let mut dirs = module.split('.');
+								let mut module_path = output_dir.join(dirs.next().unwrap());
+								for dir in dirs {
+									module_path.push(dir);
+								}
+								module_path.set_extension("lua");
+								if module_path.exists() {
+									"require"
+								} else {
+									"import"
+								}
+							}
+							None => "require",
 						};
 						let (name, start) = match name.strip_prefix("=>") {
 							Some(name) =>{
@@ -727,14 +759,15 @@ pub fn preprocess_code(
 										filename
 									))
 								}
-								if trimmed_name.contains(|c| matches!(c, '$' | '@')) {
-									let (codes, new_variables, ..) = preprocess_code(
+								if trimmed_name.contains(['$', '@']) {
+									let (codes, new_variables, _, _, warnings) = preprocess_code(
 										unsafe { trimmed_name.as_bytes_mut() },
 										code.line,
 										false,
 										filename,
 										options
 									)?;
+									code.warnings.extend(warnings);
 									for (key, value) in new_variables {
 										variables.insert(key, value);
 									}
@@ -745,7 +778,7 @@ pub fn preprocess_code(
 										filename
 									)?.to_string();
 								}
-								let start = if trimmed_name.contains(|c| matches!(c, '.' | '[')) {
+								let start = if trimmed_name.contains(['.', '[']) {
 									""
 								} else {
 									"local "
@@ -783,8 +816,14 @@ pub fn preprocess_code(
 							};
 						if let Some(v) = full_wanted_version.strip_prefix(">=") {
 							wanted_version = v;
-							println!(
-								"Note: \"@version directives should no longer start with '>='\""
+							code.warnings.push(
+								Diagnostic::warning(
+									"P0006",
+									"@version directives should no longer start with '>='",
+									filename.as_str(),
+								)
+								.at(c.1, c.2)
+								.with_help(format_clue!("use '@version ", v.trim(), "' instead")),
 							);
 						}
 						let wanted_version_iter = &mut wanted_version.split('.');
@@ -801,6 +840,7 @@ pub fn preprocess_code(
 						|| check(&current_minor, &wanted_minor)
 						|| check(&current_patch, &wanted_patch) {
 							return Err(error(
+								"P0006",
 								if full_wanted_version.starts_with('=') {
 									format_clue!(
 										"This code is only compatible with version '",
@@ -856,9 +896,13 @@ pub fn preprocess_code(
 							},
 						)?;
 						if code.comment == CommentState::String {
-							return Err(
-								error("Unterminated string", code.line, code.column, code.filename)
-							);
+							return Err(error(
+								"P0004",
+								"Unterminated string",
+								code.line,
+								code.column,
+								code.filename,
+							));
 						}
 						let value = value.trim();
 						variables.insert(
@@ -929,10 +973,11 @@ pub fn preprocess_code(
 							},
 						);
 					}
-					"error" => return Err(error(code.read_line(), c.1, c.2, filename)),
+					"error" => return Err(error("P0007", code.read_line(), c.1, c.2, filename)),
 					"print" => println!("{}", code.read_line()),
 					_ => {
 						return Err(error(
+							"P0005",
 							format!("Unknown directive '{directive_name}'"),
 							c.1,
 							c.2,
@@ -1092,31 +1137,19 @@ pub fn preprocess_code(
 		size += currentcode.len();
 		finalcode.push_back((currentcode, false))
 	}
-	if bitwise && options.env_jitbit.is_some() {
-		let bit = options.env_jitbit.as_ref().unwrap();
+	if let (true, Some(bit)) = (bitwise, &options.env_jitbit) {
 		let mut loader = Code::from((format_clue!("local ", bit, " = require(\"", bit, "\");"), 1, 1));
 		let first = finalcode.pop_front().unwrap();
 		loader.append(first.0);
 		finalcode.push_front((loader, first.1));
 	}
-	#[cfg(feature = "lsp")]
-	if options.env_symbols {
-		use PPVar::*;
-		use std::collections::HashMap;
-		let mut str_variables = HashMap::new();
-		for (name, variable) in &variables {
-			let (name, value) = match variable {
-				Simple(value) | ToProcess(value) => (format_clue!('$', name), value.to_string()),
-				_ => unimplemented!()
-			};
-			str_variables.insert(name, value);
-		}
-		println!("{}", json!({
-			"type": "PPVars",
-			"value": str_variables
-		}));
-	}
-	Ok(((finalcode, size), variables, code.line, code.read))
+	Ok((
+		(finalcode, size),
+		variables,
+		code.line,
+		code.read,
+		code.warnings,
+	))
 }
 
 fn skip_whitespace_backwards(code: &mut Peekable<Rev<std::slice::Iter<u8>>>) {
@@ -1247,7 +1280,7 @@ pub fn preprocess_codes(
 	codes: PPCode,
 	variables: &PPVars,
 	filename: &String,
-) -> Result<Code, String> {
+) -> Result<Code, Diagnostic> {
 	let (mut codes, size) = codes;
 	if codes.len() == 1 {
 		Ok(codes.pop_back().unwrap().0)
@@ -1308,7 +1341,7 @@ pub fn preprocess_variables(
 	//mut chars: Peekable<Iter<CodeChar>>,
 	variables: &PPVars,
 	filename: &String,
-) -> Result<Code, String> {
+) -> Result<Code, Diagnostic> {
 	let mut result = Code::with_capacity(size);
 	let mut chars = code.iter().peekable();
 	while let Some(c) = chars.next() {
@@ -1331,8 +1364,9 @@ pub fn preprocess_variables(
 					}
 					result.push((b'"', c.1, c.2));
 				} else if let Some(value) = variables.get(&name) {
-					if stacklevel == MAX {
+					if stacklevel == u8::MAX {
 						return Err(error(
+							"P0010",
 							"Too many variables called (likely recursive)",
 							c.1,
 							c.2,
@@ -1362,6 +1396,7 @@ pub fn preprocess_variables(
 								if !is_called || !matches!(chars.next(), Some((b'(', ..))) {
 									let name = name.to_string();
 									return Err(error(
+										"P0009",
 										format!(
 											"Macro not called (replace '${name}{}' with '${name}!()')",
 											if is_called {
@@ -1441,6 +1476,7 @@ pub fn preprocess_variables(
 										macro_variables.insert(arg_name, value);
 									} else {
 										return Err(error(
+											"P0009",
 											"Too many arguments passed to macro",
 											c.1,
 											c.2,
@@ -1453,10 +1489,8 @@ pub fn preprocess_variables(
 								}
 								if let Some(missed) = args.next() {
 									return Err(error(
-										format!(
-											"Missing argument '{}' for macro",
-											missed.to_string()
-										),
+										"P0009",
+										format!("Missing argument '{missed}' for macro"),
 										c.1,
 										c.2,
 										filename,
@@ -1490,6 +1524,7 @@ pub fn preprocess_variables(
 					});
 				} else {
 					return Err(error(
+						"P0008",
 						format_clue!("Value '", name.to_string(), "' not found"),
 						c.1,
 						c.2,
@@ -8,6 +8,7 @@
 
 use crate::{
 	code::{Code, CodeChars},
+	diagnostic::{Diagnostic, Span},
 	format_clue,
 };
 
@@ -149,7 +150,7 @@ struct CodeInfo<'a> {
 	filename: &'a String,
 	tokens: Vec<Token>,
 	last: TokenType,
-	errored: bool,
+	errors: Vec<Diagnostic>,
 }
 
 impl<'a> CodeInfo<'a> {
@@ -170,7 +171,7 @@ impl<'a> CodeInfo<'a> {
 			filename,
 			tokens: Vec::new(),
 			last: EOF,
-			errored: false,
+			errors: Vec::new(),
 		}
 	}
 
@@ -245,21 +246,24 @@ impl<'a> CodeInfo<'a> {
 			.push(Token::new(kind, lexeme, self.line, self.column));
 	}
 
-	fn warning(&mut self, message: impl Into<String>) {
-		eprintln!(
-			"Error in {}:{}:{}!\nError: \"{}\"\n",
-			self.filename,
-			self.line,
-			self.column,
-			message.into()
+	fn warning(&mut self, code: &'static str, message: impl Into<String>) {
+		self.errors.push(
+			Diagnostic::error(code, message, self.filename.as_str()).at(self.line, self.column),
 		);
-		self.errored = true;
 	}
 
 	fn reserved(&mut self, keyword: &str, msg: &str) -> TokenType {
-		self.warning(format!(
-			"'{keyword}' is a reserved keyword in Lua and it cannot be used as a variable, {msg}",
-		));
+		self.errors.push(
+			Diagnostic::error(
+				"S0004",
+				format!(
+					"'{keyword}' is a reserved keyword in Lua and it cannot be used as a variable"
+				),
+				self.filename.as_str(),
+			)
+			.with_span(Span::sized(self.line, self.column, keyword.len()))
+			.with_help(msg),
+		);
 		IDENTIFIER
 	}
 
@@ -282,7 +286,7 @@ impl<'a> CodeInfo<'a> {
 					if c == '-' && self.peek(2).is_ascii_digit() {
 						self.advance();
 					} else {
-						self.warning("Malformed number");
+						self.warning("S0002", "Malformed number");
 					}
 				}
 				self.advance();
@@ -291,7 +295,7 @@ impl<'a> CodeInfo<'a> {
 				}
 			}
 		} else if self.current == start {
-			self.warning("Malformed number");
+			self.warning("S0002", "Malformed number");
 		}
 		let llcheck = self.substr(self.current, self.current + 2);
 		if llcheck == "LL" {
@@ -303,7 +307,7 @@ impl<'a> CodeInfo<'a> {
 				self.advance();
 				self.advance();
 			} else {
-				self.warning("Malformed number");
+				self.warning("S0002", "Malformed number");
 			}
 		}
 		self.add_token(NUMBER);
@@ -317,7 +321,7 @@ impl<'a> CodeInfo<'a> {
 			}
 		}
 		if self.ended() {
-			self.warning("Unterminated string");
+			self.warning("S0003", "Unterminated string");
 			false
 		} else {
 			true
@@ -396,6 +400,7 @@ impl<'a> CodeInfo<'a> {
 }
 
 #[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
 enum SymbolType {
 	Just(TokenType),
 	Function(fn(&mut CodeInfo)),
@@ -419,7 +424,6 @@ impl fmt::Debug for SymbolType {
 enum KeywordType {
 	Just(TokenType),
 	Lua(TokenType),
-	Error(&'static str),
 	Reserved(&'static str),
 }
 
@@ -621,22 +625,67 @@ static KEYWORDS: phf::Map<&'static [u8], KeywordType> = phf_map! {
 	b"catch" => KeywordType::Just(CATCH),
 	b"match" => KeywordType::Just(MATCH),
 	b"default" => KeywordType::Just(DEFAULT),
-	b"constructor" => KeywordType::Error("'constructor' is reserved for Clue 4.0 and cannnot be used."),
-	b"struct" => KeywordType::Error("'struct' is reserved for Clue 4.0 and cannot be used"),
-	b"extern" =>KeywordType::Error("'extern' is reserved for Clue 4.0 and cannot be used"),
+	b"struct" => KeywordType::Just(STRUCT),
+	b"constructor" => KeywordType::Just(CONSTRUCTOR),
+	b"extern" => KeywordType::Just(EXTERN),
 };
 
+/// Returns the kind of token the scanner gives to the given identifier,
+/// `last` is the kind of the token that comes before it
+pub(crate) fn word_kind(word: &str, last: TokenType) -> TokenType {
+	match KEYWORDS.get(word.as_bytes()) {
+		Some(KeywordType::Lua(kind)) => *kind,
+		Some(KeywordType::Just(kind))
+			if !matches!(last, DOT | SAFE_DOT | DOUBLE_COLON | SAFE_DOUBLE_COLON) =>
+		{
+			*kind
+		}
+		_ => IDENTIFIER,
+	}
+}
+
+/// Returns the kind of token the scanner gives to the given symbol, if it is one
+pub(crate) fn symbol_kind(symbol: &str) -> Option<TokenType> {
+	fn get(symbols: &SymbolsMap, c: char) -> Option<&'static SymbolType> {
+		*symbols.get((c as usize).checked_sub('!' as usize)?)?
+	}
+
+	if symbol == "?::" {
+		return Some(SAFE_DOUBLE_COLON);
+	}
+	let mut chars = symbol.chars();
+	let mut symbol = get(&SYMBOLS, chars.next()?)?;
+	for c in chars {
+		let SymbolType::Symbols(symbols, _) = symbol else {
+			return None;
+		};
+		symbol = get(symbols, c)?;
+	}
+	match symbol {
+		SymbolType::Just(kind) | SymbolType::Symbols(_, kind) => Some(*kind),
+		SymbolType::Function(_) => None,
+	}
+}
+
+/// Returns every keyword that can be used in Clue code, in no particular order
+pub fn keywords() -> impl Iterator<Item = &'static str> {
+	KEYWORDS.entries().filter_map(|(keyword, kind)| match kind {
+		KeywordType::Just(_) | KeywordType::Lua(_) => std::str::from_utf8(keyword).ok(),
+		_ => None,
+	})
+}
+
 /// Scans the code and returns a [`Vec`] of [`Token`]s
 /// It takes a preprocessed code and a filename as arguments
 ///
 /// # Errors
-/// If the code is invalid, it will return an [`Err`] with the error message
+/// If the code is invalid, it will return an [`Err`] with every error found in the file
 ///
 /// # Examples
 /// ```
-/// use clue_core::{env::Options, preprocessor::*, scanner::*};
+/// use clue_core::{diagnostic::Diagnostic, env::Options, preprocessor::*, scanner::*};
 ///
-/// fn main() -> Result<(), String> {
+/// fn main() -> Result<(), Vec<Diagnostic>> {
 ///     let options = Options::default();
 ///     let filename = String::from("fizzbuzz.clue");
 ///     let mut code = include_str!("../../examples/fizzbuzz.clue").to_owned();
@@ -654,7 +703,7 @@ static KEYWORDS: phf::Map<&'static [u8], KeywordType> = phf_map! {
 ///     Ok(())
 /// }
 /// ```
-pub fn scan_code(code: Code, filename: &String) -> Result<Vec<Token>, String> {
+pub fn scan_code(code: Code, filename: &String) -> Result<Vec<Token>, Vec<Diagnostic>> {
 	let mut i: CodeInfo = CodeInfo::new(code, filename);
 	while !i.ended() && i.peek(0) != '\0' {
 		i.start = i.current;
@@ -699,24 +748,18 @@ pub fn scan_code(code: Code, filename: &String) -> Result<Vec<Token>, String> {
 							IDENTIFIER
 						}
 						KeywordType::Just(kind) => *kind,
-						KeywordType::Error(e) => {
-							i.warning(*e);
-							IDENTIFIER
-						}
 					}
 				} else {
 					IDENTIFIER
 				};
 				i.add_token(kind);
 			} else {
-				i.warning(format!("Unexpected character '{c}'").as_str());
+				i.warning("S0001", format!("Unexpected character '{c}'"));
 			}
 		}
 	}
-	if i.errored {
-		return Err(String::from(
-			"Cannot continue until the above errors are fixed",
-		));
+	if !i.errors.is_empty() {
+		return Err(i.errors);
 	}
 	i.add_literal_token(EOF, String::from("<end>"));
 	Ok(i.tokens)
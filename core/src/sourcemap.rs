@@ -0,0 +1,455 @@
+//! The `sourcemap` module maps the lines of the generated Lua code back to the Clue files they came from
+//!
+//! When [`Options::env_sourcemap`](crate::env::Options::env_sourcemap) is enabled the [`Compiler`](crate::compiler::Compiler)
+//! marks every statement with the file, line and column it was compiled from.
+//! Once the final Lua code is assembled [`SourceMap::extract`] removes the markers
+//! and returns the clean code together with a [`SourceMap`], which can be saved as Source Map v3 JSON.
+//! A saved map can be loaded back with [`SourceMap::from_json`]
+//! and used to rewrite Lua error messages and tracebacks with [`SourceMap::rewrite_trace`].
+
+use crate::scanner::Token;
+use ahash::AHashMap;
+use std::fmt::Write;
+
+#[cfg(feature = "serde")]
+use crate::diagnostic::Diagnostic;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+
+const MARKER_START: char = '\u{1}';
+const MARKER_END: char = '\u{2}';
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Returns the marker the [`Compiler`](crate::compiler::Compiler) inserts to map a line of code to `filename:line:column`
+pub fn marker(filename: &str, line: usize, column: usize) -> String {
+	format!("{MARKER_START}{filename}:{line}:{column}{MARKER_END}")
+}
+
+/// Returns the column of the first token of every line,
+/// which the [`Compiler`](crate::compiler::Compiler) uses for the statements that start on that line
+///
+/// # Example
+/// ```rust
+/// use clue_core::{code::Code, scanner::scan_code, sourcemap::line_columns};
+///
+/// let filename = String::from("main.clue");
+/// let tokens = scan_code(Code::from(("local x = 1", 2, 5)), &filename).unwrap();
+/// let columns = line_columns(&tokens);
+///
+/// assert_eq!(columns[&2], 5);
+/// ```
+pub fn line_columns(tokens: &[Token]) -> AHashMap<usize, usize> {
+	let mut columns = AHashMap::new();
+	for token in tokens {
+		columns.entry(token.line).or_insert(token.column);
+	}
+	columns
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Where a line of the generated Lua code comes from
+pub struct Mapping {
+	/// The index of the source file in [`SourceMap::sources`]
+	pub source: usize,
+
+	/// The line in the source file, starting from 1
+	pub line: usize,
+
+	/// The column in the source file, starting from 1
+	pub column: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// A map from every line of the generated Lua code to the Clue file, line and column it was compiled from
+pub struct SourceMap {
+	/// The name of the generated file
+	pub file: String,
+
+	/// The Clue files the code was compiled from
+	pub sources: Vec<String>,
+
+	/// The mapping of every line of the generated code, `None` if a line has none
+	pub lines: Vec<Option<Mapping>>,
+}
+
+impl SourceMap {
+	/// Removes the markers left by the [`Compiler`](crate::compiler::Compiler) from `code`
+	/// and returns the clean code with the [`SourceMap`] built from them,
+	/// `file` is the name of the generated file.
+	/// If a line contains more than one marker the first one is used.
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::sourcemap::{marker, SourceMap};
+	///
+	/// let code = format!("{}print(1);\nprint(2);{}", marker("main.clue", 3, 1), marker("main.clue", 5, 2));
+	/// let (code, map) = SourceMap::extract(&code, "main.lua");
+	///
+	/// assert_eq!(code, "print(1);\nprint(2);");
+	/// assert_eq!(map.lookup(2), Some(("main.clue", 5)));
+	/// ```
+	pub fn extract(code: &str, file: impl Into<String>) -> (String, Self) {
+		let mut result = String::with_capacity(code.len());
+		let mut map = SourceMap {
+			file: file.into(),
+			..Default::default()
+		};
+		for line in code.split('\n') {
+			if !map.lines.is_empty() {
+				result.push('\n');
+			}
+			let mut mapping = None;
+			let mut rest = line;
+			while let Some(start) = rest.find(MARKER_START) {
+				// an unterminated marker is kept as it is by the push after the loop
+				let Some(end) = rest[start..].find(MARKER_END) else {
+					break;
+				};
+				result += &rest[..start];
+				let marker = &rest[start + 1..start + end];
+				rest = &rest[start + end + 1..];
+				if mapping.is_some() {
+					continue;
+				}
+				let mut parts = marker.rsplitn(3, ':');
+				let (column, line) = (parts.next(), parts.next());
+				if let (Some(Ok(column)), Some(Ok(line)), Some(source)) =
+					(column.map(str::parse), line.map(str::parse), parts.next())
+				{
+					mapping = Some(Mapping {
+						source: map.source_index(source),
+						line,
+						column,
+					});
+				}
+			}
+			result += rest;
+			map.lines.push(mapping);
+		}
+		(result, map)
+	}
+
+	fn source_index(&mut self, source: &str) -> usize {
+		match self.sources.iter().position(|s| s == source) {
+			Some(i) => i,
+			None => {
+				self.sources.push(source.to_owned());
+				self.sources.len() - 1
+			}
+		}
+	}
+
+	/// Shifts every mapping down by `lines` lines,
+	/// used when the code is wrapped inside other code after being extracted
+	pub fn offset(&mut self, lines: usize) {
+		self.lines.splice(0..0, std::iter::repeat(None).take(lines));
+	}
+
+	/// Returns the source file and line the given line of the generated code (starting from 1) comes from
+	pub fn lookup(&self, line: usize) -> Option<(&str, usize)> {
+		let mapping = (*self.lines.get(line.checked_sub(1)?)?)?;
+		Some((&self.sources[mapping.source], mapping.line))
+	}
+
+	/// Like [`SourceMap::lookup`], but if the given line has no mapping the closest mapped line before it is used,
+	/// as long as the closest mapped line after it comes from the same file.
+	/// This keeps lines added by the bundler around every file from being attributed to the wrong one.
+	pub fn lookup_nearest(&self, line: usize) -> Option<(&str, usize)> {
+		let index = line.checked_sub(1)?;
+		if let Some(Some(mapping)) = self.lines.get(index) {
+			return Some((&self.sources[mapping.source], mapping.line));
+		}
+		let before = self.lines[..index.min(self.lines.len())]
+			.iter()
+			.rev()
+			.flatten()
+			.next()?;
+		let after = self.lines.iter().skip(index + 1).flatten().next()?;
+		(before.source == after.source).then(|| (self.sources[before.source].as_str(), before.line))
+	}
+
+	/// Rewrites every `file:line` location of the generated file found in `trace`
+	/// (like a Lua error message or the output of `debug.traceback()`) into the Clue file and line it comes from.
+	/// Locations with no mapping are left untouched.
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::sourcemap::{marker, SourceMap};
+	///
+	/// let code = format!("\n{}error(\"oops\");", marker("src/foo.clue", 3, 1));
+	/// let (_, map) = SourceMap::extract(&code, "main.lua");
+	///
+	/// assert_eq!(
+	///     map.rewrite_trace("lua: ./main.lua:2: oops"),
+	///     "lua: src/foo.clue:3: oops"
+	/// );
+	/// ```
+	pub fn rewrite_trace(&self, trace: &str) -> String {
+		if self.file.is_empty() {
+			return trace.to_owned();
+		}
+		let mut result = String::with_capacity(trace.len());
+		let mut rest = trace;
+		while let Some(found) = rest.find(&self.file) {
+			let after = &rest[found + self.file.len()..];
+			let digits = after.strip_prefix(':').map_or(0, |after| {
+				after.bytes().take_while(u8::is_ascii_digit).count()
+			});
+			let start = rest[..found]
+				.rfind(|c: char| c.is_whitespace() || "\"'()[]<>,".contains(c))
+				.map_or(0, |i| i + 1);
+			let path = &rest[start..found];
+			let location = if digits == 0 || !(path.is_empty() || path.ends_with(['/', '\\'])) {
+				None
+			} else {
+				after[1..=digits]
+					.parse()
+					.ok()
+					.and_then(|line| self.lookup_nearest(line))
+			};
+			match location {
+				Some((source, line)) => {
+					result += &rest[..start];
+					write!(result, "{source}:{line}").unwrap();
+					rest = &after[digits + 1..];
+				}
+				None => {
+					let end = found + self.file.len();
+					result += &rest[..end];
+					rest = &rest[end..];
+				}
+			}
+		}
+		result += rest;
+		result
+	}
+
+	/// Loads a [`SourceMap`] from Source Map v3 JSON, like the one created by [`SourceMap::to_json`]
+	///
+	/// # Errors
+	/// If the JSON is not a valid source map an [`Err`] containing a [`Diagnostic`] will be returned
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::sourcemap::SourceMap;
+	///
+	/// let json = r#"{"version":3,"file":"main.lua","sources":["main.clue"],"names":[],"mappings":";AAEA"}"#;
+	/// let map = SourceMap::from_json(json, "main.lua.map").unwrap();
+	///
+	/// assert_eq!(map.lookup(2), Some(("main.clue", 3)));
+	/// assert_eq!(map.to_json(), json);
+	/// ```
+	#[cfg(feature = "serde")]
+	pub fn from_json(json: &str, filename: &str) -> Result<Self, Diagnostic> {
+		let invalid = |msg: &str| Diagnostic::error("F0002", msg, filename);
+		let RawSourceMap {
+			file,
+			sources,
+			mappings,
+		} = serde_json::from_str(json)
+			.map_err(|error| invalid(&format!("Invalid source map: {error}")))?;
+		let mut lines = Vec::new();
+		// source, line and column, the generated column is not needed
+		let mut state = [0i64; 3];
+		for line in mappings.split(';') {
+			let mut mapping = None;
+			for segment in line.split(',').filter(|segment| !segment.is_empty()) {
+				let values = decode_vlq(segment).ok_or_else(|| invalid("Invalid mappings"))?;
+				for (state, value) in state.iter_mut().zip(values.iter().skip(1)) {
+					*state += value;
+				}
+				if mapping.is_none() && values.len() >= 4 {
+					let [source, line, column] = state;
+					if source < 0 || source as usize >= sources.len() || line < 0 || column < 0 {
+						return Err(invalid("Invalid mappings"));
+					}
+					mapping = Some(Mapping {
+						source: source as usize,
+						line: line as usize + 1,
+						column: column as usize + 1,
+					});
+				}
+			}
+			lines.push(mapping);
+		}
+		Ok(SourceMap {
+			file,
+			sources,
+			lines,
+		})
+	}
+
+	/// Serializes the [`SourceMap`] as Source Map v3 JSON
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::sourcemap::{marker, SourceMap};
+	///
+	/// let code = format!("\n{}print(1);", marker("main.clue", 3, 5));
+	/// let (_, map) = SourceMap::extract(&code, "main.lua");
+	///
+	/// assert_eq!(
+	///     map.to_json(),
+	///     r#"{"version":3,"file":"main.lua","sources":["main.clue"],"names":[],"mappings":";AAEI"}"#
+	/// );
+	/// ```
+	pub fn to_json(&self) -> String {
+		let mut mappings = String::with_capacity(self.lines.len() * 5);
+		let (mut source, mut line, mut column) = (0, 0, 0);
+		for (i, mapping) in self.lines.iter().enumerate() {
+			if i > 0 {
+				mappings.push(';');
+			}
+			if let Some(mapping) = mapping {
+				encode_vlq(&mut mappings, 0);
+				encode_vlq(&mut mappings, mapping.source as i64 - source);
+				encode_vlq(&mut mappings, (mapping.line - 1) as i64 - line);
+				encode_vlq(&mut mappings, (mapping.column - 1) as i64 - column);
+				source = mapping.source as i64;
+				line = (mapping.line - 1) as i64;
+				column = (mapping.column - 1) as i64;
+			}
+		}
+		let sources = self
+			.sources
+			.iter()
+			.map(|source| escape_json(source))
+			.collect::<Vec<String>>()
+			.join(",");
+		format!(
+			"{{\"version\":3,\"file\":{},\"sources\":[{sources}],\"names\":[],\"mappings\":\"{mappings}\"}}",
+			escape_json(&self.file)
+		)
+	}
+}
+
+fn encode_vlq(result: &mut String, value: i64) {
+	let mut value = if value < 0 {
+		((-value) << 1) | 1
+	} else {
+		value << 1
+	};
+	loop {
+		let mut digit = value & 0b11111;
+		value >>= 5;
+		if value > 0 {
+			digit |= 0b100000;
+		}
+		result.push(BASE64[digit as usize] as char);
+		if value == 0 {
+			break;
+		}
+	}
+}
+
+#[cfg(feature = "serde")]
+fn decode_vlq(segment: &str) -> Option<Vec<i64>> {
+	let mut values = Vec::with_capacity(4);
+	let (mut value, mut shift) = (0i64, 0);
+	for c in segment.bytes() {
+		let digit = BASE64.iter().position(|&b| b == c)? as i64;
+		value += (digit & 0b11111) << shift;
+		if digit & 0b100000 == 0 {
+			values.push(if value & 1 == 1 {
+				-(value >> 1)
+			} else {
+				value >> 1
+			});
+			(value, shift) = (0, 0);
+		} else {
+			shift += 5;
+		}
+	}
+	(shift == 0).then_some(values)
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+/// The fields of a Source Map v3 that are read back, the others are ignored
+struct RawSourceMap {
+	#[serde(default)]
+	file: String,
+
+	#[serde(default)]
+	sources: Vec<String>,
+
+	mappings: String,
+}
+
+pub(crate) fn escape_json(string: &str) -> String {
+	let mut result = String::with_capacity(string.len() + 2);
+	result.push('"');
+	for c in string.chars() {
+		match c {
+			'"' => result += "\\\"",
+			'\\' => result += "\\\\",
+			'\n' => result += "\\n",
+			'\r' => result += "\\r",
+			'\t' => result += "\\t",
+			c if c.is_control() => write!(result, "\\u{:04x}", c as u32).unwrap(),
+			c => result.push(c),
+		}
+	}
+	result.push('"');
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn extract_unterminated_marker() {
+		let code = format!(
+			"{}print(1); -- \u{1}main.clue:4\nprint(2);",
+			marker("main.clue", 3, 1)
+		);
+		let (code, map) = SourceMap::extract(&code, "main.lua");
+		assert_eq!(code, "print(1); -- \u{1}main.clue:4\nprint(2);");
+		assert_eq!(map.lookup(1), Some(("main.clue", 3)));
+		assert_eq!(map.lookup(2), None);
+	}
+
+	#[test]
+	fn statement_columns() {
+		use crate::{
+			compiler::Compiler,
+			env::Options,
+			parser::parse_tokens,
+			preprocessor::{preprocess_code, preprocess_codes},
+			scanner::scan_code,
+		};
+		let options = Options {
+			env_sourcemap: true,
+			..Options::default()
+		};
+		let filename = String::from("main.clue");
+		let mut code = b"local x = 1\nif x {\n\t\tprint(x)\n}".to_vec();
+		let (codes, variables, ..) =
+			preprocess_code(&mut code, 1, false, &filename, &options).unwrap();
+		let code = preprocess_codes(0, codes, &variables, &filename).unwrap();
+		let tokens = scan_code(code, &filename).unwrap();
+		let columns = line_columns(&tokens);
+		let (ctokens, _) = parse_tokens(tokens, &filename, &options).unwrap();
+		let code = Compiler::new(&options, &filename)
+			.with_columns(&columns)
+			.compile_tokens(0, ctokens)
+			.unwrap();
+		let (code, map) = SourceMap::extract(&code, "main.lua");
+		let print = code
+			.lines()
+			.position(|line| line.contains("print"))
+			.unwrap();
+		let mapping = map.lines[print].unwrap();
+		assert_eq!((mapping.line, mapping.column), (3, 3));
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn json_fields() {
+		let json = r#"{"names":["mappings"],"version":3,"sources":["a.clue"],"mappings":"AAAE"}"#;
+		let map = SourceMap::from_json(json, "a.lua.map").unwrap();
+		assert_eq!(map.lines[0].unwrap().column, 3);
+		assert!(SourceMap::from_json(r#"{"sources":[1],"mappings":""}"#, "a.lua.map").is_err());
+	}
+}
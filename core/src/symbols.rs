@@ -0,0 +1,598 @@
+//! The symbol table of a file, listing its functions, methods, variables, enums, structs, statics,
+//! macros and imports, so that editor plugins and documentation generators don't have to parse it again.
+//!
+//! It's built from the [`Token`]s of the preprocessed file and its [`PPVars`],
+//! so it can be created even if the file fails to parse.
+//! Locals are only listed if they are declared at the top level of the file,
+//! while functions, globals and statics are listed wherever they are declared.
+
+use crate::{
+	diagnostic::Span,
+	preprocessor::{PPVar, PPVars},
+	scanner::{
+		Token,
+		TokenType::{self, *},
+	},
+};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+/// What a [`Symbol`] is
+pub enum SymbolKind {
+	/// A function declared with `fn`
+	Function,
+
+	/// A function declared with `method`
+	Method,
+
+	/// A variable
+	Variable,
+
+	/// A value of an enum
+	Enum,
+
+	/// A struct declared with `struct`, its fields are listed as the arguments of its constructor
+	Struct,
+
+	/// A macro declared with `@macro`
+	Macro,
+
+	/// A preprocessor variable declared with `@define`
+	Define,
+
+	/// A module imported with `@import`
+	Import,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+/// Where a [`Symbol`] can be used
+pub enum Scope {
+	/// Declared with `local`
+	Local,
+
+	/// Declared with `global` or `method`
+	Global,
+
+	/// Declared with `static`
+	Static,
+
+	/// Declared by a preprocessor directive
+	Preprocessor,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// An argument of a function, method or macro
+pub struct Argument {
+	/// The name of the argument, `...` for variadic arguments
+	pub name: String,
+
+	/// The line where the name of the argument is
+	pub line: usize,
+
+	/// The column where the name of the argument is,
+	/// the variadic arguments of macros have the position of the macro
+	pub column: usize,
+
+	/// The code of the default value of the argument, if it has one
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// A declaration found in a file
+pub struct Symbol {
+	/// The name of the symbol, methods include the table they are stored in (e.g. `a.b:c`)
+	pub name: String,
+
+	/// What the symbol is
+	pub kind: SymbolKind,
+
+	/// Where the symbol can be used
+	pub scope: Scope,
+
+	/// The line where the name of the symbol is
+	pub line: usize,
+
+	/// The column where the name of the symbol is
+	pub column: usize,
+
+	/// The whole declaration, including the body of functions and enums
+	pub span: Span,
+
+	/// The arguments of functions, methods and macros and the fields of structs
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub args: Option<Vec<Argument>>,
+
+	/// The value of enums and preprocessor variables, or the imported module
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// Every symbol declared in a file, sorted by their position
+pub struct SymbolTable {
+	/// The name of the file
+	pub file: String,
+
+	/// The symbols of the file
+	pub symbols: Vec<Symbol>,
+}
+
+/// Returns the index of the bracket closing the one at `open`, if there is one
+pub fn closing_bracket(tokens: &[Token], open: usize) -> Option<usize> {
+	let open_kind = tokens[open].kind;
+	let close_kind = match open_kind {
+		ROUND_BRACKET_OPEN => ROUND_BRACKET_CLOSED,
+		SQUARE_BRACKET_OPEN => SQUARE_BRACKET_CLOSED,
+		CURLY_BRACKET_OPEN => CURLY_BRACKET_CLOSED,
+		_ => return None,
+	};
+	let mut depth = 0usize;
+	for (i, token) in tokens.iter().enumerate().skip(open) {
+		if token.kind == open_kind {
+			depth += 1;
+		} else if token.kind == close_kind {
+			depth -= 1;
+			if depth == 0 {
+				return Some(i);
+			}
+		}
+	}
+	None
+}
+
+/// Rebuilds the code of the given tokens, keeping the spaces that were between them
+fn code(tokens: &[Token]) -> String {
+	let mut code = String::new();
+	let mut last: Option<&Token> = None;
+	for token in tokens {
+		if let Some(last) = last {
+			if last.line != token.line || last.column + last.lexeme.len() != token.column {
+				code.push(' ');
+			}
+		}
+		code += &token.lexeme;
+		last = Some(token);
+	}
+	code
+}
+
+struct SymbolsInfo<'a> {
+	tokens: &'a [Token],
+	symbols: Vec<Symbol>,
+}
+
+impl<'a> SymbolsInfo<'a> {
+	fn is(&self, i: usize, kind: TokenType) -> bool {
+		self.tokens.get(i).is_some_and(|token| token.kind == kind)
+	}
+
+	fn span(&self, start: usize, end: usize) -> Span {
+		let (start, end) = (&self.tokens[start], &self.tokens[end]);
+		Span::new(
+			start.line,
+			start.column,
+			end.line,
+			end.column + end.lexeme.len(),
+		)
+	}
+
+	fn push(
+		&mut self,
+		name: usize,
+		kind: SymbolKind,
+		scope: Scope,
+		span: (usize, usize),
+		args: Option<Vec<Argument>>,
+		value: Option<String>,
+	) {
+		let token = &self.tokens[name];
+		self.symbols.push(Symbol {
+			name: token.lexeme.clone(),
+			kind,
+			scope,
+			line: token.line,
+			column: token.column,
+			span: self.span(span.0, span.1),
+			args,
+			value,
+		});
+	}
+
+	/// Reads the arguments starting with the bracket at `open`
+	/// and returns them with the index of the token that closes the body of the function
+	fn function(&self, open: usize) -> (Vec<Argument>, Option<usize>) {
+		let Some(close) = closing_bracket(self.tokens, open) else {
+			return (Vec::new(), None);
+		};
+		let mut args = Vec::new();
+		let mut depth = 0usize;
+		let mut default_start = None;
+		for i in open + 1..close {
+			match self.tokens[i].kind {
+				ROUND_BRACKET_OPEN | SQUARE_BRACKET_OPEN | CURLY_BRACKET_OPEN => depth += 1,
+				ROUND_BRACKET_CLOSED | SQUARE_BRACKET_CLOSED | CURLY_BRACKET_CLOSED => depth -= 1,
+				IDENTIFIER | THREEDOTS
+					if depth == 0
+						&& matches!(self.tokens[i - 1].kind, ROUND_BRACKET_OPEN | COMMA) =>
+				{
+					args.push(Argument {
+						name: self.tokens[i].lexeme.clone(),
+						line: self.tokens[i].line,
+						column: self.tokens[i].column,
+						default: None,
+					});
+				}
+				DEFINE if depth == 0 => default_start = Some(i + 1),
+				COMMA if depth == 0 => {
+					if let (Some(start), Some(arg)) = (default_start.take(), args.last_mut()) {
+						arg.default = Some(code(&self.tokens[start..i]));
+					}
+				}
+				_ => {}
+			}
+		}
+		if let (Some(start), Some(arg)) = (default_start, args.last_mut()) {
+			arg.default = Some(code(&self.tokens[start..close]));
+		}
+		// the return type cannot contain curly brackets
+		let body = match self.is(close + 1, COLON) {
+			true => (close + 2..self.tokens.len()).find(|&i| self.is(i, CURLY_BRACKET_OPEN)),
+			false => Some(close + 1),
+		};
+		let end = if let Some(body) = body.filter(|&body| self.is(body, CURLY_BRACKET_OPEN)) {
+			closing_bracket(self.tokens, body)
+		} else {
+			Some(close)
+		};
+		(args, end)
+	}
+
+	fn enums(&mut self, start: usize, open: usize, scope: Scope) {
+		let close = closing_bracket(self.tokens, open).unwrap_or(self.tokens.len() - 1);
+		let mut n = 0i64;
+		let mut i = open + 1;
+		while i < close {
+			if self.tokens[i].kind == IDENTIFIER {
+				let name = i;
+				if self.is(i + 1, DEFINE) {
+					i += 2;
+					let negative = self.is(i, MINUS);
+					if negative {
+						i += 1;
+					}
+					if let Ok(value) = self.tokens[i].lexeme.parse::<i64>() {
+						n = if negative { -value } else { value };
+					}
+				} else {
+					n += 1;
+				}
+				let value = Some(n.to_string());
+				self.push(name, SymbolKind::Enum, scope, (start, close), None, value);
+			}
+			i += 1;
+		}
+	}
+
+	/// Reads the fields of the struct whose body starts with the bracket at `open`
+	/// and returns them with the index of the bracket that closes the body
+	fn fields(&self, open: usize) -> (Vec<Argument>, usize) {
+		let close = closing_bracket(self.tokens, open).unwrap_or(self.tokens.len() - 1);
+		let mut fields: Vec<Argument> = Vec::new();
+		let mut depth = 0usize;
+		let mut default_start = None;
+		let mut meta = false;
+		for i in open + 1..close {
+			let (prev, token) = (&self.tokens[i - 1], &self.tokens[i]);
+			// entries are separated by commas or new lines
+			let entry = depth == 0
+				&& (matches!(prev.kind, CURLY_BRACKET_OPEN | COMMA)
+					|| prev.line < token.line
+						&& !matches!(prev.kind, DEFINE | COLON | META | WITH));
+			if entry {
+				if let (Some(start), Some(field)) = (default_start.take(), fields.last_mut()) {
+					let end = if prev.kind == COMMA { i - 1 } else { i };
+					field.default = Some(code(&self.tokens[start..end]));
+				}
+				meta = token.kind == META;
+			}
+			match token.kind {
+				ROUND_BRACKET_OPEN | SQUARE_BRACKET_OPEN | CURLY_BRACKET_OPEN => depth += 1,
+				ROUND_BRACKET_CLOSED | SQUARE_BRACKET_CLOSED | CURLY_BRACKET_CLOSED => depth -= 1,
+				IDENTIFIER if entry && !meta => fields.push(Argument {
+					name: token.lexeme.clone(),
+					line: token.line,
+					column: token.column,
+					default: None,
+				}),
+				DEFINE if depth == 0 && !meta && default_start.is_none() => {
+					default_start = Some(i + 1)
+				}
+				_ => {}
+			}
+		}
+		if let (Some(start), Some(field)) = (default_start, fields.last_mut()) {
+			let end = if self.is(close - 1, COMMA) {
+				close - 1
+			} else {
+				close
+			};
+			field.default = Some(code(&self.tokens[start..end]));
+		}
+		(fields, close)
+	}
+
+	fn declaration(&mut self, i: usize, scope: Scope, top_level: bool) {
+		let Some(next) = self.tokens.get(i + 1) else {
+			return;
+		};
+		match next.kind {
+			FN if self.is(i + 2, IDENTIFIER) => {
+				let (args, end) = if self.is(i + 3, ROUND_BRACKET_OPEN) {
+					self.function(i + 3)
+				} else {
+					(Vec::new(), None)
+				};
+				let end = end.unwrap_or(i + 2);
+				self.push(
+					i + 2,
+					SymbolKind::Function,
+					scope,
+					(i, end),
+					Some(args),
+					None,
+				);
+			}
+			STRUCT if self.is(i + 2, IDENTIFIER) => {
+				// skip the parent of a class
+				let open = if self.is(i + 3, COLON) { i + 5 } else { i + 3 };
+				let (fields, end) = if self.is(open, CURLY_BRACKET_OPEN) {
+					self.fields(open)
+				} else {
+					(Vec::new(), i + 2)
+				};
+				self.push(
+					i + 2,
+					SymbolKind::Struct,
+					scope,
+					(i, end),
+					Some(fields),
+					None,
+				);
+			}
+			_ if scope == Scope::Local && !top_level => {}
+			ENUM if self.is(i + 2, CURLY_BRACKET_OPEN) => self.enums(i, i + 2, scope),
+			CURLY_BRACKET_OPEN => {
+				let close = closing_bracket(self.tokens, i + 1).unwrap_or(self.tokens.len() - 1);
+				for name in i + 2..close {
+					if self.tokens[name].kind == IDENTIFIER && !self.is(name + 1, ARROW) {
+						self.push(name, SymbolKind::Variable, scope, (i, close), None, None);
+					}
+				}
+			}
+			IDENTIFIER => {
+				if scope == Scope::Local
+					&& self.is(i + 2, DEFINE)
+					&& matches!(self.tokens.get(i + 3), Some(token) if token.lexeme == "import" || token.lexeme == "require")
+					&& self.is(i + 4, ROUND_BRACKET_OPEN)
+					&& self.is(i + 5, STRING)
+					&& self.is(i + 6, ROUND_BRACKET_CLOSED)
+				{
+					let module = self.tokens[i + 5]
+						.lexeme
+						.trim_matches(['"', '\''])
+						.to_owned();
+					let value = Some(module);
+					self.push(i + 1, SymbolKind::Import, scope, (i, i + 6), None, value);
+					return;
+				}
+				let mut name = i + 1;
+				while self.is(name, IDENTIFIER) {
+					self.push(name, SymbolKind::Variable, scope, (i, name), None, None);
+					if !self.is(name + 1, COMMA) {
+						break;
+					}
+					name += 2;
+				}
+			}
+			_ => {}
+		}
+	}
+
+	fn method(&mut self, i: usize) {
+		let mut name = String::new();
+		let mut j = i + 1;
+		while let Some(token) = self.tokens.get(j) {
+			match token.kind {
+				IDENTIFIER | DOT => name += &token.lexeme,
+				DOUBLE_COLON => name.push(':'),
+				_ => break,
+			}
+			j += 1;
+		}
+		if name.is_empty() {
+			return;
+		}
+		let (args, end) = if self.is(j, ROUND_BRACKET_OPEN) {
+			self.function(j)
+		} else {
+			(Vec::new(), None)
+		};
+		let end = end.unwrap_or(j - 1);
+		self.push(
+			j - 1,
+			SymbolKind::Method,
+			Scope::Global,
+			(i, end),
+			Some(args),
+			None,
+		);
+		self.symbols.last_mut().unwrap().name = name;
+	}
+
+	/// Lists a method, a function or the constructor declared inside a class,
+	/// the constructor is listed as the `new` function of the class
+	fn member(&mut self, i: usize, class: &str, scope: Scope) {
+		let (kind, name, open) = match self.tokens[i].kind {
+			CONSTRUCTOR => (SymbolKind::Function, format!("{class}.new"), i + 1),
+			METHOD if self.is(i + 1, IDENTIFIER) => {
+				let name = format!("{class}:{}", self.tokens[i + 1].lexeme);
+				(SymbolKind::Method, name, i + 2)
+			}
+			FN if self.is(i + 1, IDENTIFIER) => {
+				let name = format!("{class}.{}", self.tokens[i + 1].lexeme);
+				(SymbolKind::Function, name, i + 2)
+			}
+			_ => return,
+		};
+		if !self.is(open, ROUND_BRACKET_OPEN) {
+			return;
+		}
+		let (args, end) = self.function(open);
+		let end = end.unwrap_or(open - 1);
+		self.push(open - 1, kind, scope, (i, end), Some(args), None);
+		self.symbols.last_mut().unwrap().name = name;
+	}
+}
+
+impl SymbolTable {
+	/// Creates the [`SymbolTable`] of a file given its name,
+	/// the [`Token`]s of the preprocessed code and the preprocessor variables defined by the file itself
+	///
+	/// # Example
+	/// ```rust
+	/// use clue_core::{
+	///     diagnostic::Diagnostic, env::Options, preprocessor::*, scanner::*, symbols::*,
+	/// };
+	///
+	/// fn main() -> Result<(), Vec<Diagnostic>> {
+	///     let options = Options::default();
+	///     let filename = String::from("example.clue");
+	///     let mut code = String::from("@macro double(x) { $x * 2 }\nlocal fn f(a, b = 1) {}");
+	///
+	///     let (codes, variables, ..) = preprocess_code(
+	///         unsafe { code.as_bytes_mut() },
+	///         1,
+	///         false,
+	///         &filename,
+	///         &options,
+	///     )?;
+	///     let tokens = scan_code(preprocess_codes(0, codes, &variables, &filename)?, &filename)?;
+	///     let table = SymbolTable::new(&filename, &tokens, &variables);
+	///
+	///     assert_eq!(table.symbols[0].kind, SymbolKind::Macro);
+	///     assert_eq!(table.symbols[1].name, "f");
+	///     assert_eq!(table.symbols[1].args.as_ref().unwrap()[1].default.as_deref(), Some("1"));
+	///
+	///     Ok(())
+	/// }
+	/// ```
+	pub fn new(file: impl Into<String>, tokens: &[Token], variables: &PPVars) -> Self {
+		let mut info = SymbolsInfo {
+			tokens,
+			symbols: Vec::new(),
+		};
+		let mut depth = 0usize;
+		// the name, scope and body depth of the structs the current token is in
+		let mut classes: Vec<(&str, Scope, usize)> = Vec::new();
+		for (i, token) in tokens.iter().enumerate() {
+			match token.kind {
+				CURLY_BRACKET_OPEN => depth += 1,
+				CURLY_BRACKET_CLOSED => {
+					depth = depth.saturating_sub(1);
+					if classes.last().is_some_and(|&(.., body)| depth < body) {
+						classes.pop();
+					}
+				}
+				LOCAL => info.declaration(i, Scope::Local, depth == 0),
+				GLOBAL => info.declaration(i, Scope::Global, depth == 0),
+				STATIC => info.declaration(i, Scope::Static, depth == 0),
+				STRUCT if info.is(i + 1, IDENTIFIER) => {
+					let scope = match i.checked_sub(1).map(|i| tokens[i].kind) {
+						Some(GLOBAL) => Scope::Global,
+						Some(STATIC) => Scope::Static,
+						_ => Scope::Local,
+					};
+					classes.push((&tokens[i + 1].lexeme, scope, depth + 1));
+				}
+				METHOD | FN | CONSTRUCTOR
+					if classes.last().is_some_and(|&(.., body)| body == depth) =>
+				{
+					let &(class, scope, _) = classes.last().unwrap();
+					info.member(i, class, scope);
+				}
+				METHOD => info.method(i),
+				_ => {}
+			}
+		}
+		let mut symbols = info.symbols;
+		for (name, variable) in variables {
+			let Some(&(_, line, column)) = name.iter().next() else {
+				continue;
+			};
+			let (kind, args, value) = match variable {
+				PPVar::Simple(value) | PPVar::ToProcess(value) => {
+					(SymbolKind::Define, None, Some(value.to_string()))
+				}
+				PPVar::Macro { args, vararg, .. } => {
+					let mut args: Vec<Argument> = args
+						.iter()
+						.filter_map(|arg| {
+							let &(_, line, column) = arg.iter().next()?;
+							Some(Argument {
+								name: arg.to_string(),
+								line,
+								column,
+								default: None,
+							})
+						})
+						.collect();
+					if *vararg {
+						args.push(Argument {
+							name: String::from("..."),
+							line,
+							column,
+							default: None,
+						});
+					}
+					(SymbolKind::Macro, Some(args), None)
+				}
+				PPVar::VarArgs(_) => continue,
+			};
+			symbols.push(Symbol {
+				name: name.to_string(),
+				kind,
+				scope: Scope::Preprocessor,
+				line,
+				column,
+				span: Span::sized(line, column, name.len()),
+				args,
+				value,
+			});
+		}
+		symbols.sort_by_key(|symbol| (symbol.line, symbol.column));
+		Self {
+			file: file.into(),
+			symbols,
+		}
+	}
+
+	#[cfg(feature = "lsp")]
+	/// Converts the [`SymbolTable`] into the JSON printed by the `--symbols` flag
+	pub fn to_json(&self) -> String {
+		serde_json::json!({
+			"type": "SymbolTable",
+			"value": self,
+		})
+		.to_string()
+	}
+}
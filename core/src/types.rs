@@ -0,0 +1,346 @@
+//! The `types` module contains the [`LuaType`]s used by the optional type checking
+//!
+//! Names can be annotated with a type after a `:`, like `local x: number = 1`,
+//! `fn add(a: number, b: number = 0): number {...}` or `global print: fn(...)`,
+//! and the annotations are always removed from the compiled code.
+//! When [`TypesMode`](crate::env::TypesMode) is not `NONE` the parser checks the values assigned to annotated names,
+//! the arguments of calls and the returned values, inferring the types of literals, enums and operators;
+//! the globals of the standard library chosen with [`LuaSTD`] are already declared
+//! and the ones given by the host program can be declared with `extern name: type` or `extern fn path(args): type`.
+//!
+//! The types are `any`, `nil`, `boolean`, `number`, `string`, `table`, `thread`, `userdata`,
+//! `fn` (any function) and `fn(number, string?, ...): number` (a function with a known signature),
+//! a `?` after a type makes it also accept `nil` and `|` joins more types together.
+
+#![allow(clippy::upper_case_acronyms)]
+
+use crate::{
+	code::Code,
+	diagnostic::Diagnostic,
+	env::LuaSTD,
+	scanner::{scan_code, Token, TokenType::*},
+};
+use ahash::AHashMap;
+use std::{fmt, sync::OnceLock};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// The type of a Clue value
+pub enum LuaType {
+	/// Any value, it is never checked
+	ANY,
+
+	/// `nil`
+	NIL,
+
+	/// `true` or `false`
+	BOOLEAN,
+
+	/// A number
+	NUMBER,
+
+	/// A string
+	STRING,
+
+	/// A table
+	TABLE,
+
+	/// A function, with its signature if it is known
+	FUNCTION(Option<Box<FunctionType>>),
+
+	/// A coroutine
+	THREAD,
+
+	/// A userdata, like the files of the `io` library
+	USERDATA,
+
+	/// Any of the given types
+	UNION(Vec<LuaType>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+/// The signature of a function
+pub struct FunctionType {
+	/// The types of the arguments
+	pub args: Vec<LuaType>,
+
+	/// Whether the function takes more arguments after `args`, with `...`
+	pub variadic: bool,
+
+	/// The type of the (first) returned value
+	pub returns: LuaType,
+}
+
+impl LuaType {
+	/// Creates a [`LuaType`] accepting any of the given types
+	pub fn union(types: Vec<LuaType>) -> Self {
+		let mut result: Vec<LuaType> = Vec::with_capacity(types.len());
+		for luatype in types {
+			match luatype {
+				LuaType::ANY => return LuaType::ANY,
+				LuaType::UNION(types) => {
+					for luatype in types {
+						if !result.contains(&luatype) {
+							result.push(luatype)
+						}
+					}
+				}
+				luatype if !result.contains(&luatype) => result.push(luatype),
+				_ => {}
+			}
+		}
+		match result.len() {
+			0 => LuaType::ANY,
+			1 => result.pop().unwrap(),
+			_ => LuaType::UNION(result),
+		}
+	}
+
+	/// Makes the type accept `nil` too
+	pub fn optional(self) -> Self {
+		if self.accepts(&LuaType::NIL) {
+			self
+		} else {
+			LuaType::union(vec![self, LuaType::NIL])
+		}
+	}
+
+	/// Checks if a value of type `other` can be used where this type is expected
+	/// The signatures of functions are not compared
+	///
+	/// # Examples
+	/// ```
+	/// use clue_core::types::LuaType;
+	///
+	/// let number = LuaType::NUMBER.optional();
+	/// assert!(number.accepts(&LuaType::NIL));
+	/// assert!(number.accepts(&LuaType::ANY));
+	/// assert!(!number.accepts(&LuaType::STRING));
+	/// assert!(!LuaType::NUMBER.accepts(&number));
+	/// ```
+	pub fn accepts(&self, other: &LuaType) -> bool {
+		match (self, other) {
+			(LuaType::ANY, _) | (_, LuaType::ANY) => true,
+			(_, LuaType::UNION(types)) => types.iter().all(|luatype| self.accepts(luatype)),
+			(LuaType::UNION(types), _) => types.iter().any(|luatype| luatype.accepts(other)),
+			(LuaType::FUNCTION(_), LuaType::FUNCTION(_)) => true,
+			_ => self == other,
+		}
+	}
+}
+
+impl fmt::Display for LuaType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			LuaType::ANY => f.write_str("any"),
+			LuaType::NIL => f.write_str("nil"),
+			LuaType::BOOLEAN => f.write_str("boolean"),
+			LuaType::NUMBER => f.write_str("number"),
+			LuaType::STRING => f.write_str("string"),
+			LuaType::TABLE => f.write_str("table"),
+			LuaType::THREAD => f.write_str("thread"),
+			LuaType::USERDATA => f.write_str("userdata"),
+			LuaType::FUNCTION(None) => f.write_str("fn"),
+			LuaType::FUNCTION(Some(signature)) => {
+				f.write_str("fn(")?;
+				let args = signature.args.iter().map(ToString::to_string);
+				let variadic = signature.variadic.then(|| String::from("..."));
+				let args: Vec<String> = args.chain(variadic).collect();
+				write!(f, "{})", args.join(", "))?;
+				match signature.returns {
+					LuaType::ANY => Ok(()),
+					ref returns => write!(f, ": {returns}"),
+				}
+			}
+			LuaType::UNION(types) => match &types[..] {
+				[luatype, LuaType::NIL] if !matches!(luatype, LuaType::FUNCTION(_)) => {
+					write!(f, "{luatype}?")
+				}
+				_ => {
+					let types: Vec<String> = types.iter().map(ToString::to_string).collect();
+					f.write_str(&types.join(" | "))
+				}
+			},
+		}
+	}
+}
+
+fn peek(tokens: &[Token], current: usize) -> &Token {
+	&tokens[current.min(tokens.len() - 1)]
+}
+
+fn type_error(token: &Token, message: String, filename: &str) -> Diagnostic {
+	Diagnostic::error("E0010", message, filename).at(token.line, token.column)
+}
+
+fn parse_function_type(
+	tokens: &[Token],
+	current: &mut usize,
+	filename: &str,
+) -> Result<LuaType, Diagnostic> {
+	if peek(tokens, *current).kind != ROUND_BRACKET_OPEN {
+		return Ok(LuaType::FUNCTION(None));
+	}
+	*current += 1;
+	let mut args = Vec::new();
+	let mut variadic = false;
+	if peek(tokens, *current).kind == ROUND_BRACKET_CLOSED {
+		*current += 1;
+	} else {
+		loop {
+			if peek(tokens, *current).kind == THREEDOTS {
+				*current += 1;
+				variadic = true;
+			} else {
+				args.push(parse_type(tokens, current, filename)?);
+			}
+			let t = peek(tokens, *current);
+			*current += 1;
+			match t.kind {
+				COMMA if !variadic => {}
+				ROUND_BRACKET_CLOSED => break,
+				_ => {
+					let expected = if variadic { "')'" } else { "',' or ')'" };
+					let message = format!(
+						"Expected {expected} in the function type, got '{}'",
+						t.lexeme
+					);
+					return Err(type_error(t, message, filename));
+				}
+			}
+		}
+	}
+	let returns = if peek(tokens, *current).kind == COLON {
+		*current += 1;
+		parse_type(tokens, current, filename)?
+	} else {
+		LuaType::ANY
+	};
+	Ok(LuaType::FUNCTION(Some(Box::new(FunctionType {
+		args,
+		variadic,
+		returns,
+	}))))
+}
+
+/// Reads a type from the tokens, starting from `tokens[*current]` and moving `current` after it
+pub(crate) fn parse_type(
+	tokens: &[Token],
+	current: &mut usize,
+	filename: &str,
+) -> Result<LuaType, Diagnostic> {
+	let mut types = Vec::new();
+	loop {
+		let t = peek(tokens, *current);
+		*current += 1;
+		let mut luatype = match (t.kind, t.lexeme.as_str()) {
+			(NIL, _) => LuaType::NIL,
+			(FN, _) => parse_function_type(tokens, current, filename)?,
+			(IDENTIFIER, "any") => LuaType::ANY,
+			(IDENTIFIER, "boolean") => LuaType::BOOLEAN,
+			(IDENTIFIER, "number") => LuaType::NUMBER,
+			(IDENTIFIER, "string") => LuaType::STRING,
+			(IDENTIFIER, "table") => LuaType::TABLE,
+			(IDENTIFIER, "thread") => LuaType::THREAD,
+			(IDENTIFIER, "userdata") => LuaType::USERDATA,
+			(IDENTIFIER, name) => return Err(type_error(
+				t,
+				format!("Unknown type '{name}'"),
+				filename,
+			)
+			.with_help(
+				"the types are any, nil, boolean, number, string, table, thread, userdata and fn",
+			)),
+			(_, lexeme) => {
+				return Err(type_error(
+					t,
+					format!("Expected a type, got '{lexeme}'"),
+					filename,
+				))
+			}
+		};
+		if peek(tokens, *current).kind == QUESTION_MARK {
+			*current += 1;
+			luatype = luatype.optional();
+		}
+		types.push(luatype);
+		if peek(tokens, *current).kind != BIT_OR {
+			break Ok(LuaType::union(types));
+		}
+		*current += 1;
+	}
+}
+
+/// Reads a list of declarations, every line is either empty, a comment starting with `//`
+/// or a name (which can contain dots) followed by `:` and its type
+fn parse_declarations(declarations: &str, filename: &str) -> AHashMap<String, LuaType> {
+	let mut result = AHashMap::new();
+	let filename = String::from(filename);
+	for (i, line) in declarations.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with("//") {
+			continue;
+		}
+		let (name, luatype) = line
+			.split_once(':')
+			.unwrap_or_else(|| panic!("{filename}:{}: missing ':'", i + 1));
+		let tokens = scan_code(Code::from((luatype, i + 1, name.len() + 2)), &filename)
+			.unwrap_or_else(|errors| panic!("{}", errors[0]));
+		let mut current = 0;
+		let luatype =
+			parse_type(&tokens, &mut current, &filename).unwrap_or_else(|error| panic!("{error}"));
+		result.insert(name.trim().to_owned(), luatype);
+	}
+	result
+}
+
+/// Returns the globals declared by the standard library
+///
+/// # Examples
+/// ```
+/// use clue_core::{env::LuaSTD, types::{std_declarations, LuaType}};
+///
+/// let globals = std_declarations(LuaSTD::LUA54);
+/// assert_eq!(globals["math.pi"], LuaType::NUMBER);
+/// assert_eq!(globals["tostring"].to_string(), "fn(any): string");
+/// assert!(std_declarations(LuaSTD::NONE).is_empty());
+/// ```
+pub fn std_declarations(std: LuaSTD) -> &'static AHashMap<String, LuaType> {
+	static NONE: OnceLock<AHashMap<String, LuaType>> = OnceLock::new();
+	static LUAJIT: OnceLock<AHashMap<String, LuaType>> = OnceLock::new();
+	static LUA54: OnceLock<AHashMap<String, LuaType>> = OnceLock::new();
+	match std {
+		LuaSTD::NONE => NONE.get_or_init(AHashMap::new),
+		LuaSTD::LUAJIT => LUAJIT.get_or_init(|| {
+			parse_declarations(include_str!("../std/luajit.types"), "luajit.types")
+		}),
+		LuaSTD::LUA54 => LUA54
+			.get_or_init(|| parse_declarations(include_str!("../std/lua54.types"), "lua54.types")),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn std_declarations_are_valid() {
+		for std in [LuaSTD::LUAJIT, LuaSTD::LUA54] {
+			let globals = std_declarations(std);
+			assert_eq!(
+				globals["string.format"].to_string(),
+				"fn(string, ...): string"
+			);
+			assert_eq!(
+				globals["io.open"].to_string(),
+				"fn(string, string?): userdata?"
+			);
+		}
+		assert!(std_declarations(LuaSTD::LUAJIT).contains_key("bit.band"));
+		assert!(!std_declarations(LuaSTD::LUA54).contains_key("bit.band"));
+	}
+}
@@ -0,0 +1,68 @@
+//! Completion of keywords, preprocessor directives and preprocessor variables
+
+use crate::document::Document;
+use clue_core::{preprocessor::PPVar, scanner::keywords};
+use serde_json::{json, Value};
+
+// https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#completionItemKind
+const FUNCTION_KIND: u8 = 3;
+const KEYWORD_KIND: u8 = 14;
+const CONSTANT_KIND: u8 = 21;
+
+/// Every directive understood by the preprocessor
+const DIRECTIVES: &[&str] = &[
+	"ifos",
+	"iflua",
+	"ifdef",
+	"ifndef",
+	"ifcmp",
+	"if",
+	"else",
+	"else_ifos",
+	"else_iflua",
+	"else_ifdef",
+	"else_ifndef",
+	"else_ifcmp",
+	"else_if",
+	"import",
+	"version",
+	"define",
+	"macro",
+	"error",
+	"print",
+];
+
+fn item(label: &str, kind: u8, detail: &str) -> Value {
+	json!({
+		"label": label,
+		"kind": kind,
+		"detail": detail,
+	})
+}
+
+/// Returns the LSP completion items for the given line and column:
+/// directives after `@`, preprocessor variables after `$` and keywords everywhere else
+pub fn completion(document: &Document, line: usize, column: usize) -> Vec<Value> {
+	let text = document.line(line);
+	let before = &text[..column.saturating_sub(1).min(text.len())];
+	let word = before.trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_');
+	if word.ends_with('@') {
+		DIRECTIVES
+			.iter()
+			.map(|directive| item(directive, KEYWORD_KIND, "directive"))
+			.collect()
+	} else if word.ends_with('$') {
+		document
+			.variables
+			.iter()
+			.map(|(name, value)| match value {
+				PPVar::Macro { .. } => item(&name.to_string(), FUNCTION_KIND, "macro"),
+				_ => item(&name.to_string(), CONSTANT_KIND, "define"),
+			})
+			.collect()
+	} else {
+		keywords()
+			.map(|keyword| item(keyword, KEYWORD_KIND, "keyword"))
+			.collect()
+	}
+}
@@ -0,0 +1,190 @@
+//! An open document and the result of analyzing it with [`clue_core`]
+//!
+//! Clue reports lines and columns starting from 1, with columns counted in bytes,
+//! while the Language Server Protocol counts both from 0, with columns in UTF-16 code units.
+
+use clue_core::{
+	diagnostic::{Diagnostic, Severity},
+	env::Options,
+	parser::parse_tokens,
+	preprocessor::{preprocess_code, preprocess_codes, PPVars},
+	scanner::{scan_code, Token, TokenType::IDENTIFIER},
+};
+use serde_json::{json, Value};
+
+/// A document opened by the client
+pub struct Document {
+	/// The current text of the document
+	pub text: String,
+
+	/// The name used for the document in diagnostics
+	pub filename: String,
+
+	/// The tokens of the preprocessed document, empty if the scanner failed
+	pub tokens: Vec<Token>,
+
+	/// The preprocessor variables and macros defined in the document
+	pub variables: PPVars,
+
+	/// Every error found while analyzing the document
+	pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+	/// Creates a new [`Document`] and analyzes it
+	pub fn new(text: String, filename: String) -> Self {
+		let mut document = Self {
+			text,
+			filename,
+			tokens: Vec::new(),
+			variables: PPVars::default(),
+			diagnostics: Vec::new(),
+		};
+		if let Err(diagnostics) = document.analyze() {
+			document.diagnostics = diagnostics;
+		}
+		document
+	}
+
+	fn analyze(&mut self) -> Result<(), Vec<Diagnostic>> {
+		let options = Options::default();
+		let mut code = self.text.clone().into_bytes();
+		let (codes, variables, _, _, warnings) =
+			preprocess_code(&mut code, 1, false, &self.filename, &options)?;
+		self.variables = variables;
+		let code = preprocess_codes(0, codes, &self.variables, &self.filename)?;
+		self.tokens = scan_code(code, &self.filename)?;
+		parse_tokens(self.tokens.clone(), &self.filename, &options)?;
+		self.diagnostics = warnings;
+		Ok(())
+	}
+
+	/// Returns the text of the given line, without the line ending
+	pub fn line(&self, line: usize) -> &str {
+		line.checked_sub(1)
+			.and_then(|line| self.text.split('\n').nth(line))
+			.map_or("", |text| text.strip_suffix('\r').unwrap_or(text))
+	}
+
+	/// Returns the byte offset of the given line and column inside the whole text
+	pub fn offset(&self, line: usize, column: usize) -> usize {
+		let start: usize = self
+			.text
+			.split_inclusive('\n')
+			.take(line.saturating_sub(1))
+			.map(str::len)
+			.sum();
+		(start + column.saturating_sub(1)).min(self.text.len())
+	}
+
+	/// Converts a Clue line and column into an LSP position
+	pub fn position(&self, line: usize, column: usize) -> Value {
+		let end = column.saturating_sub(1);
+		let character: usize = self
+			.line(line)
+			.char_indices()
+			.take_while(|(i, _)| *i < end)
+			.map(|(_, c)| c.len_utf16())
+			.sum();
+		json!({
+			"line": line.saturating_sub(1),
+			"character": character,
+		})
+	}
+
+	/// Converts an LSP position into a Clue line and column
+	pub fn location(&self, position: &Value) -> Option<(usize, usize)> {
+		let line = position["line"].as_u64()? as usize + 1;
+		let mut character = position["character"].as_u64()? as usize;
+		let text = self.line(line);
+		let column = text
+			.char_indices()
+			.find_map(|(i, c)| {
+				if character == 0 {
+					return Some(i);
+				}
+				character = character.saturating_sub(c.len_utf16());
+				None
+			})
+			.unwrap_or(text.len());
+		Some((line, column + 1))
+	}
+
+	/// Creates an LSP range going from `start` to `end`, both given as Clue lines and columns
+	pub fn range(&self, start: (usize, usize), end: (usize, usize)) -> Value {
+		let end = end.max(start);
+		json!({
+			"start": self.position(start.0, start.1),
+			"end": self.position(end.0, end.1),
+		})
+	}
+
+	/// Creates an LSP range covering the given token
+	pub fn token_range(&self, token: &Token) -> Value {
+		self.range(
+			(token.line, token.column),
+			(token.line, token.column + token.lexeme.len()),
+		)
+	}
+
+	/// Returns the index of the identifier token found at the given line and column
+	pub fn token_at(&self, line: usize, column: usize) -> Option<usize> {
+		self.tokens.iter().position(|token| {
+			token.kind == IDENTIFIER
+				&& token.line == line
+				&& (token.column..=token.column + token.lexeme.len()).contains(&column)
+		})
+	}
+
+	/// Returns the column where the word found at the given line and column starts, and the word itself,
+	/// looking directly at the text so that it also works inside preprocessor directives
+	pub fn word_at(&self, line: usize, column: usize) -> Option<(usize, &str)> {
+		let text = self.line(line);
+		let column = column.saturating_sub(1).min(text.len());
+		let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+		let start = text[..column]
+			.char_indices()
+			.rev()
+			.find(|(_, c)| !is_word(*c))
+			.map_or(0, |(i, c)| i + c.len_utf8());
+		let end = text[column..]
+			.find(|c| !is_word(c))
+			.map_or(text.len(), |i| column + i);
+		(start < end).then(|| (start + 1, &text[start..end]))
+	}
+
+	/// Converts the diagnostics of the document into LSP diagnostics
+	pub fn lsp_diagnostics(&self) -> Vec<Value> {
+		self.diagnostics
+			.iter()
+			.map(|diagnostic| {
+				let range = match &diagnostic.span {
+					Some(span) => {
+						self.range((span.line, span.column), (span.end_line, span.end_column))
+					}
+					None => self.range((1, 1), (1, 1)),
+				};
+				let mut message = diagnostic.message.clone();
+				for note in &diagnostic.notes {
+					message += "\nnote: ";
+					message += note;
+				}
+				for help in &diagnostic.help {
+					message += "\nhelp: ";
+					message += help;
+				}
+				json!({
+					"range": range,
+					"severity": match diagnostic.severity {
+						Severity::Error => 1,
+						Severity::Warning => 2,
+						Severity::Note => 3,
+					},
+					"code": diagnostic.code,
+					"source": "clue",
+					"message": message,
+				})
+			})
+			.collect()
+	}
+}
@@ -0,0 +1,20 @@
+//! # The Clue language server
+//! A Language Server Protocol server for Clue built on [`clue_core`],
+//! it communicates with the client using JSON-RPC over any reader and writer (usually stdin and stdout).
+//!
+//! It supports:
+//! - diagnostics for every error found by the preprocessor, the scanner and the parser
+//! - document symbols for everything listed by the [`SymbolTable`](clue_core::symbols::SymbolTable) of the document
+//! - go to definition for locals and for `@define` and `@macro` names
+//! - hover showing what macros and preprocessor variables expand to
+//! - completion of keywords, preprocessor directives and preprocessor variables
+
+
+mod completion;
+mod document;
+mod navigation;
+mod server;
+mod symbols;
+pub mod transport;
+
+pub use server::run;
@@ -0,0 +1,236 @@
+//! Go to definition for locals and preprocessor variables, and hover for macro expansions
+
+use crate::document::Document;
+use clue_core::{
+	code::Code,
+	preprocessor::{preprocess_variables, PPVar},
+	scanner::{
+		Token,
+		TokenType::{self, *},
+	},
+	symbols::closing_bracket,
+};
+use serde_json::{json, Value};
+
+/// Checks if the token at `i` exists and is of the given kind
+fn is_kind(tokens: &[Token], i: usize, kind: TokenType) -> bool {
+	tokens.get(i).is_some_and(|token| token.kind == kind)
+}
+
+/// Returns the indices of the identifiers separated by commas starting from `start`
+fn identifier_list(tokens: &[Token], start: usize) -> Vec<usize> {
+	let mut names = Vec::new();
+	let mut i = start;
+	while is_kind(tokens, i, IDENTIFIER) {
+		names.push(i);
+		if !is_kind(tokens, i + 1, COMMA) {
+			break;
+		}
+		i += 2;
+	}
+	names
+}
+
+/// Returns the indices of the names declared by the `local`, `global`, `static` or `extern` keyword at `i`
+fn declared_names(tokens: &[Token], i: usize) -> Vec<usize> {
+	let inside = |open: usize, filter: &dyn Fn(usize) -> bool| {
+		let close = closing_bracket(tokens, open).unwrap_or(tokens.len());
+		(open + 1..close)
+			.filter(|&j| tokens[j].kind == IDENTIFIER && filter(j))
+			.collect()
+	};
+	match tokens.get(i + 1).map(|token| token.kind) {
+		Some(FN | STRUCT) if is_kind(tokens, i + 2, IDENTIFIER) => vec![i + 2],
+		Some(ENUM) if is_kind(tokens, i + 2, CURLY_BRACKET_OPEN) => inside(i + 2, &|j| {
+			matches!(tokens[j - 1].kind, CURLY_BRACKET_OPEN | COMMA)
+		}),
+		Some(CURLY_BRACKET_OPEN) => inside(i + 1, &|j| !is_kind(tokens, j + 1, ARROW)),
+		_ => identifier_list(tokens, i + 1),
+	}
+}
+
+/// Returns the arguments of the function or method at `i` and the index of the bracket closing them
+fn arguments(tokens: &[Token], i: usize) -> Option<(Vec<usize>, usize)> {
+	let open = (i + 1..tokens.len())
+		.take_while(|&j| tokens[j].kind != CURLY_BRACKET_OPEN)
+		.find(|&j| tokens[j].kind == ROUND_BRACKET_OPEN)?;
+	let close = closing_bracket(tokens, open)?;
+	let mut names = Vec::new();
+	let mut depth = 0usize;
+	for j in open + 1..close {
+		match tokens[j].kind {
+			ROUND_BRACKET_OPEN | SQUARE_BRACKET_OPEN | CURLY_BRACKET_OPEN => depth += 1,
+			ROUND_BRACKET_CLOSED | SQUARE_BRACKET_CLOSED | CURLY_BRACKET_CLOSED => depth -= 1,
+			IDENTIFIER
+				if depth == 0 && matches!(tokens[j - 1].kind, ROUND_BRACKET_OPEN | COMMA) =>
+			{
+				names.push(j)
+			}
+			_ => {}
+		}
+	}
+	Some((names, close))
+}
+
+/// Returns the index of the token where the identifier at `target` is declared,
+/// following the scopes of the code blocks and falling back to global declarations
+fn resolve(tokens: &[Token], target: usize) -> Option<usize> {
+	let name = &tokens[target].lexeme;
+	if target > 0
+		&& matches!(
+			tokens[target - 1].kind,
+			DOT | SAFE_DOT | DOUBLE_COLON | SAFE_DOUBLE_COLON
+		) {
+		return None;
+	}
+	let mut scopes = vec![Vec::new()];
+	// the names declared by the next code block and the index it has to be at, if any
+	let mut pending: Option<(Vec<usize>, Option<usize>)> = None;
+	for (i, token) in tokens[..target].iter().enumerate() {
+		match token.kind {
+			CURLY_BRACKET_OPEN => {
+				let names = match pending.take() {
+					Some((names, None)) => names,
+					Some((names, Some(at))) if at == i => names,
+					Some((names, Some(at))) if i < at => {
+						pending = Some((names, Some(at)));
+						Vec::new()
+					}
+					_ => Vec::new(),
+				};
+				scopes.push(names);
+			}
+			CURLY_BRACKET_CLOSED if scopes.len() > 1 => {
+				scopes.pop();
+			}
+			LOCAL | STATIC => scopes.last_mut().unwrap().extend(declared_names(tokens, i)),
+			FN | METHOD => {
+				pending = arguments(tokens, i).map(|(names, close)| (names, Some(close + 1)))
+			}
+			FOR | CATCH => pending = Some((identifier_list(tokens, i + 1), None)),
+			_ => {}
+		}
+	}
+	scopes
+		.iter()
+		.rev()
+		.flat_map(|scope| scope.iter().rev())
+		.copied()
+		.find(|&i| i <= target && tokens[i].lexeme == *name)
+		.or_else(|| {
+			tokens
+				.iter()
+				.enumerate()
+				.filter(|(_, token)| matches!(token.kind, GLOBAL | EXTERN))
+				.flat_map(|(i, _)| declared_names(tokens, i))
+				.find(|&i| tokens[i].lexeme == *name)
+		})
+}
+
+/// Returns the column where the name starts, the name and the value
+/// of the preprocessor variable found at the given line and column,
+/// either used with `$` or defined by `@define` or `@macro`
+fn variable_at(document: &Document, line: usize, column: usize) -> Option<(usize, &Code, &PPVar)> {
+	let (start, word) = document.word_at(line, column)?;
+	let before = &document.line(line)[..start - 1];
+	let trimmed = before.trim_end();
+	if !(before.ends_with('$') || trimmed.ends_with("@define") || trimmed.ends_with("@macro")) {
+		return None;
+	}
+	let (name, value) = document
+		.variables
+		.get_key_value(&Code::from((word, line, start)))?;
+	Some((start, name, value))
+}
+
+/// Returns the LSP range of the declaration of the name found at the given line and column
+pub fn definition(document: &Document, line: usize, column: usize) -> Option<Value> {
+	if let Some((_, name, _)) = variable_at(document, line, column) {
+		let (_, line, column) = *name.iter().next()?;
+		return Some(document.range((line, column), (line, column + name.len())));
+	}
+	let target = document.token_at(line, column)?;
+	let declaration = resolve(&document.tokens, target)?;
+	Some(document.token_range(&document.tokens[declaration]))
+}
+
+/// Returns how a preprocessor variable is defined
+fn describe(name: &Code, value: &PPVar) -> String {
+	match value {
+		PPVar::Simple(value) | PPVar::ToProcess(value) => format!("@define {name} {value}"),
+		PPVar::Macro {
+			code, args, vararg, ..
+		} => {
+			let mut args: Vec<String> = args.iter().map(Code::to_string).collect();
+			if *vararg {
+				args.push(String::from("..."));
+			}
+			let body: String = code.0.iter().map(|(code, _)| code.to_string()).collect();
+			format!("@macro {name}({}) {{{body}}}", args.join(", "))
+		}
+		PPVar::VarArgs(_) => format!("{name} (variadic macro arguments)"),
+	}
+}
+
+/// Returns the length of the macro call arguments at the start of `text`,
+/// including the `!(` and `)`
+fn call_length(text: &str) -> Option<usize> {
+	if !text.starts_with("!(") {
+		return None;
+	}
+	let mut depth = 0usize;
+	let mut string = None;
+	let mut escaped = false;
+	for (i, c) in text.char_indices().skip(1) {
+		match (string, c) {
+			(Some(_), _) if escaped => escaped = false,
+			(Some(_), '\\') => escaped = true,
+			(Some(end), c) if c == end => string = None,
+			(Some(_), _) => {}
+			(None, '\'' | '"' | '`') => string = Some(c),
+			(None, '(') => depth += 1,
+			(None, ')') => {
+				depth -= 1;
+				if depth == 0 {
+					return Some(i + 1);
+				}
+			}
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Returns the LSP hover for the preprocessor variable found at the given line and column,
+/// showing its definition and, if it is being used, what it expands to
+pub fn hover(document: &Document, line: usize, column: usize) -> Option<Value> {
+	let (start, name, value) = variable_at(document, line, column)?;
+	let mut contents = format!("```clue\n{}\n```", describe(name, value));
+	if document.line(line)[..start - 1].ends_with('$') {
+		let dollar = document.offset(line, start - 1);
+		let mut end = dollar + 1 + name.len();
+		if let PPVar::Macro { .. } = value {
+			end += call_length(&document.text[end..]).unwrap_or(0);
+		}
+		let call = Code::from((&document.text[dollar..end], line, start - 1));
+		if let Ok(expansion) = preprocess_variables(
+			0,
+			&call,
+			call.len(),
+			&document.variables,
+			&document.filename,
+		) {
+			contents += &format!(
+				"\nexpands to\n```clue\n{}\n```",
+				expansion.to_string().trim()
+			);
+		}
+	}
+	Some(json!({
+		"contents": {
+			"kind": "markdown",
+			"value": contents,
+		},
+		"range": document.range((line, start), (line, start + name.len())),
+	}))
+}
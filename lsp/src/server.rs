@@ -0,0 +1,379 @@
+//! The language server itself, which keeps the open documents and answers the client
+
+use crate::{
+	completion::completion,
+	document::Document,
+	navigation::{definition, hover},
+	symbols::document_symbols,
+	transport::{read_message, write_message},
+};
+use ahash::AHashMap;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+// https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#errorCodes
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+type Response = Result<Value, (i32, String)>;
+
+struct Server<W: Write> {
+	output: W,
+	documents: AHashMap<String, Document>,
+	shutdown: bool,
+}
+
+/// Returns the file name used in diagnostics for the document at the given URI
+fn filename(uri: &str) -> String {
+	uri.strip_prefix("file://").unwrap_or(uri).to_owned()
+}
+
+impl<W: Write> Server<W> {
+	fn send(&mut self, message: Value) -> io::Result<()> {
+		write_message(&mut self.output, &message)
+	}
+
+	fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+		let diagnostics = self
+			.documents
+			.get(uri)
+			.map_or_else(Vec::new, Document::lsp_diagnostics);
+		self.send(json!({
+			"jsonrpc": "2.0",
+			"method": "textDocument/publishDiagnostics",
+			"params": {
+				"uri": uri,
+				"diagnostics": diagnostics,
+			},
+		}))
+	}
+
+	fn open(&mut self, uri: &str, text: String) -> io::Result<()> {
+		self.documents
+			.insert(uri.to_owned(), Document::new(text, filename(uri)));
+		self.publish_diagnostics(uri)
+	}
+
+	fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+		let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+		match method {
+			"textDocument/didOpen" => {
+				let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+				self.open(uri, text.to_owned())
+			}
+			"textDocument/didChange" => {
+				// the server asks for full synchronization, so the last change is the whole text
+				match params["contentChanges"]
+					.as_array()
+					.and_then(|changes| changes.last())
+					.and_then(|change| change["text"].as_str())
+				{
+					Some(text) => self.open(uri, text.to_owned()),
+					None => Ok(()),
+				}
+			}
+			"textDocument/didClose" => {
+				self.documents.remove(uri);
+				self.publish_diagnostics(uri)
+			}
+			_ => Ok(()),
+		}
+	}
+
+	/// Returns the document and the Clue line and column of the position in the request
+	fn document_position(
+		&self,
+		params: &Value,
+	) -> Result<(&Document, usize, usize), (i32, String)> {
+		let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+		let Some(document) = self.documents.get(uri) else {
+			return Err((INVALID_PARAMS, format!("Document '{uri}' is not open")));
+		};
+		let Some((line, column)) = document.location(&params["position"]) else {
+			return Err((INVALID_PARAMS, String::from("Invalid position")));
+		};
+		Ok((document, line, column))
+	}
+
+	fn request(&mut self, method: &str, params: &Value) -> Response {
+		if self.shutdown {
+			return Err((INVALID_REQUEST, String::from("The server was shut down")));
+		}
+		match method {
+			"initialize" => Ok(json!({
+				"capabilities": {
+					"textDocumentSync": 1,
+					"documentSymbolProvider": true,
+					"definitionProvider": true,
+					"hoverProvider": true,
+					"completionProvider": {
+						"triggerCharacters": ["@", "$"],
+					},
+				},
+				"serverInfo": {
+					"name": "clue-lsp",
+					"version": env!("CARGO_PKG_VERSION"),
+				},
+			})),
+			"shutdown" => {
+				self.shutdown = true;
+				Ok(Value::Null)
+			}
+			"textDocument/documentSymbol" => {
+				let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+				Ok(self
+					.documents
+					.get(uri)
+					.map_or_else(Vec::new, document_symbols)
+					.into())
+			}
+			"textDocument/definition" => {
+				let uri = &params["textDocument"]["uri"];
+				let (document, line, column) = self.document_position(params)?;
+				Ok(definition(document, line, column)
+					.map_or(Value::Null, |range| json!({"uri": uri, "range": range})))
+			}
+			"textDocument/hover" => {
+				let (document, line, column) = self.document_position(params)?;
+				Ok(hover(document, line, column).unwrap_or(Value::Null))
+			}
+			"textDocument/completion" => {
+				let (document, line, column) = self.document_position(params)?;
+				Ok(completion(document, line, column).into())
+			}
+			_ => Err((METHOD_NOT_FOUND, format!("Unknown method '{method}'"))),
+		}
+	}
+
+	fn respond(&mut self, id: Value, response: Response) -> io::Result<()> {
+		self.send(match response {
+			Ok(result) => json!({
+				"jsonrpc": "2.0",
+				"id": id,
+				"result": result,
+			}),
+			Err((code, message)) => json!({
+				"jsonrpc": "2.0",
+				"id": id,
+				"error": {
+					"code": code,
+					"message": message,
+				},
+			}),
+		})
+	}
+}
+
+/// Runs the language server, reading the messages of the client from `input` and writing the answers to `output`
+/// until the client sends the `exit` notification or closes the input
+///
+/// Returns `true` if the server was shut down properly before exiting
+///
+/// # Errors
+/// If reading or writing the messages fails an [`Err`] will be returned
+pub fn run(mut input: impl BufRead, output: impl Write) -> io::Result<bool> {
+	let mut server = Server {
+		output,
+		documents: AHashMap::default(),
+		shutdown: false,
+	};
+	while let Some(body) = read_message(&mut input)? {
+		let message: Value = match serde_json::from_slice(&body) {
+			Ok(message) => message,
+			Err(error) => {
+				server.respond(Value::Null, Err((PARSE_ERROR, error.to_string())))?;
+				continue;
+			}
+		};
+		let params = &message["params"];
+		match (message["method"].as_str(), message.get("id")) {
+			(Some("exit"), None) => return Ok(server.shutdown),
+			(Some(method), Some(id)) => {
+				let response = server.request(method, params);
+				server.respond(id.clone(), response)?;
+			}
+			(Some(method), None) => server.notification(method, params)?,
+			// responses to requests the server never sends
+			(None, _) => {}
+		}
+	}
+	Ok(server.shutdown)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::run;
+	use serde_json::{json, Value};
+	use std::io::Cursor;
+
+	/// Sends the given messages to a new server and returns every message it sent back
+	fn exchange(messages: &[Value]) -> Vec<Value> {
+		let mut input = Vec::new();
+		for message in messages {
+			let body = message.to_string();
+			input.extend(format!("Content-Length: {}\r\n\r\n{body}", body.len()).bytes());
+		}
+		let mut output = Vec::new();
+		run(Cursor::new(input), &mut output).unwrap();
+		let mut output = Cursor::new(output);
+		let mut messages = Vec::new();
+		while let Some(body) = crate::transport::read_message(&mut output).unwrap() {
+			messages.push(serde_json::from_slice(&body).unwrap());
+		}
+		messages
+	}
+
+	fn open(text: &str) -> Value {
+		json!({
+			"jsonrpc": "2.0",
+			"method": "textDocument/didOpen",
+			"params": {
+				"textDocument": {
+					"uri": "file:///test.clue",
+					"languageId": "clue",
+					"version": 1,
+					"text": text,
+				},
+			},
+		})
+	}
+
+	fn request(id: u32, method: &str, line: u32, character: u32) -> Value {
+		json!({
+			"jsonrpc": "2.0",
+			"id": id,
+			"method": method,
+			"params": {
+				"textDocument": {"uri": "file:///test.clue"},
+				"position": {"line": line, "character": character},
+			},
+		})
+	}
+
+	fn result(messages: &[Value], id: u32) -> &Value {
+		&messages
+			.iter()
+			.find(|message| message["id"] == id)
+			.expect("every request should be answered")["result"]
+	}
+
+	#[test]
+	fn lifecycle() {
+		let messages = exchange(&[
+			request(1, "initialize", 0, 0),
+			request(2, "shutdown", 0, 0),
+			json!({"jsonrpc": "2.0", "method": "exit"}),
+		]);
+		assert_eq!(result(&messages, 1)["capabilities"]["hoverProvider"], true);
+		assert_eq!(*result(&messages, 2), Value::Null);
+	}
+
+	#[test]
+	fn diagnostics() {
+		let messages = exchange(&[open("local x = 1\nlocal fn f( {\n}\nlocal y = = 2\n")]);
+		let diagnostics = &messages[0]["params"]["diagnostics"];
+		assert_eq!(diagnostics.as_array().unwrap().len(), 2);
+		assert_eq!(
+			diagnostics[0]["range"]["start"],
+			json!({"line": 1, "character": 12})
+		);
+		assert_eq!(diagnostics[0]["code"], "E0001");
+	}
+
+	#[test]
+	fn symbols() {
+		let messages = exchange(&[
+			open("local fn outer() {\n\tlocal fn inner() {}\n}\nmethod a.b::c() {}\nstatic s = 1\nglobal enum {A, B}\nlocal struct P { x = 0 }\n"),
+			json!({
+				"jsonrpc": "2.0",
+				"id": 1,
+				"method": "textDocument/documentSymbol",
+				"params": {"textDocument": {"uri": "file:///test.clue"}},
+			}),
+		]);
+		let symbols = result(&messages, 1);
+		let names: Vec<&Value> = symbols
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|s| &s["name"])
+			.collect();
+		assert_eq!(names, ["outer", "a.b:c", "s", "enum", "P"]);
+		assert_eq!(symbols[0]["children"][0]["name"], "inner");
+		assert_eq!(symbols[1]["selectionRange"]["start"]["character"], 12);
+		assert_eq!(symbols[3]["children"][1]["name"], "B");
+		assert_eq!(symbols[4]["children"][0]["name"], "x");
+	}
+
+	#[test]
+	fn definition() {
+		let messages = exchange(&[
+			open("local x = 1\nlocal fn f(x) {\n\treturn x\n}\nprint(x)\n@define VALUE 3\nprint($VALUE)\n"),
+			request(1, "textDocument/definition", 2, 8),
+			request(2, "textDocument/definition", 4, 6),
+			request(3, "textDocument/definition", 6, 8),
+		]);
+		let start = |id| &result(&messages, id)["range"]["start"];
+		assert_eq!(*start(1), json!({"line": 1, "character": 11}));
+		assert_eq!(*start(2), json!({"line": 0, "character": 6}));
+		assert_eq!(*start(3), json!({"line": 5, "character": 8}));
+	}
+
+	#[test]
+	fn hover() {
+		let messages = exchange(&[
+			open(include_str!("../../examples/macro.clue")),
+			request(1, "textDocument/hover", 13, 9),
+		]);
+		let contents = result(&messages, 1)["contents"]["value"].as_str().unwrap();
+		assert!(contents.starts_with("```clue\n@macro double(x)"));
+		assert!(contents.ends_with("expands to\n```clue\n4 * 2\n```"));
+	}
+
+	#[test]
+	fn non_ascii_definition() {
+		let messages = exchange(&[
+			open("local s = \"é\"\nlocal x = s\nprint(\"é\", x)\n"),
+			request(1, "textDocument/definition", 2, 11),
+			request(2, "textDocument/definition", 2, 8),
+		]);
+		let start = &result(&messages, 1)["range"]["start"];
+		assert_eq!(*start, json!({"line": 1, "character": 6}));
+		assert_eq!(*result(&messages, 2), Value::Null);
+	}
+
+	#[test]
+	fn non_ascii_hover() {
+		let messages = exchange(&[
+			open("@define V 2\nlocal s = \"é\" .. $V\n"),
+			request(1, "textDocument/hover", 1, 12),
+			request(2, "textDocument/hover", 1, 18),
+		]);
+		assert_eq!(*result(&messages, 1), Value::Null);
+		let contents = result(&messages, 2)["contents"]["value"].as_str().unwrap();
+		assert!(contents.starts_with("```clue\n@define V 2"));
+	}
+
+	#[test]
+	fn completion() {
+		let messages = exchange(&[
+			open("@define VALUE 1\nprint($)\n"),
+			request(1, "textDocument/completion", 0, 1),
+			request(2, "textDocument/completion", 1, 7),
+			request(3, "textDocument/completion", 1, 0),
+		]);
+		let labels = |id| -> Vec<String> {
+			result(&messages, id)
+				.as_array()
+				.unwrap()
+				.iter()
+				.map(|item| item["label"].as_str().unwrap().to_owned())
+				.collect()
+		};
+		assert!(labels(1).contains(&String::from("macro")));
+		assert_eq!(labels(2), ["VALUE"]);
+		assert!(labels(3).contains(&String::from("local")));
+	}
+}
@@ -0,0 +1,153 @@
+//! Document symbols, built from the [`SymbolTable`] of the document
+
+use crate::document::Document;
+use clue_core::{
+	diagnostic::Span,
+	symbols::{SymbolKind, SymbolTable},
+};
+use serde_json::{json, Value};
+
+// https://microsoft.github.io/language-server-protocol/specifications/lsp/3.17/specification/#symbolKind
+const MODULE_KIND: u8 = 2;
+const METHOD_KIND: u8 = 6;
+const FIELD_KIND: u8 = 8;
+const ENUM_KIND: u8 = 10;
+const FUNCTION_KIND: u8 = 12;
+const VARIABLE_KIND: u8 = 13;
+const CONSTANT_KIND: u8 = 14;
+const ENUM_MEMBER_KIND: u8 = 22;
+const STRUCT_KIND: u8 = 23;
+
+struct Symbol {
+	name: String,
+	kind: u8,
+	span: Span,
+	/// The line and column of the name
+	selection: (usize, usize),
+	children: Vec<Symbol>,
+}
+
+impl Symbol {
+	fn new(name: String, kind: u8, span: Span, selection: (usize, usize)) -> Self {
+		Self {
+			name,
+			kind,
+			span,
+			selection,
+			children: Vec::new(),
+		}
+	}
+
+	/// Checks if `other` is inside the declaration of the symbol
+	fn contains(&self, other: &Symbol) -> bool {
+		let (span, other) = (self.span, other.span);
+		(span.line, span.column) <= (other.line, other.column)
+			&& (other.end_line, other.end_column) <= (span.end_line, span.end_column)
+	}
+
+	fn to_json(&self, document: &Document) -> Value {
+		let (line, column) = self.selection;
+		// methods are named after the table they are stored in, only the last name is selected
+		let end = document
+			.word_at(line, column)
+			.map_or(column + self.name.len(), |(start, word)| start + word.len());
+		json!({
+			"name": self.name,
+			"kind": self.kind,
+			"range": document.range(
+				(self.span.line, self.span.column),
+				(self.span.end_line, self.span.end_column),
+			),
+			"selectionRange": document.range((line, column), (line, end)),
+			"children": self
+				.children
+				.iter()
+				.map(|child| child.to_json(document))
+				.collect::<Vec<_>>(),
+		})
+	}
+}
+
+/// Converts the symbols of the table, the values of an enum are put inside a symbol for the whole enum
+/// and the fields of a struct inside the struct
+fn convert(table: SymbolTable) -> Vec<Symbol> {
+	let mut symbols: Vec<Symbol> = Vec::new();
+	for symbol in table.symbols {
+		let selection = (symbol.line, symbol.column);
+		let kind = match symbol.kind {
+			SymbolKind::Function | SymbolKind::Macro => FUNCTION_KIND,
+			SymbolKind::Method => METHOD_KIND,
+			SymbolKind::Variable => VARIABLE_KIND,
+			SymbolKind::Struct => STRUCT_KIND,
+			SymbolKind::Define => CONSTANT_KIND,
+			SymbolKind::Import => MODULE_KIND,
+			SymbolKind::Enum => {
+				let span = Span::sized(symbol.line, symbol.column, symbol.name.len());
+				let value = Symbol::new(symbol.name, ENUM_MEMBER_KIND, span, selection);
+				// the values of an enum share the span of the whole enum
+				match symbols.last_mut() {
+					Some(last) if last.kind == ENUM_KIND && last.span == symbol.span => {
+						last.children.push(value)
+					}
+					_ => {
+						let name = String::from("enum");
+						let mut parent = Symbol::new(name, ENUM_KIND, symbol.span, selection);
+						parent.children.push(value);
+						symbols.push(parent);
+					}
+				}
+				continue;
+			}
+		};
+		let mut converted = Symbol::new(symbol.name, kind, symbol.span, selection);
+		if symbol.kind == SymbolKind::Struct {
+			for field in symbol.args.into_iter().flatten() {
+				let span = Span::sized(field.line, field.column, field.name.len());
+				let selection = (field.line, field.column);
+				converted
+					.children
+					.push(Symbol::new(field.name, FIELD_KIND, span, selection));
+			}
+		}
+		symbols.push(converted);
+	}
+	symbols
+}
+
+/// Nests the symbols that are inside the declaration of another symbol,
+/// `symbols` must be sorted by the start of their declaration
+fn nest(symbols: Vec<Symbol>) -> Vec<Symbol> {
+	let mut result: Vec<Symbol> = Vec::new();
+	let mut stack: Vec<Symbol> = Vec::new();
+	for symbol in symbols {
+		while let Some(parent) = stack.pop() {
+			if parent.contains(&symbol) {
+				stack.push(parent);
+				break;
+			}
+			match stack.last_mut() {
+				Some(grandparent) => grandparent.children.push(parent),
+				None => result.push(parent),
+			}
+		}
+		stack.push(symbol);
+	}
+	while let Some(symbol) = stack.pop() {
+		match stack.last_mut() {
+			Some(parent) => parent.children.push(symbol),
+			None => result.push(symbol),
+		}
+	}
+	result
+}
+
+/// Returns the LSP document symbols of the document
+pub fn document_symbols(document: &Document) -> Vec<Value> {
+	let table = SymbolTable::new(&document.filename, &document.tokens, &document.variables);
+	let mut symbols = convert(table);
+	symbols.sort_by_key(|symbol| (symbol.span.line, symbol.span.column));
+	nest(symbols)
+		.iter()
+		.map(|symbol| symbol.to_json(document))
+		.collect()
+}
@@ -0,0 +1,49 @@
+//! The base protocol of the Language Server Protocol:
+//! every message is a JSON-RPC object preceded by a `Content-Length` header
+
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+
+/// Reads the body of the next message
+/// Returns [`None`] if the input was closed
+///
+/// # Errors
+/// If the input could not be read or the message has no `Content-Length` header an [`Err`] will be returned
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+	let mut length = None;
+	let mut header = String::new();
+	loop {
+		header.clear();
+		if input.read_line(&mut header)? == 0 {
+			return Ok(None);
+		}
+		let header = header.trim_end();
+		if header.is_empty() {
+			break;
+		}
+		if let Some((name, value)) = header.split_once(':') {
+			if name.eq_ignore_ascii_case("Content-Length") {
+				length = value.trim().parse().ok();
+			}
+		}
+	}
+	let Some(length) = length else {
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			"Missing Content-Length header",
+		));
+	};
+	let mut body = vec![0; length];
+	input.read_exact(&mut body)?;
+	Ok(Some(body))
+}
+
+/// Writes a message to the output and flushes it
+///
+/// # Errors
+/// If the output could not be written an [`Err`] will be returned
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+	let body = message.to_string();
+	write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+	output.flush()
+}
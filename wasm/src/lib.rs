@@ -4,6 +4,7 @@
 
 use clue_core::{
 	code::Code,
+	diagnostic::Diagnostic,
 	env::{BitwiseMode, ContinueMode, LuaVersion},
 	parser::Expression,
 	scanner::Token,
@@ -17,6 +18,14 @@ pub fn get_version() -> String {
 	env!("CARGO_PKG_VERSION").to_string()
 }
 
+fn join_errors(errors: Vec<Diagnostic>) -> String {
+	errors
+		.iter()
+		.map(Diagnostic::to_string)
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
 /// The Clue WebAssembly API.
 /// uses the `clue_core` crate.
 #[wasm_bindgen]
@@ -145,7 +154,7 @@ impl Clue {
 	/// If the code is invalid then an error will be thrown
 	#[wasm_bindgen(js_name = "preprocessCode")]
 	pub fn preprocess_code(&self, code: String) -> Result<JsValue, String> {
-		let preprocessed = self.inner.preprocess_code(code)?;
+		let preprocessed = self.inner.preprocess_code(code).map_err(join_errors)?;
 		serde_wasm_bindgen::to_value(&preprocessed).map_err(|err| err.to_string())
 	}
 
@@ -157,7 +166,7 @@ impl Clue {
 	#[wasm_bindgen(js_name = "scanPreprocessed")]
 	pub fn scan_preprocessed(&self, code: JsValue) -> Result<JsValue, String> {
 		let code: Code = serde_wasm_bindgen::from_value(code).map_err(|err| err.to_string())?;
-		let scanned = self.inner.scan_preprocessed(code)?;
+		let scanned = self.inner.scan_preprocessed(code).map_err(join_errors)?;
 		serde_wasm_bindgen::to_value(&scanned).map_err(|err| err.to_string())
 	}
 
@@ -168,7 +177,7 @@ impl Clue {
 	/// If the code is invalid then an error will be thrown
 	#[wasm_bindgen(js_name = "scanCode")]
 	pub fn scan_code(&self, code: String) -> Result<JsValue, String> {
-		let scanned = self.inner.scan_code(code)?;
+		let scanned = self.inner.scan_code(code).map_err(join_errors)?;
 		serde_wasm_bindgen::to_value(&scanned).map_err(|err| err.to_string())
 	}
 
@@ -180,7 +189,7 @@ impl Clue {
 	#[wasm_bindgen(js_name = "parsePreprocessed")]
 	pub fn parse_preprocessed(&self, code: JsValue) -> Result<JsValue, String> {
 		let code: Code = serde_wasm_bindgen::from_value(code).map_err(|err| err.to_string())?;
-		let parsed = self.inner.parse_preprocessed(code)?;
+		let parsed = self.inner.parse_preprocessed(code).map_err(join_errors)?;
 		serde_wasm_bindgen::to_value(&parsed).map_err(|err| err.to_string())
 	}
 
@@ -193,7 +202,7 @@ impl Clue {
 	pub fn parse_tokens(&self, tokens: JsValue) -> Result<JsValue, String> {
 		let tokens: Vec<Token> =
 			serde_wasm_bindgen::from_value(tokens).map_err(|err| err.to_string())?;
-		let parsed = self.inner.parse_tokens(tokens)?;
+		let parsed = self.inner.parse_tokens(tokens).map_err(join_errors)?;
 		serde_wasm_bindgen::to_value(&parsed).map_err(|err| err.to_string())
 	}
 
@@ -204,7 +213,7 @@ impl Clue {
 	/// If the code is invalid then an error will be thrown
 	#[wasm_bindgen(js_name = "parseCode")]
 	pub fn parse_code(&self, code: String) -> Result<JsValue, String> {
-		let parsed = self.inner.parse_code(code)?;
+		let parsed = self.inner.parse_code(code).map_err(join_errors)?;
 		serde_wasm_bindgen::to_value(&parsed).map_err(|err| err.to_string())
 	}
 
@@ -217,7 +226,7 @@ impl Clue {
 	pub fn compile_preprocessed(&self, code: JsValue) -> Result<String, String> {
 		let code: Code = serde_wasm_bindgen::from_value(code).map_err(|err| err.to_string())?;
 
-		self.inner.compile_preprocessed(code)
+		self.inner.compile_preprocessed(code).map_err(join_errors)
 	}
 
 	/// Compiles the given tokens
@@ -230,7 +239,7 @@ impl Clue {
 		let tokens: Vec<Token> =
 			serde_wasm_bindgen::from_value(tokens).map_err(|err| err.to_string())?;
 
-		self.inner.compile_tokens(tokens)
+		self.inner.compile_tokens(tokens).map_err(join_errors)
 	}
 
 	/// Compiles the given parse result
@@ -243,7 +252,7 @@ impl Clue {
 		let parse_result: (Expression, String) =
 			serde_wasm_bindgen::from_value(parse_result).map_err(|err| err.to_string())?;
 
-		self.inner.compile_ast(parse_result)
+		self.inner.compile_ast(parse_result).map_err(join_errors)
 	}
 
 	/// Compiles the given code
@@ -253,7 +262,7 @@ impl Clue {
 	/// If the code is invalid then an error will be thrown
 	#[wasm_bindgen(js_name = "compileCode")]
 	pub fn compile_code(&self, code: String) -> Result<String, String> {
-		self.inner.compile_code(code)
+		self.inner.compile_code(code).map_err(join_errors)
 	}
 }
 